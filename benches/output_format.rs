@@ -0,0 +1,139 @@
+//! Compare the TOML and CBOR output formats on a large styled sheet: conversion time in the
+//! plugin, output size and, when the `typst` CLI is on the PATH, decoding time in Typst.
+//!
+//! Run with `cargo bench --bench output_format`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use ciborium::Value;
+use umya_spreadsheet::Border;
+use xlsx_parser_rs::to_typst_v2;
+
+const ROWS: u32 = 5_000;
+const COLUMNS: u32 = 12;
+const RUNS: u32 = 3;
+
+/// A workbook with a merged title, a bold header and bordered, filled and number formatted body
+/// cells, so that the output carries styles, borders, merges and typed values.
+fn workbook() -> Vec<u8> {
+    let mut book = umya_spreadsheet::new_file();
+    let sheet = book.get_sheet_mut(&0).unwrap();
+    sheet.add_merge_cells("A1:L1");
+    sheet.get_cell_mut((1, 1)).set_value("Quarterly figures");
+    sheet.get_style_mut((1, 1)).get_font_mut().set_bold(true);
+    for col_num in 1..=COLUMNS {
+        let cell = sheet.get_cell_mut((col_num, 2));
+        cell.set_value(format!("Column {}", col_num));
+        let style = sheet.get_style_mut((col_num, 2));
+        style.get_font_mut().set_bold(true);
+        style.set_background_color("FFD9E1F2");
+        style
+            .get_borders_mut()
+            .get_bottom_mut()
+            .set_border_style(Border::BORDER_DOUBLE);
+    }
+    for row_num in 3..=ROWS + 2 {
+        for col_num in 1..=COLUMNS {
+            let cell = sheet.get_cell_mut((col_num, row_num));
+            if col_num == 1 {
+                cell.set_value(format!("Item {}", row_num - 2));
+            } else {
+                cell.set_value_number(f64::from(row_num * col_num) / 7.0);
+            }
+            let style = sheet.get_style_mut((col_num, row_num));
+            style
+                .get_borders_mut()
+                .get_bottom_mut()
+                .set_border_style(Border::BORDER_THIN);
+            if col_num > 1 {
+                style.get_number_format_mut().set_format_code("#,##0.00");
+            }
+            if row_num % 2 == 0 {
+                style.set_background_color("FFF2F2F2");
+            }
+        }
+    }
+    let mut bytes = Cursor::new(Vec::new());
+    umya_spreadsheet::writer::xlsx::write_writer(&book, &mut bytes).unwrap();
+    bytes.into_inner()
+}
+
+/// Options of the `styled` preset of the package with the given output format.
+fn options(format: &str) -> Vec<u8> {
+    let options: BTreeMap<&str, Value> = [
+        ("output_format", Value::Text(format.to_string())),
+        ("parse_alignment", Value::Bool(true)),
+        ("parse_border", Value::Bool(true)),
+        ("parse_bg_color", Value::Bool(true)),
+        ("parse_font_style", Value::Bool(true)),
+        ("typed_values", Value::Bool(true)),
+    ]
+    .into_iter()
+    .collect();
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&options, &mut bytes).unwrap();
+    bytes
+}
+
+/// Average time of `RUNS` runs of `run`, with the result of the last run.
+fn time<T>(mut run: impl FnMut() -> T) -> (Duration, T) {
+    let start = Instant::now();
+    let mut result = run();
+    for _ in 1..RUNS {
+        result = run();
+    }
+    (start.elapsed() / RUNS, result)
+}
+
+/// Average time `typst` takes to compile a document decoding `data` with `decoder`, minus the
+/// time of one that only reads it. `None` when `typst` isn't available.
+fn typst_decode(dir: &Path, data: &str, decoder: &str) -> Option<Duration> {
+    let compile = |source: String| -> Option<Duration> {
+        let input = dir.join("decode.typ");
+        fs::write(&input, source).ok()?;
+        let (elapsed, status) = time(|| {
+            Command::new("typst")
+                .arg("compile")
+                .arg(&input)
+                .arg(dir.join("decode.pdf"))
+                .status()
+        });
+        status.ok()?.success().then_some(elapsed)
+    };
+    let read = format!("#let data = read(\"{}\", encoding: none)\n", data);
+    let baseline = compile(format!("{}#data.len()", read))?;
+    let decoded = compile(format!("{}#{}(data).rows.len()", read, decoder))?;
+    Some(decoded.saturating_sub(baseline))
+}
+
+fn main() {
+    let bytes = workbook();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("output_format");
+    fs::create_dir_all(&dir).unwrap();
+
+    println!(
+        "{} rows x {} columns, average of {} runs",
+        ROWS, COLUMNS, RUNS
+    );
+    println!("format  convert     size        typst decode");
+    for (format, decoder) in [("toml", "toml"), ("cbor", "cbor")] {
+        let options = options(format);
+        let (convert, output) = time(|| to_typst_v2(&bytes, &options).unwrap());
+        let data = format!("sheet.{}", format);
+        fs::write(dir.join(&data), &output).unwrap();
+        let decode = typst_decode(&dir, &data, decoder)
+            .map_or("no typst".to_string(), |decode| format!("{:.1?}", decode));
+        println!(
+            "{:<8}{:<12}{:<12}{}",
+            format,
+            format!("{:.1?}", convert),
+            format!("{:.1} MiB", output.len() as f64 / (1024.0 * 1024.0)),
+            decode
+        );
+    }
+}
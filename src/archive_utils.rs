@@ -0,0 +1,184 @@
+use std::io::{self, Cursor, Read};
+
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::xml_utils::{parse_xml, XmlNode};
+
+pub struct Relationship {
+    pub id: String,
+    pub rel_type: String,
+    /// Target part path, resolved relative to the archive root; external targets are kept as is.
+    pub target: String,
+}
+
+/// Limits checked when opening an archive, so a zip bomb fails instead of exhausting memory.
+pub struct ArchiveLimits {
+    /// Total decompressed size of all parts in bytes.
+    pub max_decompressed_size: u64,
+    pub max_parts: usize,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        ArchiveLimits {
+            max_decompressed_size: 512 * 1024 * 1024,
+            max_parts: 10_000,
+        }
+    }
+}
+
+/// Raw access to the parts of an xlsx archive.
+pub struct Archive<'a> {
+    zip: ZipArchive<Cursor<&'a [u8]>>,
+}
+
+impl<'a> Archive<'a> {
+    pub fn new(bytes: &'a [u8], limits: &ArchiveLimits) -> Result<Self, String> {
+        let mut zip = ZipArchive::new(Cursor::new(bytes))
+            .map_err(|e| format!("Failed to open xlsx archive: {}", e))?;
+        if zip.len() > limits.max_parts {
+            return Err(format!(
+                "Failed to open xlsx archive: {} parts exceed the limit of {}",
+                zip.len(),
+                limits.max_parts
+            ));
+        }
+        // 头部记录的大小可能被伪造，逐个解压到 sink 统计实际大小
+        let mut total: u64 = 0;
+        for index in 0..zip.len() {
+            let file = zip
+                .by_index(index)
+                .map_err(|e| format!("Failed to open xlsx archive: {}", e))?;
+            let name = file.name().to_string();
+            let remaining = limits.max_decompressed_size.saturating_sub(total);
+            total += io::copy(&mut file.take(remaining + 1), &mut io::sink())
+                .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            if total > limits.max_decompressed_size {
+                return Err(format!(
+                    "Failed to open xlsx archive: decompressed size exceeds the limit of {} bytes",
+                    limits.max_decompressed_size
+                ));
+            }
+        }
+        Ok(Archive { zip })
+    }
+
+    pub fn read_part(&mut self, path: &str) -> Result<Option<String>, String> {
+        let mut file = match self.zip.by_name(path.trim_start_matches('/')) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+        };
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        Ok(Some(content))
+    }
+
+    pub fn has_part(&mut self, path: &str) -> bool {
+        self.zip.by_name(path.trim_start_matches('/')).is_ok()
+    }
+
+    /// Raw bytes of a binary part, e.g. the records of an xlsb workbook.
+    pub fn read_part_bytes(&mut self, path: &str) -> Result<Option<Vec<u8>>, String> {
+        let mut file = match self.zip.by_name(path.trim_start_matches('/')) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+        };
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        Ok(Some(content))
+    }
+
+    pub fn read_xml(&mut self, path: &str) -> Result<Option<XmlNode>, String> {
+        match self.read_part(path)? {
+            Some(content) => parse_xml(&content).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Relationships declared for a part, e.g. `xl/workbook.xml` -> `xl/_rels/workbook.xml.rels`.
+    pub fn relationships(&mut self, part: &str) -> Result<Vec<Relationship>, String> {
+        let (dir, file) = split_part_path(part);
+        let rels_path = if dir.is_empty() {
+            format!("_rels/{}.rels", file)
+        } else {
+            format!("{}/_rels/{}.rels", dir, file)
+        };
+        let rels = match self.read_xml(&rels_path)? {
+            Some(rels) => rels,
+            None => return Ok(Vec::new()),
+        };
+        Ok(rels
+            .children_named("Relationship")
+            .map(|rel| Relationship {
+                id: rel.attr("Id").unwrap_or_default().to_string(),
+                rel_type: rel.attr("Type").unwrap_or_default().to_string(),
+                // 外部链接（如超链接地址）保持原样，不按包内路径解析
+                target: if rel.attr("TargetMode") == Some("External") {
+                    rel.attr("Target").unwrap_or_default().to_string()
+                } else {
+                    resolve_target(dir, rel.attr("Target").unwrap_or_default())
+                },
+            })
+            .collect())
+    }
+
+    /// Path of the worksheet part at `sheet_index`, in workbook order.
+    pub fn sheet_path(&mut self, sheet_index: usize) -> Result<Option<String>, String> {
+        let workbook = match self.read_xml("xl/workbook.xml")? {
+            Some(workbook) => workbook,
+            None => return Ok(None),
+        };
+        let rel_id = match workbook
+            .child("sheets")
+            .and_then(|sheets| sheets.children_named("sheet").nth(sheet_index))
+            .and_then(|sheet| sheet.attr("r:id"))
+        {
+            Some(rel_id) => rel_id.to_string(),
+            None => return Ok(None),
+        };
+        Ok(self
+            .relationships("xl/workbook.xml")?
+            .into_iter()
+            .find(|rel| rel.id == rel_id)
+            .map(|rel| rel.target))
+    }
+
+    pub fn read_sheet_xml(&mut self, sheet_index: usize) -> Result<XmlNode, String> {
+        let path = self
+            .sheet_path(sheet_index)?
+            .ok_or_else(|| "Failed to get worksheet".to_string())?;
+        self.read_xml(&path)?
+            .ok_or_else(|| format!("Failed to read worksheet part {}", path))
+    }
+}
+
+fn split_part_path(part: &str) -> (&str, &str) {
+    let part = part.trim_start_matches('/');
+    match part.rfind('/') {
+        Some(index) => (&part[..index], &part[index + 1..]),
+        None => ("", part),
+    }
+}
+
+fn resolve_target(dir: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = if target.starts_with('/') {
+        Vec::new()
+    } else {
+        dir.split('/').filter(|s| !s.is_empty()).collect()
+    };
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
@@ -0,0 +1,436 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::data_structures::{Border, BorderStrokes, CellData, CellStyle, Stroke, TableData};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+fn has_side(border: &Border, side: Side) -> bool {
+    match side {
+        Side::Left => border.left,
+        Side::Right => border.right,
+        Side::Top => border.top,
+        Side::Bottom => border.bottom,
+    }
+}
+
+fn side_stroke(border: &Border, side: Side) -> Option<&Stroke> {
+    let strokes = border.strokes.as_ref()?;
+    match side {
+        Side::Left => strokes.left.as_ref(),
+        Side::Right => strokes.right.as_ref(),
+        Side::Top => strokes.top.as_ref(),
+        Side::Bottom => strokes.bottom.as_ref(),
+    }
+}
+
+fn set_side(border: &mut Border, side: Side, present: bool, stroke: Option<Stroke>) {
+    match side {
+        Side::Left => border.left = present,
+        Side::Right => border.right = present,
+        Side::Top => border.top = present,
+        Side::Bottom => border.bottom = present,
+    }
+    let strokes = border.strokes.get_or_insert_with(BorderStrokes::default);
+    match side {
+        Side::Left => strokes.left = stroke,
+        Side::Right => strokes.right = stroke,
+        Side::Top => strokes.top = stroke,
+        Side::Bottom => strokes.bottom = stroke,
+    }
+    if *strokes == BorderStrokes::default() {
+        border.strokes = None;
+    }
+}
+
+fn clear_side(border: &mut Border, side: Side) {
+    set_side(border, side, false, None);
+}
+
+/// Excel's precedence of border styles, heavier styles win when adjacent edges disagree.
+fn edge_weight(present: bool, stroke: Option<&Stroke>) -> u32 {
+    if !present {
+        return 0;
+    }
+    match stroke.map(|stroke| stroke.style.as_str()) {
+        Some("hair") => 1,
+        Some("dotted") => 2,
+        Some("dashDotDot") => 3,
+        Some("dashDot") => 4,
+        Some("dashed") => 5,
+        // 没有描述的边框为普通实线
+        None | Some("thin") => 6,
+        Some("mediumDashDotDot") => 7,
+        Some("slantDashDot") => 8,
+        Some("mediumDashDot") => 9,
+        Some("mediumDashed") => 10,
+        Some("medium") => 11,
+        Some("thick") => 12,
+        Some("double") => 13,
+        Some(_) => 6,
+    }
+}
+
+fn cell_border(cell: &CellData) -> Option<&Border> {
+    cell.style.as_ref().and_then(|style| style.border.as_ref())
+}
+
+fn uniform_border<'a>(borders: impl Iterator<Item = Option<&'a Border>>) -> Option<&'a Border> {
+    let mut uniform: Option<&Border> = None;
+    for border in borders {
+        let border = border?;
+        match uniform {
+            Some(first) if first != border => return None,
+            _ => uniform = Some(border),
+        }
+    }
+    uniform
+}
+
+/// The start cell of the merged range covering each merged position.
+fn merge_owners(table_data: &TableData) -> HashMap<(u32, u32), (u32, u32)> {
+    let mut owners = HashMap::new();
+    for mc in &table_data.merged_cells {
+        for row in mc.start.row..=mc.end.row {
+            for col in mc.start.column..=mc.end.column {
+                owners.insert((row, col), (mc.start.row, mc.start.column));
+            }
+        }
+    }
+    owners
+}
+
+/// Positions hidden by merged cells, i.e. every merged position except the start cell.
+pub fn covered_positions(table_data: &TableData) -> HashSet<(u32, u32)> {
+    let mut covered = HashSet::new();
+    for mc in &table_data.merged_cells {
+        for row in mc.start.row..=mc.end.row {
+            for col in mc.start.column..=mc.end.column {
+                if !(row == mc.start.row && col == mc.start.column) {
+                    covered.insert((row, col));
+                }
+            }
+        }
+    }
+    covered
+}
+
+/// Move borders shared by every cell of a row or column into `row_borders` / `column_borders`,
+/// removing them from the cells.
+pub fn collapse_borders(table_data: &mut TableData) {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    let covered = covered_positions(table_data);
+
+    let mut cells: HashMap<(u32, u32), &CellData> = HashMap::new();
+    for row in &table_data.rows {
+        for cell in &row.cells {
+            cells.insert((row.row_number, cell.column), cell);
+        }
+    }
+
+    // 整行（或整列）所有单元格的边框都一致时才合并
+    let mut row_borders = BTreeMap::new();
+    for row_num in 1..=max_row {
+        let border = uniform_border(
+            (1..=max_col)
+                .filter(|col_num| !covered.contains(&(row_num, *col_num)))
+                .map(|col_num| cells.get(&(row_num, col_num)).and_then(|c| cell_border(c))),
+        );
+        if let Some(border) = border {
+            row_borders.insert(row_num, border.clone());
+        }
+    }
+    let mut column_borders = BTreeMap::new();
+    for col_num in 1..=max_col {
+        let border = uniform_border(
+            (1..=max_row)
+                .filter(|row_num| !covered.contains(&(*row_num, col_num)))
+                .map(|row_num| cells.get(&(row_num, col_num)).and_then(|c| cell_border(c))),
+        );
+        if let Some(border) = border {
+            column_borders.insert(col_num, border.clone());
+        }
+    }
+
+    for row in &mut table_data.rows {
+        for cell in &mut row.cells {
+            if row_borders.contains_key(&row.row_number) || column_borders.contains_key(&cell.column) {
+                if let Some(style) = cell.style.as_mut() {
+                    style.border = None;
+                }
+            }
+        }
+    }
+
+    table_data.row_borders = row_borders
+        .into_iter()
+        .map(|(row_num, border)| (row_num.to_string(), border))
+        .collect();
+    table_data.column_borders = column_borders
+        .into_iter()
+        .map(|(col_num, border)| (col_num.to_string(), border))
+        .collect();
+}
+
+/// Detect a border running around the whole used range and move it into `frame`,
+/// leaving only the inner borders on the cells.
+pub fn detect_frame(table_data: &mut TableData) {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    if max_col == 0 || max_row == 0 {
+        return;
+    }
+
+    // 被合并的位置归属于合并区域的起始单元格
+    let owners = merge_owners(table_data);
+    let owner_of = |pos: (u32, u32)| owners.get(&pos).copied().unwrap_or(pos);
+
+    let mut edges: HashMap<(u32, u32), Vec<Side>> = HashMap::new();
+    for col in 1..=max_col {
+        edges.entry(owner_of((1, col))).or_default().push(Side::Top);
+        edges.entry(owner_of((max_row, col))).or_default().push(Side::Bottom);
+    }
+    for row in 1..=max_row {
+        edges.entry(owner_of((row, 1))).or_default().push(Side::Left);
+        edges.entry(owner_of((row, max_col))).or_default().push(Side::Right);
+    }
+
+    // 外框的每一段都必须存在，且线型一致
+    let frame_stroke = {
+        let mut cells: HashMap<(u32, u32), &CellData> = HashMap::new();
+        for row in &table_data.rows {
+            for cell in &row.cells {
+                cells.insert((row.row_number, cell.column), cell);
+            }
+        }
+        let mut frame_stroke: Option<Option<Stroke>> = None;
+        for (pos, sides) in &edges {
+            let border = match cells.get(pos).and_then(|cell| cell_border(cell)) {
+                Some(border) => border,
+                None => return,
+            };
+            for side in sides {
+                if !has_side(border, *side) {
+                    return;
+                }
+                let stroke = side_stroke(border, *side).cloned();
+                match &frame_stroke {
+                    Some(first) if *first != stroke => return,
+                    _ => frame_stroke = Some(stroke),
+                }
+            }
+        }
+        frame_stroke.flatten()
+    };
+
+    for row in &mut table_data.rows {
+        for cell in &mut row.cells {
+            let sides = match edges.get(&(row.row_number, cell.column)) {
+                Some(sides) => sides,
+                None => continue,
+            };
+            if let Some(border) = cell.style.as_mut().and_then(|style| style.border.as_mut()) {
+                for side in sides {
+                    clear_side(border, *side);
+                }
+            }
+        }
+    }
+
+    table_data.frame = Some(Border {
+        left: true,
+        right: true,
+        top: true,
+        bottom: true,
+        diagonal_up: false,
+        diagonal_down: false,
+        stroke: None,
+        strokes: frame_stroke.map(|stroke| BorderStrokes {
+            left: Some(stroke.clone()),
+            right: Some(stroke.clone()),
+            top: Some(stroke.clone()),
+            bottom: Some(stroke),
+            diagonal: None,
+        }),
+    });
+}
+
+/// Give the cell sides without a border the hairline Excel shows as gridline, when the sheet
+/// displays gridlines. The stroke is also kept in `gridline` for the positions without a cell.
+pub fn add_gridlines(table_data: &mut TableData) {
+    if table_data.show_gridlines != Some(true) {
+        return;
+    }
+    let gridline = Stroke {
+        style: "hair".to_string(),
+        thickness: 0.25,
+        gap: None,
+        dash: None,
+        color: Some("D4D4D4".to_string()),
+    };
+    let styles = table_data
+        .rows
+        .iter_mut()
+        .flat_map(|row| row.cells.iter_mut())
+        .filter_map(|cell| cell.style.as_mut());
+    for style in styles {
+        let border = style.border.get_or_insert_with(Border::default);
+        for side in [Side::Left, Side::Right, Side::Top, Side::Bottom] {
+            if !has_side(border, side) {
+                set_side(border, side, true, Some(gridline.clone()));
+            }
+        }
+    }
+    table_data.gridline = Some(gridline);
+}
+
+/// A stroke descriptor as a Typst stroke, e.g. `(thickness: 0.75pt, dash: (3pt, 1.5pt))`.
+/// `vertical` tells the double lines of left and right sides apart from top and bottom ones.
+fn typst_stroke(stroke: &Stroke, vertical: bool) -> String {
+    if let (Some(gap), "double") = (stroke.gap, stroke.style.as_str()) {
+        // 双线画成两条线加间隙宽的线，用渐变把中间的间隙画成透明，间隙两端留 1% 过渡以免 PNG 输出错乱
+        let paint = match &stroke.color {
+            Some(color) => format!("rgb(\"#{}\")", color),
+            None => "black".to_string(),
+        };
+        let clear = format!("{}.transparentize(100%)", paint);
+        let thickness = stroke.thickness * 2.0 + gap;
+        let inner = stroke.thickness / thickness * 100.0;
+        let outer = (stroke.thickness + gap) / thickness * 100.0;
+        return format!(
+            "(thickness: {}pt, paint: gradient.linear(({p}, 0%), ({p}, {i}%), ({c}, {s}%), ({c}, {e}%), ({p}, {o}%), ({p}, 100%), angle: {a}deg, relative: \"self\"))",
+            thickness,
+            p = paint,
+            c = clear,
+            i = inner,
+            s = inner + 1.0,
+            e = outer - 1.0,
+            o = outer,
+            a = if vertical { 0 } else { 90 },
+        );
+    }
+    let mut args = vec![format!("thickness: {}pt", stroke.thickness)];
+    if let Some(dash) = &stroke.dash {
+        let lengths: Vec<String> = dash.iter().map(|length| format!("{}pt", length)).collect();
+        args.push(format!("dash: ({},)", lengths.join(", ")));
+    }
+    if let Some(color) = &stroke.color {
+        args.push(format!("paint: rgb(\"#{}\")", color));
+    }
+    format!("({})", args.join(", "))
+}
+
+/// The sides of a border as a Typst stroke dictionary. Drawn sides without a descriptor are left
+/// out, so they keep the stroke of the table.
+pub fn stroke_expression(border: &Border) -> String {
+    let sides = [
+        ("left", Side::Left),
+        ("right", Side::Right),
+        ("top", Side::Top),
+        ("bottom", Side::Bottom),
+    ];
+    let entries: Vec<String> = sides
+        .iter()
+        .filter_map(|(name, side)| match (has_side(border, *side), side_stroke(border, *side)) {
+            (false, _) => Some(format!("{}: none", name)),
+            (true, Some(stroke)) => Some(format!(
+                "{}: {}",
+                name,
+                typst_stroke(stroke, matches!(side, Side::Left | Side::Right))
+            )),
+            (true, None) => None,
+        })
+        .collect();
+    if entries.is_empty() {
+        "(:)".to_string()
+    } else {
+        format!("({})", entries.join(", "))
+    }
+}
+
+/// Set `stroke` on every cell border, the collapsed row and column borders and the frame.
+pub fn add_stroke_expressions(table_data: &mut TableData) {
+    let borders = table_data
+        .rows
+        .iter_mut()
+        .flat_map(|row| row.cells.iter_mut())
+        .filter_map(|cell| cell.style.as_mut())
+        .filter_map(|style| style.border.as_mut())
+        .chain(table_data.row_borders.values_mut())
+        .chain(table_data.column_borders.values_mut())
+        .chain(table_data.frame.as_mut());
+    for border in borders {
+        border.stroke = Some(stroke_expression(border));
+    }
+}
+
+/// Make the shared edge of adjacent cells consistent, keeping the heavier of two disagreeing borders.
+pub fn resolve_border_conflicts(table_data: &mut TableData) {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    let owners = merge_owners(table_data);
+    let owner_of = |pos: (u32, u32)| owners.get(&pos).copied().unwrap_or(pos);
+
+    let mut index: HashMap<(u32, u32), (usize, usize)> = HashMap::new();
+    for (i, row) in table_data.rows.iter().enumerate() {
+        for (j, cell) in row.cells.iter().enumerate() {
+            index.insert((row.row_number, cell.column), (i, j));
+        }
+    }
+
+    // 相邻单元格的共享边：左侧单元格的右边与右侧单元格的左边，上方单元格的下边与下方单元格的上边
+    let mut pairs = Vec::new();
+    for row in 1..=max_row {
+        for col in 1..=max_col {
+            let pos = owner_of((row, col));
+            if col < max_col {
+                pairs.push((pos, Side::Right, owner_of((row, col + 1)), Side::Left));
+            }
+            if row < max_row {
+                pairs.push((pos, Side::Bottom, owner_of((row + 1, col)), Side::Top));
+            }
+        }
+    }
+
+    for (a, a_side, b, b_side) in pairs {
+        if a == b {
+            continue;
+        }
+        let (a, b) = match (index.get(&a), index.get(&b)) {
+            (Some(a), Some(b)) => (*a, *b),
+            _ => continue,
+        };
+        let edge = |(i, j): (usize, usize), side: Side| -> (bool, Option<Stroke>) {
+            match cell_border(&table_data.rows[i].cells[j]) {
+                Some(border) => (has_side(border, side), side_stroke(border, side).cloned()),
+                None => (false, None),
+            }
+        };
+        let a_edge = edge(a, a_side);
+        let b_edge = edge(b, b_side);
+        if a_edge == b_edge {
+            continue;
+        }
+        let (present, stroke) = if edge_weight(a_edge.0, a_edge.1.as_ref())
+            >= edge_weight(b_edge.0, b_edge.1.as_ref())
+        {
+            a_edge
+        } else {
+            b_edge
+        };
+        for ((i, j), side) in [(a, a_side), (b, b_side)] {
+            let border = table_data.rows[i].cells[j]
+                .style
+                .get_or_insert_with(CellStyle::default)
+                .border
+                .get_or_insert_with(Border::default);
+            set_side(border, side, present, stroke.clone());
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Seek};
+
+use calamine::{Data, Dimensions, Reader, Xls, Xlsb};
+
+use crate::archive_utils::Archive;
+use crate::data_structures::{
+    CellData, MergedCell, PluginError, Position, RowData, TableData, TypedValue,
+};
+use crate::options::ParseOptions;
+use crate::utils::number_to_column;
+
+/// Default column width of Excel in characters, used since calamine doesn't read column widths.
+pub const EXCEL_DEFAULT_COLUMN_WIDTH: f64 = 8.43;
+/// Signature of OLE2 compound files, the container of legacy .xls workbooks.
+const OLE_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+pub fn is_xls(bytes: &[u8]) -> bool {
+    bytes.starts_with(&OLE_SIGNATURE)
+}
+
+fn data_value(
+    value: &Data,
+    row_num: u32,
+    col_num: u32,
+    options: &ParseOptions,
+) -> Result<String, PluginError> {
+    Ok(match value {
+        Data::Int(value) => value.to_string(),
+        Data::Float(value) => value.to_string(),
+        Data::String(value) => value.replace("\r\n", "\n"),
+        Data::Bool(value) => if *value { "TRUE" } else { "FALSE" }.to_string(),
+        // 与 xlsx 一致，日期输出为序列号
+        Data::DateTime(value) => value.as_f64().to_string(),
+        Data::DateTimeIso(value) | Data::DurationIso(value) => value.clone(),
+        Data::Error(error) => {
+            let coordinate = format!("{}{}", number_to_column(col_num), row_num);
+            return options.error_text(&error.to_string()).ok_or_else(|| {
+                PluginError::new(
+                    "error_cell",
+                    format!("Error in cell {}: {}", coordinate, error),
+                )
+                .at_cell(coordinate)
+            });
+        }
+        Data::Empty => String::new(),
+    })
+}
+
+fn data_kind(value: &Data) -> &'static str {
+    match value {
+        Data::Int(_) | Data::Float(_) => "number",
+        Data::String(_) | Data::DurationIso(_) => "string",
+        Data::Bool(_) => "bool",
+        Data::DateTime(_) | Data::DateTimeIso(_) => "date",
+        Data::Error(_) => "error",
+        Data::Empty => "empty",
+    }
+}
+
+fn sheet_name<RS: Read + Seek, R: Reader<RS>>(
+    workbook: &R,
+    sheet_index: usize,
+) -> Result<String, String> {
+    workbook
+        .sheet_names()
+        .get(sheet_index)
+        .cloned()
+        .ok_or_else(|| "Failed to get worksheet".to_string())
+}
+
+/// Read the values of a sheet through calamine, for the formats umya-spreadsheet can't open.
+/// Styles and column widths are not available; merges are passed in by the format-specific reader.
+fn read_calamine_table<RS: Read + Seek, R: Reader<RS>>(
+    workbook: &mut R,
+    name: &str,
+    merges: &[Dimensions],
+    options: &ParseOptions,
+) -> Result<(TableData, HashSet<u32>), PluginError> {
+    let range = workbook
+        .worksheet_range(name)
+        .map_err(|e| format!("Failed to read worksheet: {:?}", e))?;
+    let (start_row, start_col) = range.start().unwrap_or((0, 0));
+    let (end_row, end_col) = range.end().unwrap_or((0, 0));
+    let (max_row, max_col) = if range.is_empty() {
+        (0, 0)
+    } else {
+        (end_row + 1, end_col + 1)
+    };
+
+    let merged_cells: Vec<MergedCell> = merges
+        .iter()
+        .map(|merge| {
+            let start = Position {
+                row: merge.start.0 + 1,
+                column: merge.start.1 + 1,
+            };
+            let end = Position {
+                row: merge.end.0 + 1,
+                column: merge.end.1 + 1,
+            };
+            MergedCell::new(start, end)
+        })
+        .collect();
+
+    // 公式按位置记下，同时标记含 SUM 公式的行
+    let mut formulas = HashMap::new();
+    let mut sum_rows = HashSet::new();
+    if let Ok(range) = workbook.worksheet_formula(name) {
+        let (formula_row, formula_col) = range.start().unwrap_or((0, 0));
+        for (row, col, formula) in range.used_cells() {
+            let row_num = formula_row + row as u32 + 1;
+            let upper = formula.to_ascii_uppercase();
+            if upper.contains("SUM(") || upper.contains("SUBTOTAL(") {
+                sum_rows.insert(row_num);
+            }
+            formulas.insert((row_num, formula_col + col as u32 + 1), format!("={}", formula));
+        }
+    }
+
+    let mut rows: Vec<RowData> = Vec::new();
+    let mut error_cells = Vec::new();
+    for (row, col, value) in range.used_cells() {
+        let row_num = start_row + row as u32 + 1;
+        let col_num = start_col + col as u32 + 1;
+        let formula = formulas.remove(&(row_num, col_num));
+        // 被合并的单元格不输出
+        let is_merged = merged_cells.iter().any(|mc| {
+            (mc.start.row..=mc.end.row).contains(&row_num)
+                && (mc.start.column..=mc.end.column).contains(&col_num)
+                && !(row_num == mc.start.row && col_num == mc.start.column)
+        });
+        if is_merged {
+            continue;
+        }
+        if matches!(value, Data::Error(_)) {
+            error_cells.push(format!("{}{}", number_to_column(col_num), row_num));
+        }
+        let kind = data_kind(value);
+        let value = data_value(value, row_num, col_num, options)?;
+        let cell = CellData {
+            typed: TypedValue::from_kind(kind, &value),
+            kind: Some(kind.to_string()),
+            value,
+            column: col_num,
+            formula,
+            ..Default::default()
+        };
+        match rows.last_mut() {
+            Some(last) if last.row_number == row_num => last.cells.push(cell),
+            _ => rows.push(RowData {
+                row_number: row_num,
+                cells: vec![cell],
+            }),
+        }
+    }
+
+    // 剩下的公式没有对应的值，即没有缓存结果
+    let mut missing_results: Vec<(u32, u32)> = formulas.into_keys().collect();
+    missing_results.sort();
+    let missing_results: Vec<String> = missing_results
+        .into_iter()
+        .map(|(row_num, col_num)| format!("{}{}", number_to_column(col_num), row_num))
+        .collect();
+
+    let mut table_data = TableData::new(max_col, max_row);
+    table_data.dimensions.columns = vec![EXCEL_DEFAULT_COLUMN_WIDTH; max_col as usize];
+    table_data.dimensions.rows = vec![0.0; max_row as usize];
+    table_data.rows = rows;
+    table_data.merged_cells = merged_cells;
+    table_data.warn_cells(
+        "missing_result",
+        "Formula results not cached, recalculate and save the workbook",
+        missing_results,
+    );
+    table_data.warn_cells("error_cell", "Error cells emitted as text", error_cells);
+    Ok((table_data, sum_rows))
+}
+
+/// Read a sheet of a legacy .xls (BIFF8) workbook: values, merged cells and table size.
+pub fn read_xls_table(
+    bytes: &[u8],
+    sheet_index: usize,
+    options: &ParseOptions,
+) -> Result<(TableData, HashSet<u32>), PluginError> {
+    let mut workbook = Xls::new(Cursor::new(bytes))
+        .map_err(|e| {
+            PluginError::new("invalid_workbook", format!("Failed to read xls file: {}", e))
+        })?;
+    let name = sheet_name(&workbook, sheet_index)?;
+    let merges = workbook.worksheet_merge_cells(&name).unwrap_or_default();
+    read_calamine_table(&mut workbook, &name, &merges, options)
+}
+
+/// Whether the archive is a binary (.xlsb) workbook.
+pub fn is_xlsb(archive: &mut Archive) -> bool {
+    archive.has_part("xl/workbook.bin")
+}
+
+/// BIFF12 record type of a sheet in the workbook (`BrtBundleSh`).
+const BRT_BUNDLE_SH: u32 = 156;
+/// BIFF12 record type of a merged range in a sheet (`BrtMergeCell`).
+const BRT_MERGE_CELL: u32 = 176;
+
+/// Split a BIFF12 stream into (record type, payload) pairs.
+/// Types take up to 2 bytes and sizes up to 4 bytes, 7 bits per byte.
+fn biff12_records(data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut records = Vec::new();
+    let mut pos = 0;
+    let read_varint = |pos: &mut usize, max_bytes: usize| -> Option<u32> {
+        let mut value = 0;
+        for i in 0..max_bytes {
+            let byte = *data.get(*pos)?;
+            *pos += 1;
+            value |= ((byte & 0x7F) as u32) << (7 * i);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Some(value)
+    };
+    while pos < data.len() {
+        let (record_type, size) = match (read_varint(&mut pos, 2), read_varint(&mut pos, 4)) {
+            (Some(record_type), Some(size)) => (record_type, size as usize),
+            _ => break,
+        };
+        match data.get(pos..pos + size) {
+            Some(payload) => records.push((record_type, payload)),
+            None => break,
+        }
+        pos += size;
+    }
+    records
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Relationship id of the sheet at `sheet_index` from its `BrtBundleSh` record.
+fn xlsb_sheet_rel_id(workbook: &[u8], sheet_index: usize) -> Option<String> {
+    let (_, payload) = biff12_records(workbook)
+        .into_iter()
+        .filter(|(record_type, _)| *record_type == BRT_BUNDLE_SH)
+        .nth(sheet_index)?;
+    // hsState 与 iTabID 之后是可为空的 UTF-16 字符串 strRelID
+    let length = read_u32(payload, 8)?;
+    if length == u32::MAX {
+        return None;
+    }
+    let units: Vec<u16> = payload
+        .get(12..12 + length as usize * 2)?
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+/// Merged ranges of an xlsb sheet, which calamine doesn't expose.
+fn xlsb_merge_cells(archive: &mut Archive, sheet_index: usize) -> Result<Vec<Dimensions>, String> {
+    let workbook = match archive.read_part_bytes("xl/workbook.bin")? {
+        Some(workbook) => workbook,
+        None => return Ok(Vec::new()),
+    };
+    let target = xlsb_sheet_rel_id(&workbook, sheet_index).and_then(|rel_id| {
+        archive
+            .relationships("xl/workbook.bin")
+            .ok()?
+            .into_iter()
+            .find(|rel| rel.id == rel_id)
+            .map(|rel| rel.target)
+    });
+    let sheet = match target {
+        Some(target) => archive.read_part_bytes(&target)?,
+        None => None,
+    };
+    Ok(sheet
+        .as_deref()
+        .map(biff12_records)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(record_type, _)| *record_type == BRT_MERGE_CELL)
+        .filter_map(|(_, payload)| {
+            // RfX：起止行、起止列，均从 0 开始
+            Some(Dimensions {
+                start: (read_u32(payload, 0)?, read_u32(payload, 8)?),
+                end: (read_u32(payload, 4)?, read_u32(payload, 12)?),
+            })
+        })
+        .collect())
+}
+
+/// Read a sheet of a binary (.xlsb) workbook: values, merged cells and table size.
+pub fn read_xlsb_table(
+    bytes: &[u8],
+    archive: &mut Archive,
+    sheet_index: usize,
+    options: &ParseOptions,
+) -> Result<(TableData, HashSet<u32>), PluginError> {
+    let mut workbook = Xlsb::new(Cursor::new(bytes))
+        .map_err(|e| {
+            PluginError::new("invalid_workbook", format!("Failed to read xlsb file: {}", e))
+        })?;
+    let name = sheet_name(&workbook, sheet_index)?;
+    let merges = xlsb_merge_cells(archive, sheet_index)?;
+    read_calamine_table(&mut workbook, &name, &merges, options)
+}
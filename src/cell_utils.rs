@@ -1,24 +1,130 @@
 // cell_utils.rs
-use umya_spreadsheet::{BorderStyleValues, Cell, HorizontalAlignmentValues, Spreadsheet, UnderlineValues, VerticalAlignmentValues};
-use crate::data_structures::{Alignment, Border, FontStyle};
+use umya_spreadsheet::{BorderStyleValues, Cell, HorizontalAlignmentValues, PatternValues, Spreadsheet, Style, UnderlineValues, VerticalAlignmentValues};
+use crate::color_utils::{apply_tint, hex_to_rgb, resolve_raw_color, rgb_to_hex};
+use crate::data_structures::{
+    Alignment, Border, BorderStrokes, FontStyle, GradientFill, GradientStop, PatternFill,
+    PluginError, Stroke, TextRun,
+};
+use crate::format_utils::{
+    builtin_format_code, format_general, format_number, is_date_format, NumberLocale,
+};
+use crate::options::ParseOptions;
+use crate::style_utils::{flag, RawTheme};
+use crate::xml_utils::XmlNode;
 
-pub fn cell_value(cell: &Cell) -> Result<String, String> {
+pub fn cell_value(cell: &Cell, options: &ParseOptions) -> Result<String, PluginError> {
     if cell.get_raw_value().is_error() {
-        return Err(format!(
-            "Error in cell {}",
-            cell.get_coordinate().to_string()
-        ));
+        // 容错模式下错误单元格输出错误代码或占位文本
+        let coordinate = cell.get_coordinate().to_string();
+        options.error_text(&cell.get_value()).ok_or_else(|| {
+            PluginError::new(
+                "error_cell",
+                format!("Error in cell {}: {}", coordinate, cell.get_value()),
+            )
+            .at_cell(coordinate)
+        })
     } else {
-        Ok(cell.get_value().to_string())
+        Ok(normalize_line_breaks(&cell.get_value()))
+    }
+}
+
+/// Cell text with in-cell line breaks as "\n", decoding the `_x000D_` style escapes of OOXML
+/// strings and dropping carriage returns.
+pub fn normalize_line_breaks(value: &str) -> String {
+    if !value.contains('\r') && !value.contains("_x") {
+        return value.to_string();
+    }
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("_x") {
+        result.push_str(&rest[..start]);
+        // 转义形如 _x000A_，四位十六进制数
+        let escaped = rest[start..]
+            .get(2..7)
+            .and_then(|code| code.strip_suffix('_'))
+            .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32);
+        match escaped {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[start + 7..];
+            }
+            None => {
+                result.push_str("_x");
+                rest = &rest[start + 2..];
+            }
+        }
     }
+    result.push_str(rest);
+    // Windows 换行和单独的回车都按一个换行处理
+    result.replace("\r\n", "\n").replace('\r', "\n")
 }
 
-pub fn get_cell_alignment(cell: &Cell) -> Option<Alignment> {
-    let style = cell.get_style();
-    let alignment = match style.get_alignment() {
-        Some(alignment) => alignment,
-        None => return None,
+/// "date" or "number", by the number format of the style.
+pub fn number_kind(style: &Style) -> &'static str {
+    let is_date = style.get_number_format().is_some_and(|format| {
+        is_date_format(*format.get_number_format_id(), format.get_format_code())
+    });
+    if is_date {
+        "date"
+    } else {
+        "number"
+    }
+}
+
+/// Data type of an xlsx cell, telling dates from numbers by the number format.
+pub fn xlsx_cell_kind(cell: &Cell, style: &Style, value: &str) -> &'static str {
+    if value.is_empty() {
+        return "empty";
+    }
+    match cell.get_data_type() {
+        "n" => number_kind(style),
+        "b" => "bool",
+        "e" => "error",
+        _ => "string",
+    }
+}
+
+/// Text of a number or date cell as Excel displays it with the cell's number format.
+pub fn display_value(
+    style: &Style,
+    kind: &str,
+    value: String,
+    locale: &NumberLocale,
+) -> String {
+    if kind != "number" && kind != "date" {
+        return value;
+    }
+    let number: f64 = match value.parse() {
+        Ok(number) => number,
+        Err(_) => return value,
     };
+    match style.get_number_format() {
+        Some(format) => {
+            // 内置格式以 Excel 的显示为准
+            let code = builtin_format_code(*format.get_number_format_id())
+                .unwrap_or(format.get_format_code());
+            format_number(number, code, locale)
+        }
+        None => format_general(number, locale),
+    }
+}
+
+/// `textRotation` value marking stacked text.
+pub const STACKED_ROTATION: u32 = 255;
+
+/// Convert `textRotation` to degrees counterclockwise: 0-90 rotate up, 91-180 rotate down.
+fn excel_rotation(rotation: u32) -> i32 {
+    match rotation {
+        0..=90 => rotation as i32,
+        91..=180 => 90 - rotation as i32,
+        _ => 0,
+    }
+}
+
+pub fn get_cell_alignment(style: &Style, raw_xf: Option<&XmlNode>) -> Option<Alignment> {
+    let alignment = style.get_alignment()?;
 
     Some(Alignment {
         horizontal: match alignment.get_horizontal() {
@@ -35,64 +141,353 @@ pub fn get_cell_alignment(cell: &Cell) -> Option<Alignment> {
             _ => "default",
         }
         .to_string(),
+        wrap: *alignment.get_wrap_text(),
+        rotation: excel_rotation(*alignment.get_text_rotation()),
+        stacked: *alignment.get_text_rotation() == STACKED_ROTATION,
+        // Excel 以 "@" 开头的字体（如 "@SimSun"）竖排显示
+        east_asian_vertical: style
+            .get_font()
+            .is_some_and(|font| font.get_name().starts_with('@')),
+        // umya-spreadsheet 不解析缩进，从 styles.xml 中读取
+        indent: raw_xf
+            .and_then(|xf| xf.child("alignment"))
+            .and_then(|alignment| alignment.attr("indent"))
+            .and_then(|indent| indent.parse::<u32>().ok())
+            .unwrap_or(0),
     })
 }
 
-pub fn get_cell_border(cell: &Cell) -> Option<Border> {
-    let style = cell.get_style();
-    let border = match style.get_borders() {
-        Some(border) => border,
-        None => return None,
+pub fn get_cell_border(style: &Style, book: &Spreadsheet, theme: &RawTheme) -> Option<Border> {
+    let border = style.get_borders()?;
+
+    let strokes = BorderStrokes {
+        left: get_border_stroke(border.get_left(), book, theme),
+        right: get_border_stroke(border.get_right(), book, theme),
+        top: get_border_stroke(border.get_top(), book, theme),
+        bottom: get_border_stroke(border.get_bottom(), book, theme),
+        diagonal: get_border_stroke(border.get_diagonal(), book, theme),
     };
 
+    let has_diagonal = border.get_diagonal().get_style() != &BorderStyleValues::None;
     Some(Border {
         left: border.get_left().get_style() != &BorderStyleValues::None,
         right: border.get_right().get_style() != &BorderStyleValues::None,
         top: border.get_top().get_style() != &BorderStyleValues::None,
         bottom: border.get_bottom().get_style() != &BorderStyleValues::None,
+        // 对角线的方向由标志给出，线型保存在 diagonal 边上
+        diagonal_up: *border.get_diagonal_up() && has_diagonal,
+        diagonal_down: *border.get_diagonal_down() && has_diagonal,
+        stroke: None,
+        strokes: if strokes == BorderStrokes::default() {
+            None
+        } else {
+            Some(strokes)
+        },
     })
 }
 
-pub fn get_cell_bg_color(cell: &Cell, book: &Spreadsheet) -> Option<String> {
-    let style = cell.get_style();
-    let color = style.get_background_color()?;
+pub fn solid_stroke(style: &str, thickness: f64) -> Option<Stroke> {
+    Some(Stroke {
+        style: style.to_string(),
+        thickness,
+        gap: None,
+        dash: None,
+        color: None,
+    })
+}
+
+pub fn dashed_stroke(style: &str, thickness: f64, dash: &[f64]) -> Option<Stroke> {
+    Some(Stroke {
+        style: style.to_string(),
+        thickness,
+        gap: None,
+        dash: Some(dash.to_vec()),
+        color: None,
+    })
+}
+
+fn get_border_stroke(
+    border: &umya_spreadsheet::Border,
+    book: &Spreadsheet,
+    theme: &RawTheme,
+) -> Option<Stroke> {
+    // 线宽按 Excel 在 100% 缩放下的像素宽度换算
+    let stroke = match border.get_style() {
+        BorderStyleValues::Hair => solid_stroke("hair", 0.25),
+        BorderStyleValues::Thin => solid_stroke("thin", 0.75),
+        BorderStyleValues::Medium => solid_stroke("medium", 1.5),
+        BorderStyleValues::Thick => solid_stroke("thick", 2.25),
+        // 双线：两条细线，中间留出间隙
+        BorderStyleValues::Double => Some(Stroke {
+            style: "double".to_string(),
+            thickness: 0.5,
+            gap: Some(1.0),
+            dash: None,
+            color: None,
+        }),
+        BorderStyleValues::Dotted => dashed_stroke("dotted", 0.75, &[0.75, 0.75]),
+        BorderStyleValues::Dashed => dashed_stroke("dashed", 0.75, &[3.0, 1.5]),
+        BorderStyleValues::DashDot => dashed_stroke("dashDot", 0.75, &[3.0, 1.5, 0.75, 1.5]),
+        BorderStyleValues::DashDotDot => {
+            dashed_stroke("dashDotDot", 0.75, &[3.0, 1.5, 0.75, 1.5, 0.75, 1.5])
+        }
+        BorderStyleValues::MediumDashed => dashed_stroke("mediumDashed", 1.5, &[6.0, 3.0]),
+        BorderStyleValues::MediumDashDot => {
+            dashed_stroke("mediumDashDot", 1.5, &[6.0, 3.0, 1.5, 3.0])
+        }
+        BorderStyleValues::MediumDashDotDot => {
+            dashed_stroke("mediumDashDotDot", 1.5, &[6.0, 3.0, 1.5, 3.0, 1.5, 3.0])
+        }
+        BorderStyleValues::SlantDashDot => {
+            dashed_stroke("slantDashDot", 1.5, &[6.0, 1.5, 1.5, 1.5])
+        }
+        _ => None,
+    };
+    let color = color_hex(border.get_color(), book, theme);
+    stroke.map(|stroke| Stroke { color, ..stroke })
+}
+
+/// A color as "RRGGBB", theme colors with their tint applied and indexed colors looked up in the
+/// palette of the workbook; `None` when the color isn't set. Translucent colors come as
+/// "RRGGBBAA" when the theme keeps the alpha channel.
+pub fn color_hex(
+    color: &umya_spreadsheet::Color,
+    book: &Spreadsheet,
+    theme: &RawTheme,
+) -> Option<String> {
+    // 旧版文件使用调色板索引，工作簿可能自定义调色板
+    let indexed = *color.get_indexed() as usize;
+    if indexed > 0 {
+        if let Some(hex) = theme.indexed_color(indexed) {
+            return apply_tint(&hex, *color.get_tint());
+        }
+    }
     let argb = color.get_argb_with_theme(book.get_theme());
-    if argb.is_empty() {
-        Some("".to_string())
+    let (alpha, hex) = match argb.len() {
+        8 => (&argb[..2], &argb[2..]),
+        6 => ("FF", &argb[..]),
+        _ => return None,
+    };
+    // umya-spreadsheet 已对主题色应用色调（tint），只有直接给出的 RGB 颜色需另行计算
+    let tint = if color.get_argb().is_empty() { 0.0 } else { *color.get_tint() };
+    let hex = apply_tint(hex, tint)?;
+    // Typst 的 rgb() 把透明度写在最后
+    if theme.keep_alpha && !alpha.eq_ignore_ascii_case("FF") {
+        Some(format!("{}{}", hex, alpha.to_uppercase()))
     } else {
-        Some(if argb.len() == 8 {
-            argb.chars().skip(2).collect::<String>() // skip 的作用是去掉前两位，即 alpha 通道
-        } else {
-            argb.to_string()
+        Some(hex)
+    }
+}
+
+/// Pattern fill of the style when it isn't a plain solid fill, and the color blending its
+/// foreground and background by the share of the cell the pattern covers.
+pub fn get_cell_pattern_fill(
+    style: &Style,
+    book: &Spreadsheet,
+    theme: &RawTheme,
+) -> Option<(PatternFill, String)> {
+    let fill = style.get_fill()?.get_pattern_fill()?;
+    let (pattern, coverage) = match fill.get_pattern_type() {
+        PatternValues::DarkGray => ("darkGray", 0.75),
+        PatternValues::MediumGray => ("mediumGray", 0.5),
+        PatternValues::LightGray => ("lightGray", 0.25),
+        PatternValues::Gray125 => ("gray125", 0.125),
+        PatternValues::Gray0625 => ("gray0625", 0.0625),
+        PatternValues::DarkHorizontal => ("darkHorizontal", 0.5),
+        PatternValues::DarkVertical => ("darkVertical", 0.5),
+        PatternValues::DarkDown => ("darkDown", 0.5),
+        PatternValues::DarkUp => ("darkUp", 0.5),
+        PatternValues::DarkGrid => ("darkGrid", 0.75),
+        PatternValues::DarkTrellis => ("darkTrellis", 0.75),
+        PatternValues::LightHorizontal => ("lightHorizontal", 0.25),
+        PatternValues::LightVertical => ("lightVertical", 0.25),
+        PatternValues::LightDown => ("lightDown", 0.25),
+        PatternValues::LightUp => ("lightUp", 0.25),
+        PatternValues::LightGrid => ("lightGrid", 0.4375),
+        PatternValues::LightTrellis => ("lightTrellis", 0.375),
+        _ => return None,
+    };
+    // 未设置颜色时前景为黑色，背景为白色
+    let hex = |color: Option<&umya_spreadsheet::Color>, default: &str| {
+        color
+            .and_then(|color| color_hex(color, book, theme))
+            .unwrap_or_else(|| default.to_string())
+    };
+    let foreground = hex(fill.get_foreground_color(), "000000");
+    let background = hex(fill.get_background_color(), "FFFFFF");
+    let blend = blend_colors(&foreground, &background, coverage);
+    Some((
+        PatternFill {
+            pattern: pattern.to_string(),
+            foreground,
+            background,
+        },
+        blend,
+    ))
+}
+
+/// Gradient of a raw `<fill>` element and its average color, weighting each color by the length
+/// of the gradient it covers.
+pub fn get_cell_gradient_fill(
+    raw_fill: Option<&XmlNode>,
+    theme: &RawTheme,
+) -> Option<(GradientFill, String)> {
+    let gradient = raw_fill?.child("gradientFill")?;
+    let stops: Vec<GradientStop> = gradient
+        .children_named("stop")
+        .filter_map(|stop| {
+            Some(GradientStop {
+                position: stop.attr("position")?.parse::<f64>().ok()?.clamp(0.0, 1.0),
+                color: resolve_raw_color(stop.child("color")?, theme)?,
+            })
         })
+        .collect();
+    let (first, last) = (stops.first()?, stops.last()?);
+    // 首尾之外的部分保持端点的颜色，相邻两点之间按线性渐变取中间色
+    let mut average = [0.0; 3];
+    let mut add = |color: &str, weight: f64| {
+        if let Some((r, g, b)) = hex_to_rgb(color) {
+            for (sum, channel) in average.iter_mut().zip([r, g, b]) {
+                *sum += channel * weight;
+            }
+        }
+    };
+    add(&first.color, first.position);
+    add(&last.color, 1.0 - last.position);
+    for pair in stops.windows(2) {
+        let weight = (pair[1].position - pair[0].position).max(0.0) / 2.0;
+        add(&pair[0].color, weight);
+        add(&pair[1].color, weight);
     }
+    let color = rgb_to_hex(average[0], average[1], average[2]);
+    let kind = gradient.attr("type").unwrap_or("linear").to_string();
+    let angle = gradient
+        .attr("degree")
+        .and_then(|degree| degree.parse().ok())
+        .unwrap_or(0.0);
+    Some((GradientFill { kind, angle, stops }, color))
+}
+
+/// Mix two "RRGGBB" colors, `share` being the part of the first one.
+fn blend_colors(first: &str, second: &str, share: f64) -> String {
+    let channel = |color: &str, index: usize| {
+        color
+            .get(index * 2..index * 2 + 2)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .unwrap_or(0) as f64
+    };
+    (0..3)
+        .map(|index| {
+            let mixed = channel(first, index) * share + channel(second, index) * (1.0 - share);
+            format!("{:02X}", mixed.round() as u8)
+        })
+        .collect()
 }
 
-pub fn get_cell_font_style(cell: &Cell, book: &Spreadsheet) -> Option<FontStyle> {
-    let font = match cell.get_style().get_font() {
+pub fn get_cell_bg_color(style: &Style, book: &Spreadsheet, theme: &RawTheme) -> Option<String> {
+    let color = style.get_background_color()?;
+    Some(color_hex(color, book, theme).unwrap_or_default())
+}
+
+pub fn get_cell_font_style(
+    style: &Style,
+    book: &Spreadsheet,
+    raw_font: Option<&XmlNode>,
+    raw_theme: &RawTheme,
+) -> Option<FontStyle> {
+    let font = match style.get_font() {
         Some(font) => font,
         None => {
             return None;
         }
     };
 
+    // 使用主题字体（major/minor）时，字体名以主题中的为准
+    let scheme = raw_font
+        .and_then(|font| font.child("scheme"))
+        .and_then(|scheme| scheme.attr("val"))
+        .filter(|scheme| *scheme == "major" || *scheme == "minor");
+    let theme_fonts = scheme.and_then(|scheme| raw_theme.fonts(scheme));
+    // 竖排字体的 "@" 前缀不属于字体名，竖排由 alignment 报告
+    let declared_name = Some(font.get_name().trim_start_matches('@').to_string())
+        .filter(|name| !name.is_empty());
+    let (name, east_asian_name, complex_script_name) = match theme_fonts {
+        Some(fonts) => (
+            fonts.latin.or(declared_name),
+            fonts.east_asian,
+            fonts.complex_script,
+        ),
+        None => (declared_name, None, None),
+    };
+
     Some(FontStyle {
+        name,
+        east_asian_name,
+        complex_script_name,
+        scheme: scheme.map(|scheme| scheme.to_string()),
         bold: *font.get_font_bold().get_val(),
         italic: *font.get_font_italic().get_val(),
         size: *font.get_font_size().get_val(),
-        color: {
-            let argb = font.get_color().get_argb_with_theme(book.get_theme());
-            if argb.is_empty() {
-                None
-            } else {
-                Some(if argb.len() == 8 {
-                    argb.chars().skip(2).collect::<String>() // skip 的作用是去掉前两位，即 alpha 通道
-                } else {
-                    argb.to_string()
-                })
-            }
-        },
+        color: color_hex(font.get_color(), book, raw_theme),
         underline: font.get_font_underline().get_val() != &UnderlineValues::None,
+        underline_style: match font.get_font_underline().get_val() {
+            UnderlineValues::Single => Some("single"),
+            UnderlineValues::Double => Some("double"),
+            UnderlineValues::SingleAccounting => Some("singleAccounting"),
+            UnderlineValues::DoubleAccounting => Some("doubleAccounting"),
+            _ => None,
+        }
+        .map(|style| style.to_string()),
         strike: *font.get_font_strike().get_val(),
+        // umya-spreadsheet 不解析以下属性，从 styles.xml 中读取
+        vertical_align: raw_font
+            .and_then(|font| font.child("vertAlign"))
+            .and_then(|align| align.attr("val"))
+            .filter(|align| *align == "superscript" || *align == "subscript")
+            .map(|align| align.to_string()),
+        outline: flag(raw_font.and_then(|font| font.child("outline"))),
+        shadow: flag(raw_font.and_then(|font| font.child("shadow"))),
+        condense: flag(raw_font.and_then(|font| font.child("condense"))),
+        extend: flag(raw_font.and_then(|font| font.child("extend"))),
     })
-}
\ No newline at end of file
+}
+
+/// Formatted runs of a rich text cell, `None` for cells with plain text.
+pub fn get_rich_text_runs(
+    cell: &Cell,
+    book: &Spreadsheet,
+    theme: &RawTheme,
+) -> Option<Vec<TextRun>> {
+    let rich_text = cell.get_raw_value().get_rich_text()?;
+    let runs = rich_text
+        .get_rich_text_elements()
+        .iter()
+        .map(|element| {
+            let text = normalize_line_breaks(element.get_text());
+            match element.get_run_properties() {
+                // 没有格式的片段沿用单元格的字体
+                None => TextRun {
+                    text,
+                    font: None,
+                    bold: None,
+                    italic: None,
+                    underline: None,
+                    strike: None,
+                    size: None,
+                    color: None,
+                },
+                Some(font) => TextRun {
+                    text,
+                    font: Some(font.get_name().to_string()).filter(|name| !name.is_empty()),
+                    bold: Some(*font.get_font_bold().get_val()),
+                    italic: Some(*font.get_font_italic().get_val()),
+                    underline: Some(font.get_font_underline().get_val() != &UnderlineValues::None),
+                    strike: Some(*font.get_font_strike().get_val()),
+                    size: Some(*font.get_font_size().get_val()).filter(|size| *size > 0.0),
+                    color: color_hex(font.get_color(), book, theme),
+                },
+            }
+        })
+        .collect();
+    Some(runs)
+}
@@ -0,0 +1,110 @@
+use crate::style_utils::RawTheme;
+use crate::xml_utils::XmlNode;
+
+/// Parse a 6-digit (RGB) or 8-digit (ARGB) hex color into RGB components in `0..=1`.
+pub fn hex_to_rgb(hex: &str) -> Option<(f64, f64, f64)> {
+    let hex = hex.trim_start_matches('#');
+    let hex = if hex.len() == 8 { &hex[2..] } else { hex };
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .ok()
+            .map(|c| c as f64 / 255.0)
+    };
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn rgb_to_hex(r: f64, g: f64, b: f64) -> String {
+    let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("{:02X}{:02X}{:02X}", channel(r), channel(g), channel(b))
+}
+
+fn rgb_to_hsl(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    if max == min {
+        return (0.0, 0.0, l);
+    }
+    let d = max - min;
+    let s = if l > 0.5 {
+        d / (2.0 - max - min)
+    } else {
+        d / (max + min)
+    };
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (h / 6.0, s, l)
+}
+
+fn hsl_to_rgb(h: f64, s: f64, l: f64) -> (f64, f64, f64) {
+    if s == 0.0 {
+        return (l, l, l);
+    }
+    let hue = |p: f64, q: f64, mut t: f64| {
+        if t < 0.0 {
+            t += 1.0;
+        }
+        if t > 1.0 {
+            t -= 1.0;
+        }
+        if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        }
+    };
+    let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+    let p = 2.0 * l - q;
+    (
+        hue(p, q, h + 1.0 / 3.0),
+        hue(p, q, h),
+        hue(p, q, h - 1.0 / 3.0),
+    )
+}
+
+/// Apply an Excel tint (-1 darker .. 1 lighter) to the luminance of a color.
+pub fn apply_tint(hex: &str, tint: f64) -> Option<String> {
+    let (r, g, b) = hex_to_rgb(hex)?;
+    if tint == 0.0 {
+        return Some(rgb_to_hex(r, g, b));
+    }
+    let (h, s, l) = rgb_to_hsl(r, g, b);
+    let l = if tint < 0.0 {
+        l * (1.0 + tint)
+    } else {
+        l * (1.0 - tint) + tint
+    };
+    let (r, g, b) = hsl_to_rgb(h, s, l);
+    Some(rgb_to_hex(r, g, b))
+}
+
+/// Resolve a raw `<color>` element (`rgb`, `indexed`, or `theme` with optional `tint`) to a
+/// 6-digit hex color.
+pub fn resolve_raw_color(color: &XmlNode, theme: &RawTheme) -> Option<String> {
+    let base = if let Some(rgb) = color.attr("rgb") {
+        let (r, g, b) = hex_to_rgb(rgb)?;
+        rgb_to_hex(r, g, b)
+    } else if let Some(indexed) = color.attr("indexed") {
+        theme.indexed_color(indexed.parse::<usize>().ok()?)?
+    } else {
+        let index = color.attr("theme")?.parse::<usize>().ok()?;
+        theme.color(index)?
+    };
+    let tint = color
+        .attr("tint")
+        .and_then(|tint| tint.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    apply_tint(&base, tint)
+}
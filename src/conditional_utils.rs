@@ -0,0 +1,651 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::color_utils::{hex_to_rgb, resolve_raw_color, rgb_to_hex};
+use crate::data_structures::{CellData, CellIcon, DataBar, RowData, TableData, TypedValue};
+use crate::style_utils::{flag, RawStyles, RawTheme};
+use crate::utils::{parse_cell_reference, parse_range};
+use crate::xml_utils::XmlNode;
+
+/// A `<cfRule>` together with the ranges of its `<conditionalFormatting>` block.
+pub struct ConditionalRule<'a> {
+    pub ranges: Vec<((u32, u32), (u32, u32))>,
+    pub priority: u32,
+    pub stop_if_true: bool,
+    pub rule: &'a XmlNode,
+}
+
+impl ConditionalRule<'_> {
+    pub fn applies_to(&self, col_num: u32, row_num: u32) -> bool {
+        self.ranges.iter().any(|((start_col, start_row), (end_col, end_row))| {
+            (*start_col..=*end_col).contains(&col_num) && (*start_row..=*end_row).contains(&row_num)
+        })
+    }
+}
+
+/// All conditional formatting rules of the sheet, highest priority (lowest number) first.
+pub fn get_conditional_rules(sheet_xml: &XmlNode) -> Vec<ConditionalRule<'_>> {
+    let mut rules: Vec<ConditionalRule> = sheet_xml
+        .children_named("conditionalFormatting")
+        .flat_map(|block| {
+            let ranges: Vec<_> = block
+                .attr("sqref")
+                .unwrap_or_default()
+                .split_whitespace()
+                .filter_map(parse_range)
+                .collect();
+            block.children_named("cfRule").map(move |rule| ConditionalRule {
+                ranges: ranges.clone(),
+                priority: rule
+                    .attr("priority")
+                    .and_then(|priority| priority.parse().ok())
+                    .unwrap_or(u32::MAX),
+                stop_if_true: matches!(rule.attr("stopIfTrue"), Some("1") | Some("true")),
+                rule,
+            })
+        })
+        .collect();
+    // 稳定排序，优先级相同时保持文件中的顺序
+    rules.sort_by_key(|rule| rule.priority);
+    rules
+}
+
+/// Formatting collected from the matching rules of a cell.
+#[derive(Default, Clone)]
+pub struct ConditionalFormat {
+    pub fill: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    /// Kind of underline, "none" turning it off.
+    pub underline: Option<String>,
+    pub strike: Option<bool>,
+    pub color: Option<String>,
+}
+
+impl ConditionalFormat {
+    /// Take the properties not already set by a higher priority rule.
+    pub fn merge(&mut self, other: ConditionalFormat) {
+        self.fill = self.fill.take().or(other.fill);
+        self.bold = self.bold.or(other.bold);
+        self.italic = self.italic.or(other.italic);
+        self.underline = self.underline.take().or(other.underline);
+        self.strike = self.strike.or(other.strike);
+        self.color = self.color.take().or(other.color);
+    }
+}
+
+pub fn dxf_format(dxf: &XmlNode, theme: &RawTheme) -> ConditionalFormat {
+    let font = dxf.child("font");
+    let font_flag = |name: &str| font.and_then(|font| font.child(name)).map(|f| flag(Some(f)));
+    // dxf 中的纯色填充使用 bgColor
+    let fill = dxf
+        .child("fill")
+        .and_then(|fill| fill.child("patternFill"))
+        .and_then(|pattern| pattern.child("bgColor").or_else(|| pattern.child("fgColor")));
+    ConditionalFormat {
+        fill: fill.and_then(|color| resolve_raw_color(color, theme)),
+        bold: font_flag("b"),
+        italic: font_flag("i"),
+        underline: font
+            .and_then(|font| font.child("u"))
+            .map(|u| u.attr("val").unwrap_or("single").to_string()),
+        strike: font_flag("strike"),
+        color: font
+            .and_then(|font| font.child("color"))
+            .and_then(|color| resolve_raw_color(color, theme)),
+    }
+}
+
+enum Constant {
+    Number(f64),
+    Text(String),
+}
+
+/// Constant operand of a rule formula; references and expressions are not evaluated.
+fn constant(formula: &str) -> Option<Constant> {
+    let formula = formula.trim();
+    if formula.len() >= 2 && formula.starts_with('"') && formula.ends_with('"') {
+        return Some(Constant::Text(formula[1..formula.len() - 1].replace("\"\"", "\"")));
+    }
+    formula.parse::<f64>().ok().map(Constant::Number)
+}
+
+fn compare(value: &str, operand: &Constant) -> Ordering {
+    // 空单元格按 0 比较，文本总是大于数字
+    let number = if value.trim().is_empty() {
+        Some(0.0)
+    } else {
+        value.trim().parse::<f64>().ok()
+    };
+    match (operand, number) {
+        (Constant::Number(operand), Some(number)) => {
+            number.partial_cmp(operand).unwrap_or(Ordering::Equal)
+        }
+        (Constant::Number(_), None) => Ordering::Greater,
+        (Constant::Text(operand), _) => value.to_lowercase().cmp(&operand.to_lowercase()),
+    }
+}
+
+fn cell_is(value: &str, operator: &str, formulas: &[&str]) -> bool {
+    let operands: Option<Vec<Constant>> = formulas.iter().map(|f| constant(f)).collect();
+    let operands = match operands {
+        Some(operands) if !operands.is_empty() => operands,
+        _ => return false,
+    };
+    let first = compare(value, &operands[0]);
+    let between = || {
+        operands.get(1).is_some_and(|second| {
+            let second = compare(value, second);
+            (first != Ordering::Less && second != Ordering::Greater)
+                || (first != Ordering::Greater && second != Ordering::Less)
+        })
+    };
+    match operator {
+        "equal" => first == Ordering::Equal,
+        "notEqual" => first != Ordering::Equal,
+        "greaterThan" => first == Ordering::Greater,
+        "greaterThanOrEqual" => first != Ordering::Less,
+        "lessThan" => first == Ordering::Less,
+        "lessThanOrEqual" => first != Ordering::Greater,
+        "between" => between(),
+        "notBetween" => !between(),
+        _ => false,
+    }
+}
+
+fn rule_matches(rule: &XmlNode, value: &str) -> bool {
+    let formulas: Vec<&str> = rule
+        .children_named("formula")
+        .map(|formula| formula.text.as_str())
+        .collect();
+    let text = rule.attr("text").unwrap_or_default().to_lowercase();
+    let lower = value.to_lowercase();
+    match rule.attr("type").unwrap_or_default() {
+        "cellIs" => cell_is(value, rule.attr("operator").unwrap_or("equal"), &formulas),
+        "containsText" => lower.contains(&text),
+        "notContainsText" => !lower.contains(&text),
+        "beginsWith" => lower.starts_with(&text),
+        "endsWith" => lower.ends_with(&text),
+        "containsBlanks" => value.trim().is_empty(),
+        "notContainsBlanks" => !value.trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// An anchor value of a color scale with its RGB color.
+type ScaleAnchor = (f64, (f64, f64, f64));
+
+/// A color scale rule resolved against the values of its ranges: ascending anchor values with their colors.
+struct ColorScale {
+    anchors: Vec<ScaleAnchor>,
+}
+
+impl ColorScale {
+    /// Interpolate linearly in RGB between the surrounding anchors, clamping outside them.
+    fn color(&self, value: f64) -> String {
+        let (first, last) = (self.anchors[0], self.anchors[self.anchors.len() - 1]);
+        let (r, g, b) = if value <= first.0 {
+            first.1
+        } else if value >= last.0 {
+            last.1
+        } else {
+            self.anchors
+                .windows(2)
+                .find(|pair| value <= pair[1].0)
+                .map(|pair| {
+                    let ((low, from), (high, to)) = (pair[0], pair[1]);
+                    let t = if high > low { (value - low) / (high - low) } else { 1.0 };
+                    (
+                        from.0 + (to.0 - from.0) * t,
+                        from.1 + (to.1 - from.1) * t,
+                        from.2 + (to.2 - from.2) * t,
+                    )
+                })
+                .unwrap_or(last.1)
+        };
+        rgb_to_hex(r, g, b)
+    }
+}
+
+fn numeric_value(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok()
+}
+
+/// Value a rule evaluates: the stored number of numeric cells, whose text may be formatted
+/// ("45%", "$1,000.00"), and the text of the others.
+fn rule_value(cell: &CellData) -> String {
+    match &cell.typed {
+        Some(TypedValue::Number(number)) => number.to_string(),
+        _ => cell.value.clone(),
+    }
+}
+
+/// Number in the cell a formula like `$B$1` refers to, for anchors taken from the sheet.
+fn reference_value(formula: &str, rows: &[RowData]) -> Option<f64> {
+    let reference = formula.trim().trim_start_matches('=').replace('$', "");
+    let letters = reference
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .count();
+    let digits = &reference[letters..];
+    if letters == 0 || digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (column, row_num) = parse_cell_reference(&reference.to_ascii_uppercase());
+    rows.iter()
+        .find(|row| row.row_number == row_num)?
+        .cells
+        .iter()
+        .find(|cell| cell.column == column)
+        .and_then(|cell| numeric_value(&rule_value(cell)))
+}
+
+/// Inclusive percentile of sorted values, as `PERCENTILE.INC` computes it.
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let low = rank.floor() as usize;
+    let high = rank.ceil() as usize;
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+}
+
+/// Value of a `<cfvo>` anchor over the sorted values of the rule's ranges. The value of an
+/// anchor is a constant or a reference to a single cell.
+fn anchor_value(cfvo: &XmlNode, sorted: &[f64], rows: &[RowData]) -> Option<f64> {
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    let val = || {
+        let val = cfvo.attr("val")?;
+        match constant(val) {
+            Some(Constant::Number(number)) => Some(number),
+            Some(Constant::Text(_)) => None,
+            None => reference_value(val, rows),
+        }
+    };
+    match cfvo.attr("type").unwrap_or_default() {
+        "min" => Some(min),
+        "max" => Some(max),
+        "num" | "formula" => val(),
+        "percent" => Some(min + (max - min) * val()? / 100.0),
+        "percentile" => Some(percentile(sorted, val()?)),
+        _ => None,
+    }
+}
+
+/// Cells within the ranges of a rule.
+fn range_cells<'a>(
+    rule: &'a ConditionalRule,
+    rows: &'a [RowData],
+) -> impl Iterator<Item = &'a CellData> + 'a {
+    rows.iter().flat_map(move |row| {
+        row.cells
+            .iter()
+            .filter(move |cell| rule.applies_to(cell.column, row.row_number))
+    })
+}
+
+/// Numeric values within the ranges of a rule in ascending order, `None` when there are none.
+fn sorted_numbers(rule: &ConditionalRule, rows: &[RowData]) -> Option<Vec<f64>> {
+    let mut sorted: Vec<f64> = range_cells(rule, rows)
+        .filter_map(|cell| numeric_value(&rule_value(cell)))
+        .collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    Some(sorted)
+}
+
+fn color_scale(rule: &ConditionalRule, rows: &[RowData], theme: &RawTheme) -> Option<ColorScale> {
+    let scale = rule.rule.child("colorScale")?;
+    let sorted = sorted_numbers(rule, rows)?;
+    let anchors: Option<Vec<ScaleAnchor>> = scale
+        .children_named("cfvo")
+        .zip(scale.children_named("color"))
+        .map(|(cfvo, color)| {
+            let rgb = hex_to_rgb(&resolve_raw_color(color, theme)?)?;
+            Some((anchor_value(cfvo, &sorted, rows)?, rgb))
+        })
+        .collect();
+    let mut anchors = anchors.filter(|anchors| anchors.len() >= 2)?;
+    // 锚点的值可能因公式或百分位而不递增，按值排序保证插值区间有效
+    anchors.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    Some(ColorScale { anchors })
+}
+
+/// A data bar rule: the values of the shortest and the longest bar, and their lengths in
+/// percent of the cell width.
+struct DataBarRule {
+    min: f64,
+    max: f64,
+    min_length: f64,
+    max_length: f64,
+    color: String,
+    show_value: bool,
+}
+
+impl DataBarRule {
+    fn bar(&self, value: f64) -> DataBar {
+        let t = if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let length = self.min_length + (self.max_length - self.min_length) * t;
+        DataBar {
+            fraction: (length / 100.0).clamp(0.0, 1.0),
+            color: self.color.clone(),
+            show_value: self.show_value,
+        }
+    }
+}
+
+fn data_bar(rule: &ConditionalRule, rows: &[RowData], theme: &RawTheme) -> Option<DataBarRule> {
+    let bar = rule.rule.child("dataBar")?;
+    let sorted = sorted_numbers(rule, rows)?;
+    let mut anchors = bar.children_named("cfvo");
+    let min = anchor_value(anchors.next()?, &sorted, rows)?;
+    let max = anchor_value(anchors.next()?, &sorted, rows)?;
+    let length = |name: &str, default: f64| {
+        bar.attr(name)
+            .and_then(|length| length.parse::<f64>().ok())
+            .unwrap_or(default)
+    };
+    Some(DataBarRule {
+        min,
+        max,
+        min_length: length("minLength", 10.0),
+        max_length: length("maxLength", 90.0),
+        color: resolve_raw_color(bar.child("color")?, theme)?,
+        show_value: rule_flag(bar, "showValue", true),
+    })
+}
+
+/// An icon set rule: the thresholds from which each icon after the first one is shown.
+struct IconSetRule {
+    set: String,
+    /// Threshold values, and whether a value equal to the threshold reaches it.
+    thresholds: Vec<(f64, bool)>,
+    reverse: bool,
+    show_value: bool,
+}
+
+impl IconSetRule {
+    fn icon(&self, value: f64) -> CellIcon {
+        let reached = self
+            .thresholds
+            .iter()
+            .take_while(|(threshold, inclusive)| {
+                value > *threshold || (*inclusive && value == *threshold)
+            })
+            .count();
+        let index = if self.reverse {
+            self.thresholds.len() - reached
+        } else {
+            reached
+        };
+        CellIcon {
+            set: self.set.clone(),
+            index: index as u32,
+            show_value: self.show_value,
+        }
+    }
+}
+
+fn icon_set(rule: &ConditionalRule, rows: &[RowData]) -> Option<IconSetRule> {
+    let icons = rule.rule.child("iconSet")?;
+    let sorted = sorted_numbers(rule, rows)?;
+    // 第一个锚点对应最小的图标，不参与比较
+    let thresholds: Option<Vec<(f64, bool)>> = icons
+        .children_named("cfvo")
+        .skip(1)
+        .map(|cfvo| Some((anchor_value(cfvo, &sorted, rows)?, rule_flag(cfvo, "gte", true))))
+        .collect();
+    Some(IconSetRule {
+        set: icons.attr("iconSet").unwrap_or("3TrafficLights1").to_string(),
+        thresholds: thresholds.filter(|thresholds| !thresholds.is_empty())?,
+        reverse: rule_flag(icons, "reverse", false),
+        show_value: rule_flag(icons, "showValue", true),
+    })
+}
+
+/// Cells a rule selects by comparing their value with all the values of its ranges.
+enum Selection {
+    /// Numbers beyond a threshold: the top or bottom ranks, or above or below the average.
+    Threshold {
+        value: f64,
+        above: bool,
+        inclusive: bool,
+    },
+    /// Values occurring more than once, or exactly once when `unique`.
+    Duplicates {
+        counts: HashMap<String, usize>,
+        unique: bool,
+    },
+}
+
+impl Selection {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Selection::Threshold {
+                value: threshold,
+                above,
+                inclusive,
+            } => numeric_value(value).is_some_and(|number| match (above, inclusive) {
+                (true, true) => number >= *threshold,
+                (true, false) => number > *threshold,
+                (false, true) => number <= *threshold,
+                (false, false) => number < *threshold,
+            }),
+            Selection::Duplicates { counts, unique } => {
+                let key = duplicate_key(value);
+                !key.is_empty() && counts.get(&key).is_some_and(|count| (*count == 1) == *unique)
+            }
+        }
+    }
+}
+
+/// Duplicates are compared ignoring case, like Excel does.
+fn duplicate_key(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+fn rule_flag(rule: &XmlNode, name: &str, default: bool) -> bool {
+    rule.attr(name)
+        .map_or(default, |value| value == "1" || value == "true")
+}
+
+/// Top or bottom N values (`top10`), N counting items or percent of the values.
+fn top_selection(rule: &ConditionalRule, rows: &[RowData]) -> Option<Selection> {
+    let sorted = sorted_numbers(rule, rows)?;
+    let rank = rule
+        .rule
+        .attr("rank")
+        .and_then(|rank| rank.parse::<f64>().ok())
+        .unwrap_or(10.0);
+    let count = if rule_flag(rule.rule, "percent", false) {
+        (sorted.len() as f64 * rank / 100.0).floor()
+    } else {
+        rank
+    };
+    // 与临界值相等的值一并选中
+    let count = (count as usize).clamp(1, sorted.len());
+    let bottom = rule_flag(rule.rule, "bottom", false);
+    Some(Selection::Threshold {
+        value: if bottom {
+            sorted[count - 1]
+        } else {
+            sorted[sorted.len() - count]
+        },
+        above: !bottom,
+        inclusive: true,
+    })
+}
+
+/// Values above or below the average (`aboveAverage`), optionally by a number of standard
+/// deviations.
+fn average_selection(rule: &ConditionalRule, rows: &[RowData]) -> Option<Selection> {
+    let numbers = sorted_numbers(rule, rows)?;
+    let count = numbers.len() as f64;
+    let mean = numbers.iter().sum::<f64>() / count;
+    let deviations = rule
+        .rule
+        .attr("stdDev")
+        .and_then(|deviations| deviations.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    let std_dev = if count > 1.0 {
+        (numbers.iter().map(|number| (number - mean).powi(2)).sum::<f64>() / (count - 1.0)).sqrt()
+    } else {
+        0.0
+    };
+    let above = rule_flag(rule.rule, "aboveAverage", true);
+    Some(Selection::Threshold {
+        value: if above {
+            mean + deviations * std_dev
+        } else {
+            mean - deviations * std_dev
+        },
+        above,
+        inclusive: rule_flag(rule.rule, "equalAverage", false),
+    })
+}
+
+fn duplicate_selection(rule: &ConditionalRule, rows: &[RowData], unique: bool) -> Selection {
+    let mut counts = HashMap::new();
+    for cell in range_cells(rule, rows) {
+        let key = duplicate_key(&rule_value(cell));
+        if !key.is_empty() {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    Selection::Duplicates { counts, unique }
+}
+
+/// A rule that depends on all the values of its ranges, resolved before evaluating the cells.
+enum RangeRule {
+    Scale(ColorScale),
+    Select(Selection),
+    Bar(DataBarRule),
+    Icons(IconSetRule),
+}
+
+fn range_rule(rule: &ConditionalRule, rows: &[RowData], theme: &RawTheme) -> Option<RangeRule> {
+    match rule.rule.attr("type")? {
+        "colorScale" => color_scale(rule, rows, theme).map(RangeRule::Scale),
+        "dataBar" => data_bar(rule, rows, theme).map(RangeRule::Bar),
+        "iconSet" => icon_set(rule, rows).map(RangeRule::Icons),
+        "top10" => top_selection(rule, rows).map(RangeRule::Select),
+        "aboveAverage" => average_selection(rule, rows).map(RangeRule::Select),
+        "duplicateValues" => Some(RangeRule::Select(duplicate_selection(rule, rows, false))),
+        "uniqueValues" => Some(RangeRule::Select(duplicate_selection(rule, rows, true))),
+        _ => None,
+    }
+}
+
+pub fn apply_format(
+    cell: &mut CellData,
+    format: ConditionalFormat,
+    parse_fill: bool,
+    parse_font: bool,
+) {
+    let style = match cell.style.as_mut() {
+        Some(style) => style,
+        None => return,
+    };
+    if parse_fill && format.fill.is_some() {
+        style.color = format.fill;
+    }
+    if let (true, Some(font)) = (parse_font, style.font.as_mut()) {
+        if let Some(bold) = format.bold {
+            font.bold = bold;
+        }
+        if let Some(italic) = format.italic {
+            font.italic = italic;
+        }
+        if let Some(underline) = format.underline {
+            font.underline = underline != "none";
+            font.underline_style = Some(underline).filter(|underline| underline != "none");
+        }
+        if let Some(strike) = format.strike {
+            font.strike = strike;
+        }
+        if format.color.is_some() {
+            font.color = format.color;
+        }
+    }
+}
+
+/// Evaluate the rules against every cell and merge the resulting formatting into its style.
+/// Rules are applied in priority order: a higher priority rule wins on conflicting properties,
+/// and a matching rule with `stopIfTrue` stops the evaluation of the lower priority ones.
+/// Data bars and icon sets are exported separately as `data_bar` and `icon`, the highest
+/// priority one winning.
+pub fn apply_conditional_formats(
+    table_data: &mut TableData,
+    rules: &[ConditionalRule],
+    raw_styles: &RawStyles,
+    raw_theme: &RawTheme,
+    parse_fill: bool,
+    parse_font: bool,
+) {
+    let range_rules: Vec<Option<RangeRule>> = rules
+        .iter()
+        .map(|rule| range_rule(rule, &table_data.rows, raw_theme))
+        .collect();
+    for row in &mut table_data.rows {
+        for cell in &mut row.cells {
+            let value = rule_value(cell);
+            let mut format = ConditionalFormat::default();
+            let mut data_bar = None;
+            let mut icon = None;
+            for (rule, range_rule) in rules
+                .iter()
+                .zip(&range_rules)
+                .filter(|(rule, _)| rule.applies_to(cell.column, row.row_number))
+            {
+                let rule_dxf = || {
+                    rule.rule
+                        .attr("dxfId")
+                        .and_then(|dxf_id| dxf_id.parse::<usize>().ok())
+                        .and_then(|dxf_id| raw_styles.dxf(dxf_id))
+                        .map(|dxf| dxf_format(dxf, raw_theme))
+                        .unwrap_or_default()
+                };
+                // 色阶只作用于数值单元格
+                let rule_format = match range_rule {
+                    Some(RangeRule::Scale(scale)) => match numeric_value(&value) {
+                        Some(value) => ConditionalFormat {
+                            fill: Some(scale.color(value)),
+                            ..Default::default()
+                        },
+                        None => continue,
+                    },
+                    Some(RangeRule::Select(selection)) if selection.matches(&value) => {
+                        rule_dxf()
+                    }
+                    Some(RangeRule::Select(_)) => continue,
+                    // 数据条与图标集不改变格式，也不受 stopIfTrue 影响
+                    Some(RangeRule::Bar(bar)) => {
+                        if let (None, Some(value)) = (&data_bar, numeric_value(&value)) {
+                            data_bar = Some(bar.bar(value));
+                        }
+                        continue;
+                    }
+                    Some(RangeRule::Icons(icons)) => {
+                        if let (None, Some(value)) = (&icon, numeric_value(&value)) {
+                            icon = Some(icons.icon(value));
+                        }
+                        continue;
+                    }
+                    None if rule_matches(rule.rule, &value) => rule_dxf(),
+                    None => continue,
+                };
+                format.merge(rule_format);
+                if rule.stop_if_true {
+                    break;
+                }
+            }
+            cell.data_bar = data_bar;
+            cell.icon = icon;
+            apply_format(cell, format, parse_fill, parse_font);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::calamine_utils::EXCEL_DEFAULT_COLUMN_WIDTH;
+use crate::data_structures::{CellData, RowData, TableData, TypedValue};
+
+/// Split delimited text into records, following RFC 4180: quoted fields may contain
+/// delimiters, line breaks and doubled quote characters.
+fn parse_records(text: &str, delimiter: char, quote: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == quote {
+                if chars.peek() == Some(&quote) {
+                    field.push(quote);
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == quote && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\r' || c == '\n' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            record.push(std::mem::take(&mut field));
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    // 最后一行可能没有换行符
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+fn single_char(value: &str, name: &str) -> Result<char, String> {
+    let value = if value == "\\t" { "\t" } else { value };
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("Invalid {}: {:?}", name, value)),
+    }
+}
+
+/// Read delimited text into `TableData`, one cell per field. Empty fields are left out,
+/// the same as empty cells of a worksheet.
+pub fn read_csv_table(bytes: &[u8], delimiter: &str, quote: &str) -> Result<TableData, String> {
+    let text = String::from_utf8(bytes.to_vec())
+        .map_err(|e| format!("Failed to read CSV file: {}", e))?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let records = parse_records(
+        text,
+        single_char(delimiter, "delimiter")?,
+        single_char(quote, "quote")?,
+    );
+
+    let max_row = records.len() as u32;
+    let max_col = records.iter().map(|record| record.len()).max().unwrap_or(0) as u32;
+    let mut table_data = TableData::new(max_col, max_row);
+    table_data.dimensions.columns = vec![EXCEL_DEFAULT_COLUMN_WIDTH; max_col as usize];
+    table_data.dimensions.rows = vec![0.0; max_row as usize];
+    for (row_index, record) in records.into_iter().enumerate() {
+        let cells: Vec<CellData> = record
+            .into_iter()
+            .enumerate()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(col_index, value)| {
+                // 文本文件没有类型信息，能解析为数字的字段视为数字
+                let kind = if value.trim().parse::<f64>().is_ok() {
+                    "number"
+                } else {
+                    "string"
+                };
+                CellData {
+                    typed: TypedValue::from_kind(kind, value.trim()),
+                    kind: Some(kind.to_string()),
+                    value,
+                    column: col_index as u32 + 1,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        if !cells.is_empty() {
+            table_data.rows.push(RowData {
+                row_number: row_index as u32 + 1,
+                cells,
+            });
+        }
+    }
+    Ok(table_data)
+}
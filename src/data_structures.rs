@@ -1,74 +1,744 @@
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Serialize, Deserialize)]
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::format_utils::{duration_to_time, serial_to_iso};
+use crate::utils::number_to_column;
+
+/// Every field may be left out when the data comes back from Typst, e.g. for `from_typst`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct TableData {
     pub dimensions: TableDimensions,
+    /// Leading rows to be repeated as `table.header`.
+    pub header: Vec<RowData>,
     pub rows: Vec<RowData>,
+    /// Trailing rows to be repeated as `table.footer`.
+    pub footer: Vec<RowData>,
     pub merged_cells: Vec<MergedCell>,
+    pub row_borders: BTreeMap<String, Border>,
+    pub column_borders: BTreeMap<String, Border>,
+    pub frame: Option<Border>,
+    /// Whether the sheet displays gridlines, known for xlsx files only.
+    pub show_gridlines: Option<bool>,
+    /// Stroke of the synthesized gridlines, drawn on the sides without a border.
+    pub gridline: Option<Stroke>,
+    /// Row numbers of the rows hidden in the sheet, kept with `hidden = "mark"`.
+    pub hidden_rows: Vec<u32>,
+    /// Column numbers of the columns hidden in the sheet, kept with `hidden = "mark"`.
+    pub hidden_columns: Vec<u32>,
+    /// Rows hidden by the autofilter of the sheet, removed with the `respect_filter` option.
+    #[serde(skip)]
+    pub filtered_rows: Vec<u32>,
+    /// Number of leading rows frozen in the sheet view.
+    pub frozen_rows: Option<u32>,
+    /// Number of leading columns frozen in the sheet view.
+    pub frozen_cols: Option<u32>,
+    /// Page setup of the sheet, known for xlsx files only.
+    pub page_setup: Option<PageSetup>,
+    /// Header and footer texts of the printed pages, known for xlsx files only.
+    pub header_footer: Option<HeaderFooter>,
+    /// Rows followed by a manual page break.
+    pub page_breaks: Vec<u32>,
+    /// Print area of the sheet as an A1 range, e.g. "A1:F40".
+    pub print_area: Option<String>,
+    /// Rows repeated at the top of every printed page (print titles).
+    pub print_title_rows: Option<RowRange>,
+    /// Outline (grouping) level of the grouped rows, keyed by row number.
+    pub row_outline_levels: BTreeMap<String, u32>,
+    /// Outline (grouping) level of the grouped columns, keyed by column number.
+    pub column_outline_levels: BTreeMap<String, u32>,
+    pub banding: Option<Banding>,
+    pub heading_rows: Vec<DetectedRow>,
+    pub total_rows: Vec<DetectedRow>,
+    /// Multi-level column headers built from horizontal merges in the top rows.
+    pub header_groups: Vec<HeaderGroup>,
+    pub orientation: Option<Orientation>,
+    /// Sub-tables to be rendered one after another instead of `rows`.
+    pub segments: Vec<TableData>,
+    /// Deduplicated cell styles keyed by class name.
+    pub styles: BTreeMap<String, CellStyle>,
+    /// Runs of rows without any cell.
+    pub blank_rows: Vec<BlankRun>,
+    /// Runs of empty columns within each row, keyed by row number.
+    pub blank_cells: BTreeMap<String, Vec<BlankRun>>,
+    /// Title and description for accessible output.
+    pub metadata: Option<TableMetadata>,
+    /// The table (list object) converted by `table_to_typst`.
+    pub list_object: Option<ListObject>,
+    /// Plain values replacing the rows in the `values_only` mode.
+    pub values: Option<ValueGrid>,
+    /// Body rows keyed by column header, when the `keyed_rows` option is set.
+    pub records: Vec<BTreeMap<String, String>>,
+    /// Problems that didn't stop the conversion: unsupported features, skipped cells and fallbacks.
+    pub warnings: Vec<Warning>,
+    /// Hash of the workbook and parse arguments, for skipping unchanged sheets.
+    pub hash: Option<String>,
+}
+
+/// A problem that didn't stop the conversion.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Warning {
+    /// Stable identifier, e.g. "missing_result", "error_cell" or "unsupported_option".
+    pub code: String,
+    pub message: String,
+    /// Coordinates of the affected cells in the sheet, e.g. "B4".
+    pub cells: Vec<String>,
+}
+
+impl TableData {
+    pub fn warn(&mut self, code: &str, message: String, cells: Vec<String>) {
+        self.warnings.push(Warning {
+            code: code.to_string(),
+            message,
+            cells,
+        });
+    }
+
+    /// Warn about the listed cells, if there are any.
+    pub fn warn_cells(&mut self, code: &str, message: &str, cells: Vec<String>) {
+        if !cells.is_empty() {
+            self.warn(code, message.to_string(), cells);
+        }
+    }
+}
+
+/// Accessibility metadata: the table's alt text, falling back to the document title and description.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TableMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub sheet_name: Option<String>,
+    pub table_name: Option<String>,
+}
+
+/// A table (list object) defined on a sheet.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ListObject {
+    pub name: String,
+    /// A1 range of the table, including its header and totals rows.
+    pub range: String,
+    pub header_rows: u32,
+    pub totals_rows: u32,
+    /// Column names, in the order of the columns.
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub style: Option<ListStyle>,
+}
+
+/// The table style of a list object and the parts of the table it emphasizes.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ListStyle {
+    /// Built-in style like "TableStyleMedium2" or a custom style of the workbook.
+    pub name: String,
+    pub first_column: bool,
+    pub last_column: bool,
+    pub row_stripes: bool,
+    pub column_stripes: bool,
+}
+
+/// Response returned instead of the table when the hash matches `previous_hash`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UnchangedData {
+    pub unchanged: bool,
+    pub hash: String,
+}
+
+/// A failed conversion, with a stable code and the location of the offending cell if known.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PluginError {
+    /// "invalid_options", "invalid_workbook", "sheet_not_found", "name_not_found", "error_cell"
+    /// or "conversion_failed".
+    pub code: String,
+    pub message: String,
+    pub sheet: Option<String>,
+    /// Coordinate of the cell, e.g. "B4".
+    pub cell: Option<String>,
+}
+
+impl PluginError {
+    pub fn new(code: &str, message: impl Into<String>) -> Self {
+        PluginError {
+            code: code.to_string(),
+            message: message.into(),
+            sheet: None,
+            cell: None,
+        }
+    }
+
+    pub fn at_cell(mut self, cell: String) -> Self {
+        self.cell = Some(cell);
+        self
+    }
+
+    pub fn in_sheet(mut self, sheet: Option<String>) -> Self {
+        self.sheet = self.sheet.or(sheet);
+        self
+    }
+}
+
+/// Errors of the shared helpers become `conversion_failed`.
+impl From<String> for PluginError {
+    fn from(message: String) -> Self {
+        PluginError::new("conversion_failed", message)
+    }
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        match (&self.sheet, &self.cell) {
+            (Some(sheet), Some(cell)) => write!(f, " (sheet \"{}\", cell {})", sheet, cell),
+            (Some(sheet), None) => write!(f, " (sheet \"{}\")", sheet),
+            (None, Some(cell)) => write!(f, " (cell {})", cell),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Response returned instead of the table when the conversion fails and `structured_errors` is set.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ErrorResponse {
+    pub error: PluginError,
+}
+
+/// Tables of all sheets keyed by sheet name, serialized in workbook order.
+pub struct SheetTables(pub Vec<(String, TableData)>);
+
+impl Serialize for SheetTables {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, table_data) in &self.0 {
+            map.serialize_entry(name, table_data)?;
+        }
+        map.end()
+    }
+}
+
+/// Worksheets of a workbook, in workbook order.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SheetList {
+    pub sheets: Vec<SheetName>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SheetName {
+    /// 0-based index, as taken by the `sheet_index` argument.
+    pub index: usize,
+    pub name: String,
+}
+
+/// Workbook-level overview: the sheets with their used ranges, and the defined names.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorkbookInfo {
+    pub sheet_count: usize,
+    pub sheets: Vec<SheetInfo>,
+    pub defined_names: Vec<DefinedName>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SheetInfo {
+    pub index: usize,
+    pub name: String,
+    /// Range covering the used cells, e.g. "A1:D10", if known.
+    pub used_range: Option<String>,
+    /// "visible", "hidden" or "veryHidden".
+    pub visibility: String,
+    pub tab_color: Option<String>,
+}
+
+/// Color scheme of the workbook theme as "RRGGBB", `None` for workbooks without a theme
+/// (xls, xlsb and ods).
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ThemeColors {
+    pub dark1: Option<String>,
+    pub light1: Option<String>,
+    pub dark2: Option<String>,
+    pub light2: Option<String>,
+    pub accent1: Option<String>,
+    pub accent2: Option<String>,
+    pub accent3: Option<String>,
+    pub accent4: Option<String>,
+    pub accent5: Option<String>,
+    pub accent6: Option<String>,
+    pub hyperlink: Option<String>,
+    pub followed_hyperlink: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DefinedName {
+    pub name: String,
+    /// The formula or range the name refers to, e.g. "Sheet1!$A$1:$B$4".
+    pub refers_to: String,
+    /// Index of the sheet the name is scoped to, `None` for workbook-wide names.
+    pub sheet: Option<usize>,
+}
+
+impl TableData {
+    /// An empty table of the given size, to be filled in by a reader.
+    pub fn new(max_columns: u32, max_rows: u32) -> Self {
+        TableData {
+            dimensions: TableDimensions {
+                max_columns: Some(max_columns),
+                max_rows: Some(max_rows),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// Cell values of every position, row by row, with empty strings for empty and merged-over cells.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ValueGrid {
+    /// Row number of each row of `cells`, for matching merged cells.
+    pub row_numbers: Vec<u32>,
+    pub cells: Vec<Vec<String>>,
+    /// Number of leading rows belonging to the header section.
+    pub header_rows: u32,
+    /// Number of trailing rows belonging to the footer section.
+    pub footer_rows: u32,
+}
+
+/// `length` consecutive empty rows or columns starting at `start`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlankRun {
+    pub start: u32,
+    pub length: u32,
+}
+
+/// Print settings of a sheet, for configuring Typst's `page` to match.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PageSetup {
+    /// "portrait" or "landscape".
+    pub orientation: Option<String>,
+    /// Typst paper name, e.g. "a4" or "us-letter", `None` for sizes unknown to Typst.
+    pub paper: Option<String>,
+    pub margins: Option<PageMargins>,
+    /// Print scale in percent.
+    pub scale: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Page margins in pt; `header` and `footer` are the distances of the header and footer
+/// from the page edge.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PageMargins {
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
+    pub header: f64,
+    pub footer: f64,
+}
+
+/// Headers and footers of the printed pages. The first page and even pages have their own
+/// only when the sheet sets them apart.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HeaderFooter {
+    pub header: Option<HeaderFooterText>,
+    pub footer: Option<HeaderFooterText>,
+    pub first_header: Option<HeaderFooterText>,
+    pub first_footer: Option<HeaderFooterText>,
+    pub even_header: Option<HeaderFooterText>,
+    pub even_footer: Option<HeaderFooterText>,
+}
+
+/// The left, center and right sections of a header or footer, with fields as placeholders:
+/// `{page}`, `{pages}`, `{date}`, `{time}`, `{file}` and `{sheet}`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HeaderFooterText {
+    pub left: Option<String>,
+    pub center: Option<String>,
+    pub right: Option<String>,
+}
+
+/// An inclusive range of row numbers.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RowRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+/// A row flagged by a detection heuristic, with a confidence score in `0..=1`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DetectedRow {
+    pub row: u32,
+    pub confidence: f64,
+}
+
+/// A header cell spanning `start_column..=end_column`, with the header cells below it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HeaderGroup {
+    pub label: String,
+    pub row: u32,
+    pub start_column: u32,
+    pub end_column: u32,
+    pub children: Vec<HeaderGroup>,
+}
+
+/// Whether the labels of the table run across the first row or down the first column.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Orientation {
+    pub header_axis: String,
+    pub confidence: f64,
+    /// Whether the table was transposed so that the labels run across the first row.
+    pub transposed: bool,
+}
+
+/// Alternating row fills, starting at `start_row` and repeating every `period` rows.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Banding {
+    pub start_row: u32,
+    pub period: u32,
+    /// One fill per row of the period, an empty string means no fill.
+    pub colors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct TableDimensions {
     pub columns: Vec<f64>,
     pub rows: Vec<f64>,
     pub max_columns: Option<u32>,
     pub max_rows: Option<u32>,
+    pub column_overrides: BTreeMap<String, String>,
+    pub uniform_row_height: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct RowData {
     pub row_number: u32,
     pub cells: Vec<CellData>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct CellData {
     pub value: String,
     pub column: u32,
     pub style: Option<CellStyle>,
+    /// Name of the shared style in `TableData::styles`, used instead of `style` when style classes are enabled.
+    pub class: Option<String>,
+    /// Auxiliary data from the sources selected by the `cell_meta` option, e.g. `comment` or `hyperlink`.
+    pub meta: Option<BTreeMap<String, String>>,
+    /// Data type of the value: "string", "number", "bool", "date", "error" or "empty".
+    pub kind: Option<String>,
+    /// The value as a number, boolean or string according to `kind`, dates as ISO 8601 text.
+    pub typed: Option<TypedValue>,
+    /// Formula of the cell, starting with "=", when the `parse_formula` option is set.
+    pub formula: Option<String>,
+    /// Set for formula cells saved without a cached result, which come out empty.
+    pub missing_result: Option<bool>,
+    /// Differently formatted fragments of a rich text cell, whose texts make up `value`.
+    pub runs: Option<Vec<TextRun>>,
+    /// Comment thread or note attached to the cell, when the `parse_comments` option is set.
+    pub comment: Option<Comment>,
+    /// Phonetic guide (furigana) of the text, when the `parse_phonetic` option is set.
+    pub phonetic: Option<String>,
+    /// In-cell bar of a data bar conditional formatting rule.
+    pub data_bar: Option<DataBar>,
+    /// Icon of an icon set conditional formatting rule.
+    pub icon: Option<CellIcon>,
+    /// Data validation rule of the cell, when the `parse_validation` option is set.
+    pub validation: Option<DataValidation>,
+    /// Pictures whose top left corner lies in the cell, when the `parse_images` option is set.
+    pub images: Option<Vec<CellImage>>,
+}
+
+/// A picture of the sheet's drawing, attached to the cell of its top left corner.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CellImage {
+    /// Content of the image file, a byte string in CBOR output.
+    #[serde(serialize_with = "serialize_bytes")]
+    pub data: Vec<u8>,
+    /// Format from the file extension, e.g. "png", "jpeg" or "emf".
+    pub format: String,
+    pub name: Option<String>,
+    /// Alternative text of the picture.
+    pub description: Option<String>,
+    /// Offset of the top left corner from the top left of the cell, in pt.
+    pub x: f64,
+    pub y: f64,
+    /// Size of the picture in pt.
+    pub width: f64,
+    pub height: f64,
+    /// Number of columns and rows the picture covers, starting at its cell.
+    pub colspan: u32,
+    pub rowspan: u32,
+}
+
+fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(bytes)
+}
+
+/// Bar drawn in a cell by a data bar rule.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DataBar {
+    /// Length of the bar as a fraction of the cell width, from 0 to 1.
+    pub fraction: f64,
+    pub color: String,
+    /// Whether the cell value is shown along with the bar.
+    pub show_value: bool,
+}
+
+/// Icon shown in a cell by an icon set rule.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CellIcon {
+    /// Name of the icon set, e.g. "3Arrows", "4TrafficLights" or "5Rating".
+    pub set: String,
+    /// Position of the icon in the set, 0 being the icon of the lowest values.
+    pub index: u32,
+    /// Whether the cell value is shown along with the icon.
+    pub show_value: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+/// A data validation rule: the values allowed in a cell and the messages shown for it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DataValidation {
+    /// Kind of allowed values: "any", "list", "whole", "decimal", "date", "time", "textLength"
+    /// or "custom".
+    pub kind: String,
+    /// Comparison with the bounds, e.g. "between" or "greaterThan", for numbers, dates, times
+    /// and text lengths.
+    pub operator: Option<String>,
+    /// First bound, source of a list or custom formula, as written in the workbook.
+    pub formula1: Option<String>,
+    /// Second bound of "between" and "notBetween".
+    pub formula2: Option<String>,
+    /// Items of a list, from the literal list or the cells it refers to.
+    pub values: Option<Vec<String>>,
+    pub allow_blank: bool,
+    pub prompt_title: Option<String>,
+    /// Input message shown when the cell is selected.
+    pub prompt: Option<String>,
+    pub error_title: Option<String>,
+    /// Message shown when an invalid value is entered.
+    pub error: Option<String>,
+}
+
+/// A cell comment: a legacy note, or the first comment of a thread with its replies.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Comment {
+    pub text: String,
+    pub author: Option<String>,
+    /// Time the comment was written (ISO 8601), known for threaded comments only.
+    pub timestamp: Option<String>,
+    /// Whether the thread is marked resolved, `None` for notes.
+    pub resolved: Option<bool>,
+    pub replies: Vec<Comment>,
+}
+
+/// A fragment of rich text. Properties left `None` follow the font of the cell.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TextRun {
+    pub text: String,
+    pub font: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underline: Option<bool>,
+    pub strike: Option<bool>,
+    pub size: Option<f64>,
+    pub color: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TypedValue {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl TypedValue {
+    /// Typed value of a cell from its kind and string value.
+    pub fn from_kind(kind: &str, value: &str) -> Option<TypedValue> {
+        match kind {
+            // 日期统一为 ISO 8601 文本：序列号先换算，ods 的日期本身就是 ISO 8601
+            "date" => Some(TypedValue::Text(match value.parse::<f64>() {
+                Ok(serial) => serial_to_iso(serial),
+                Err(_) => duration_to_time(value).unwrap_or_else(|| value.to_string()),
+            })),
+            "number" => Some(
+                value
+                    .parse()
+                    .map_or_else(|_| TypedValue::Text(value.to_string()), TypedValue::Number),
+            ),
+            "bool" => Some(TypedValue::Bool(
+                value.eq_ignore_ascii_case("true") || value == "1",
+            )),
+            "empty" => None,
+            _ => Some(TypedValue::Text(value.to_string())),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct CellStyle {
     pub alignment: Option<Alignment>,
     pub border: Option<Border>,
+    /// Fill color; the blend of the two colors for pattern fills.
     pub color: Option<String>,
     pub font: Option<FontStyle>,
+    /// Pattern fill other than a solid color, e.g. "gray125" or "lightUp".
+    #[serde(default)]
+    pub pattern: Option<PatternFill>,
+    /// Gradient fill, with `color` holding its average color.
+    #[serde(default)]
+    pub gradient: Option<GradientFill>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct GradientFill {
+    /// "linear", or "path" for gradients spreading from the center outwards.
+    pub kind: String,
+    /// Direction of a linear gradient in degrees, 0 running left to right and 90 top to bottom.
+    pub angle: f64,
+    pub stops: Vec<GradientStop>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct GradientStop {
+    /// Position along the gradient between 0 and 1.
+    pub position: f64,
+    pub color: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct PatternFill {
+    /// Pattern type as named in the xlsx file, e.g. "darkGrid".
+    pub pattern: String,
+    /// Color of the pattern lines or dots as "RRGGBB".
+    pub foreground: String,
+    /// Color behind the pattern as "RRGGBB".
+    pub background: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Position {
     pub row: u32,
     pub column: u32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct MergedCell {
     pub range: String,
     pub start: Position,
     pub end: Position,
 }
 
-#[derive(Serialize, Deserialize)]
+impl MergedCell {
+    pub fn new(start: Position, end: Position) -> Self {
+        let mut merged_cell = MergedCell {
+            range: String::new(),
+            start,
+            end,
+        };
+        merged_cell.update_range();
+        merged_cell
+    }
+
+    /// Rebuild `range`, e.g. "B2:C4", after `start` or `end` moved.
+    pub fn update_range(&mut self) {
+        self.range = format!(
+            "{}{}:{}{}",
+            number_to_column(self.start.column),
+            self.start.row,
+            number_to_column(self.end.column),
+            self.end.row
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Alignment {
     pub horizontal: String,
     pub vertical: String,
+    /// Whether the text wraps within the cell ("wrap text" in Excel).
+    #[serde(default)]
+    pub wrap: bool,
+    /// Text rotation in degrees, counterclockwise from -90 to 90.
+    #[serde(default)]
+    pub rotation: i32,
+    /// Characters stacked top to bottom without rotating them ("vertical text" in Excel).
+    #[serde(default)]
+    pub stacked: bool,
+    /// Vertical East Asian layout: upright characters top to bottom, lines right to left.
+    #[serde(default)]
+    pub east_asian_vertical: bool,
+    /// Indent level, each level being about the width of three characters.
+    #[serde(default)]
+    pub indent: u32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct Border {
     pub left: bool,
     pub right: bool,
     pub top: bool,
     pub bottom: bool,
+    /// Diagonal line from the bottom-left to the top-right corner.
+    #[serde(default)]
+    pub diagonal_up: bool,
+    /// Diagonal line from the top-left to the bottom-right corner.
+    #[serde(default)]
+    pub diagonal_down: bool,
+    pub strokes: Option<BorderStrokes>,
+    /// The four sides as a Typst stroke dictionary, e.g. `(top: 0.75pt, bottom: none)`, when the
+    /// `stroke_expressions` option is set.
+    #[serde(default)]
+    pub stroke: Option<String>,
+}
+
+/// Stroke descriptors of the drawn sides: the Excel border style with its thickness and color.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct BorderStrokes {
+    pub left: Option<Stroke>,
+    pub right: Option<Stroke>,
+    pub top: Option<Stroke>,
+    pub bottom: Option<Stroke>,
+    /// Shared by both diagonals, as in Excel.
+    #[serde(default)]
+    pub diagonal: Option<Stroke>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Stroke {
+    pub style: String,
+    /// Thickness of a single line in pt.
+    pub thickness: f64,
+    /// Gap between the two lines of a double stroke in pt.
+    pub gap: Option<f64>,
+    /// Alternating dash and gap lengths in pt.
+    pub dash: Option<Vec<f64>>,
+    /// Line color as "RRGGBB", `None` for the automatic (black) color.
+    pub color: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct FontStyle {
+    /// Font family, resolved through the theme when the font follows the major/minor scheme.
+    pub name: Option<String>,
+    pub east_asian_name: Option<String>,
+    pub complex_script_name: Option<String>,
+    /// Theme font scheme the font follows, "major" (headings) or "minor" (body).
+    #[serde(default)]
+    pub scheme: Option<String>,
     pub bold: bool,
     pub italic: bool,
     pub size: f64,
     pub color: Option<String>,
     pub underline: bool,
+    /// Kind of underline: "single", "double", "singleAccounting" or "doubleAccounting".
+    #[serde(default)]
+    pub underline_style: Option<String>,
     pub strike: bool,
+    /// "superscript" or "subscript", `None` for text on the baseline.
+    #[serde(default)]
+    pub vertical_align: Option<String>,
+    pub outline: bool,
+    pub shadow: bool,
+    pub condense: bool,
+    pub extend: bool,
 }
\ No newline at end of file
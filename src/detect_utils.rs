@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use umya_spreadsheet::Worksheet;
+
+use crate::border_utils::covered_positions;
+use crate::data_structures::{
+    Banding, CellData, DetectedRow, HeaderGroup, MergedCell, Orientation, RowData, TableData,
+};
+use crate::utils::parse_cell_reference;
+
+fn cell_fill(cell: &CellData) -> String {
+    cell.style
+        .as_ref()
+        .and_then(|style| style.color.clone())
+        .unwrap_or_default()
+}
+
+/// The fill shared by every cell of each row, `None` when the cells disagree.
+/// An empty string stands for "no fill", the same as in `CellStyle::color`.
+fn row_fills(table_data: &TableData) -> HashMap<u32, Option<String>> {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let covered = covered_positions(table_data);
+    let mut fills = HashMap::new();
+    for row in &table_data.rows {
+        let cells: HashMap<u32, &CellData> = row.cells.iter().map(|c| (c.column, c)).collect();
+        let mut fill: Option<String> = None;
+        let mut uniform = true;
+        for col_num in 1..=max_col {
+            if covered.contains(&(row.row_number, col_num)) {
+                continue;
+            }
+            let color = cells.get(&col_num).map(|c| cell_fill(c)).unwrap_or_default();
+            match &fill {
+                Some(first) if *first != color => {
+                    uniform = false;
+                    break;
+                }
+                _ => fill = Some(color),
+            }
+        }
+        fills.insert(row.row_number, if uniform { fill } else { None });
+    }
+    fills
+}
+
+/// Detect alternating row fills and replace them with a `banding` hint.
+pub fn detect_banding(table_data: &mut TableData) {
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    let fills = row_fills(table_data);
+
+    // 允许第一行作为表头不参与条纹
+    let mut banding = None;
+    'search: for start_row in 1..=2 {
+        for period in 2..=3 {
+            if max_row < start_row + period * 2 - 1 {
+                continue;
+            }
+            let colors: Option<Vec<String>> = (start_row..start_row + period)
+                .map(|row_num| fills.get(&row_num).cloned().flatten())
+                .collect();
+            let colors = match colors {
+                Some(colors) => colors,
+                None => continue,
+            };
+            if colors.iter().all(|color| *color == colors[0]) {
+                continue;
+            }
+            let matches = (start_row..=max_row).all(|row_num| {
+                let expected = &colors[((row_num - start_row) % period) as usize];
+                fills.get(&row_num).cloned().flatten().as_ref() == Some(expected)
+            });
+            if matches {
+                banding = Some(Banding {
+                    start_row,
+                    period,
+                    colors,
+                });
+                break 'search;
+            }
+        }
+    }
+
+    let banding = match banding {
+        Some(banding) => banding,
+        None => return,
+    };
+    for row in &mut table_data.rows {
+        if row.row_number < banding.start_row {
+            continue;
+        }
+        for cell in &mut row.cells {
+            if let Some(style) = cell.style.as_mut() {
+                style.color = None;
+            }
+        }
+    }
+    table_data.banding = Some(banding);
+}
+
+/// Share of styled features among a group of cells, each in `0..=1`.
+struct RowFeatures {
+    bold: f64,
+    filled: f64,
+    bottom_border: f64,
+    text: f64,
+}
+
+fn row_features(rows: &[&RowData]) -> Option<RowFeatures> {
+    let cells: Vec<&CellData> = rows.iter().flat_map(|row| row.cells.iter()).collect();
+    cell_features(&cells)
+}
+
+fn cell_features(cells: &[&CellData]) -> Option<RowFeatures> {
+    if cells.is_empty() {
+        return None;
+    }
+    let ratio = |f: &dyn Fn(&CellData) -> bool| {
+        cells.iter().filter(|&&cell| f(cell)).count() as f64 / cells.len() as f64
+    };
+    Some(RowFeatures {
+        bold: ratio(&|cell| {
+            cell.style
+                .as_ref()
+                .and_then(|style| style.font.as_ref())
+                .is_some_and(|font| font.bold)
+        }),
+        filled: ratio(&|cell| !cell_fill(cell).is_empty()),
+        bottom_border: ratio(&|cell| {
+            cell.style
+                .as_ref()
+                .and_then(|style| style.border.as_ref())
+                .is_some_and(|border| border.bottom)
+        }),
+        text: ratio(&|cell| !cell.value.is_empty() && cell.value.trim().parse::<f64>().is_err()),
+    })
+}
+
+/// Score the leading rows by how much their styling differs from the rows below them.
+pub fn detect_headings(table_data: &mut TableData) {
+    const MAX_HEADING_ROWS: usize = 5;
+    const MIN_CONFIDENCE: f64 = 0.3;
+
+    let mut heading_rows = Vec::new();
+    for (i, row) in table_data.rows.iter().enumerate().take(MAX_HEADING_ROWS) {
+        let body: Vec<&RowData> = table_data.rows.iter().skip(i + 1).collect();
+        let (heading, body) = match (row_features(&[row]), row_features(&body)) {
+            (Some(heading), Some(body)) => (heading, body),
+            _ => break,
+        };
+        let confidence = 0.35 * (heading.bold - body.bold).max(0.0)
+            + 0.3 * (heading.filled - body.filled).max(0.0)
+            + 0.2 * (heading.bottom_border - body.bottom_border).max(0.0)
+            + 0.15 * (heading.text - body.text).max(0.0);
+        // 表头必须从第一行开始连续出现
+        if confidence < MIN_CONFIDENCE {
+            break;
+        }
+        heading_rows.push(DetectedRow {
+            row: row.row_number,
+            confidence: (confidence * 100.0).round() / 100.0,
+        });
+    }
+    table_data.heading_rows = heading_rows;
+}
+
+fn header_nodes(
+    table_data: &TableData,
+    covered: &HashSet<(u32, u32)>,
+    row_num: u32,
+    columns: (u32, u32),
+    last_row: u32,
+) -> Vec<HeaderGroup> {
+    let row = table_data.rows.iter().find(|row| row.row_number == row_num);
+    let mut nodes = Vec::new();
+    for col_num in columns.0..=columns.1 {
+        if covered.contains(&(row_num, col_num)) {
+            continue;
+        }
+        let merge: Option<&MergedCell> = table_data
+            .merged_cells
+            .iter()
+            .find(|mc| mc.start.row == row_num && mc.start.column == col_num);
+        let (end_column, end_row) = merge.map_or((col_num, row_num), |mc| {
+            (mc.end.column.min(columns.1), mc.end.row)
+        });
+        let label = row
+            .and_then(|row| row.cells.iter().find(|cell| cell.column == col_num))
+            .map(|cell| cell.value.clone())
+            .unwrap_or_default();
+        // 纵向合并到最底层的单元格没有子列
+        let children = if end_row < last_row {
+            header_nodes(table_data, covered, end_row + 1, (col_num, end_column), last_row)
+        } else {
+            Vec::new()
+        };
+        nodes.push(HeaderGroup {
+            label,
+            row: row_num,
+            start_column: col_num,
+            end_column,
+            children,
+        });
+    }
+    nodes
+}
+
+/// Build a header tree from the leading rows that contain horizontal merges;
+/// the row below them holds the leaf columns.
+pub fn detect_header_groups(table_data: &mut TableData) {
+    const MAX_GROUP_ROWS: usize = 4;
+
+    let is_group_row = |row_num: u32| {
+        table_data
+            .merged_cells
+            .iter()
+            .any(|mc| mc.start.row == row_num && mc.end.column > mc.start.column)
+    };
+    let group_rows = table_data
+        .rows
+        .iter()
+        .take(MAX_GROUP_ROWS)
+        .take_while(|row| is_group_row(row.row_number))
+        .count();
+    if group_rows == 0 || table_data.rows.len() <= group_rows {
+        return;
+    }
+    let first_row = table_data.rows[0].row_number;
+    let last_row = table_data.rows[group_rows].row_number;
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let covered = covered_positions(table_data);
+    table_data.header_groups = header_nodes(table_data, &covered, first_row, (1, max_col), last_row);
+}
+
+const TOTAL_LABELS: [&str; 8] = [
+    "total", "subtotal", "grand total", "sum", "合计", "总计", "小计", "總計",
+];
+
+fn has_double_edge(cell: &CellData, top: bool) -> bool {
+    cell.style
+        .as_ref()
+        .and_then(|style| style.border.as_ref())
+        .and_then(|border| border.strokes.as_ref())
+        .and_then(|strokes| if top { strokes.top.as_ref() } else { strokes.bottom.as_ref() })
+        .is_some_and(|stroke| stroke.style == "double")
+}
+
+/// Rows containing a SUM or SUBTOTAL formula.
+pub fn get_sum_rows(worksheet: &Worksheet) -> HashSet<u32> {
+    let mut sum_rows = HashSet::new();
+    for cell in worksheet.get_cell_collection() {
+        let formula = cell.get_formula().to_ascii_uppercase();
+        if formula.contains("SUM(") || formula.contains("SUBTOTAL(") {
+            let (_, row_num) = parse_cell_reference(&cell.get_coordinate().to_string());
+            sum_rows.insert(row_num);
+        }
+    }
+    sum_rows
+}
+
+/// Score the trailing rows by total-row signals: labels, SUM formulas, double rules above and bold text.
+pub fn detect_totals(table_data: &mut TableData, sum_rows: &HashSet<u32>) {
+    const MAX_TOTAL_ROWS: usize = 3;
+    const MIN_CONFIDENCE: f64 = 0.3;
+
+    let rows = &table_data.rows;
+    let mut total_rows = Vec::new();
+    for i in (0..rows.len()).rev().take(MAX_TOTAL_ROWS) {
+        let row = &rows[i];
+        let body: Vec<&RowData> = rows.iter().take(i).collect();
+        let (total, body) = match (row_features(&[row]), row_features(&body)) {
+            (Some(total), Some(body)) => (total, body),
+            _ => break,
+        };
+        let labeled = row.cells.iter().any(|cell| {
+            let value = cell.value.trim().trim_end_matches(':').to_lowercase();
+            TOTAL_LABELS.contains(&value.as_str())
+        });
+        let double_rule = row.cells.iter().any(|cell| has_double_edge(cell, true))
+            || (i > 0 && rows[i - 1].cells.iter().any(|cell| has_double_edge(cell, false)));
+        let confidence = if labeled { 0.35 } else { 0.0 }
+            + if sum_rows.contains(&row.row_number) { 0.3 } else { 0.0 }
+            + if double_rule { 0.2 } else { 0.0 }
+            + 0.15 * (total.bold - body.bold).max(0.0);
+        // 合计行必须从最后一行开始连续出现
+        if confidence < MIN_CONFIDENCE {
+            break;
+        }
+        total_rows.push(DetectedRow {
+            row: row.row_number,
+            confidence: (confidence * 100.0).round() / 100.0,
+        });
+    }
+    total_rows.reverse();
+    table_data.total_rows = total_rows;
+}
+
+fn header_score(header: &RowFeatures, body: &RowFeatures) -> f64 {
+    0.4 * (header.text - body.text).max(0.0)
+        + 0.3 * (header.bold - body.bold).max(0.0)
+        + 0.3 * (header.filled - body.filled).max(0.0)
+}
+
+/// Compare the first row with the first column to tell whether labels run across the top
+/// (`"row"`) or down the side (`"column"`).
+pub fn detect_orientation(table_data: &mut TableData) {
+    const MIN_CONFIDENCE: f64 = 0.3;
+
+    let mut first_row = Vec::new();
+    let mut first_column = Vec::new();
+    let mut other_rows = Vec::new();
+    let mut other_columns = Vec::new();
+    for (i, row) in table_data.rows.iter().enumerate() {
+        for cell in &row.cells {
+            if i == 0 {
+                first_row.push(cell);
+            } else {
+                other_rows.push(cell);
+            }
+            if cell.column == 1 {
+                first_column.push(cell);
+            } else {
+                other_columns.push(cell);
+            }
+        }
+    }
+
+    let score = |header: &[&CellData], body: &[&CellData]| {
+        match (cell_features(header), cell_features(body)) {
+            (Some(header), Some(body)) => header_score(&header, &body),
+            _ => 0.0,
+        }
+    };
+    let row_score = score(&first_row, &other_rows);
+    let column_score = score(&first_column, &other_columns);
+
+    let (header_axis, confidence) = if column_score > row_score {
+        ("column", column_score)
+    } else {
+        ("row", row_score)
+    };
+    if confidence < MIN_CONFIDENCE {
+        return;
+    }
+    table_data.orientation = Some(Orientation {
+        header_axis: header_axis.to_string(),
+        confidence: (confidence * 100.0).round() / 100.0,
+        transposed: false,
+    });
+}
@@ -0,0 +1,76 @@
+use crate::archive_utils::Archive;
+use crate::xml_utils::XmlNode;
+
+/// Cached values of the workbooks referenced by `[N]Sheet!A1` style formulas.
+pub struct ExternalLinks {
+    /// `externalLink` parts in `externalReferences` order, so `[1]` is the first one.
+    links: Vec<Option<XmlNode>>,
+}
+
+impl ExternalLinks {
+    pub fn read(archive: &mut Archive) -> Result<ExternalLinks, String> {
+        let workbook = match archive.read_xml("xl/workbook.xml")? {
+            Some(workbook) => workbook,
+            None => return Ok(ExternalLinks { links: Vec::new() }),
+        };
+        let rels = archive.relationships("xl/workbook.xml")?;
+        let mut links = Vec::new();
+        let references = workbook
+            .children_named("externalReferences")
+            .flat_map(|references| references.children_named("externalReference"));
+        for reference in references {
+            let target = reference
+                .attr("r:id")
+                .and_then(|rel_id| rels.iter().find(|rel| rel.id == rel_id))
+                .map(|rel| rel.target.clone());
+            links.push(match target {
+                Some(target) => archive.read_xml(&target)?,
+                None => None,
+            });
+        }
+        Ok(ExternalLinks { links })
+    }
+
+    /// Cached value of a formula that consists of a single external cell reference.
+    pub fn value(&self, formula: &str) -> Option<String> {
+        let (index, sheet, reference) = parse_external_reference(formula)?;
+        let book = self.links.get(index.checked_sub(1)?)?.as_ref()?.child("externalBook")?;
+        let sheet_id = book
+            .child("sheetNames")?
+            .children_named("sheetName")
+            .position(|name| name.attr("val").is_some_and(|val| val.eq_ignore_ascii_case(&sheet)))?
+            .to_string();
+        let cell = book
+            .child("sheetDataSet")?
+            .children_named("sheetData")
+            .find(|data| data.attr("sheetId") == Some(sheet_id.as_str()))?
+            .children_named("row")
+            .flat_map(|row| row.children_named("cell"))
+            .find(|cell| cell.attr("r") == Some(reference.as_str()))?;
+        let value = cell.child("v")?.text.clone();
+        // 外部缓存中的字符串直接存放在 v 中，不经过共享字符串表
+        Some(match cell.attr("t") {
+            Some("b") => if value == "1" { "TRUE" } else { "FALSE" }.to_string(),
+            _ => value,
+        })
+    }
+}
+
+/// Split `[1]Sheet1!$A$1` or `='[2]My Sheet'!B3` into (book index, sheet name, cell reference).
+fn parse_external_reference(formula: &str) -> Option<(usize, String, String)> {
+    let formula = formula.trim().trim_start_matches('=');
+    let (book_sheet, reference) = formula.rsplit_once('!')?;
+    let book_sheet = book_sheet
+        .strip_prefix('\'')
+        .and_then(|quoted| quoted.strip_suffix('\''))
+        .map(|quoted| quoted.replace("''", "'"))
+        .unwrap_or_else(|| book_sheet.to_string());
+    let (index, sheet) = book_sheet.strip_prefix('[')?.split_once(']')?;
+    let reference = reference.replace('$', "").to_ascii_uppercase();
+    let letters = reference.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+    let (column, row) = reference.split_at(letters);
+    if column.is_empty() || row.is_empty() || !row.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((index.parse().ok()?, sheet.to_string(), reference))
+}
@@ -0,0 +1,864 @@
+/// Whether a builtin number format id is a date or time format.
+fn is_builtin_date_format(format_id: u32) -> bool {
+    matches!(format_id, 14..=22 | 27..=36 | 45..=47 | 50..=58)
+}
+
+/// Whether the content of a bracket is an elapsed time token such as "h", "mm" or "SS".
+fn is_elapsed_time(inner: &str) -> bool {
+    let lower = inner.to_ascii_lowercase();
+    matches!(lower.chars().next(), Some('h' | 'm' | 's'))
+        && lower
+            .chars()
+            .all(|c| c == lower.chars().next().unwrap_or_default())
+}
+
+/// Whether a number format shows its value as a date or time: the format code contains
+/// date or time tokens outside of quoted text, escapes and bracketed currencies, colors or
+/// conditions.
+pub fn is_date_format(format_id: u32, format_code: &str) -> bool {
+    if is_builtin_date_format(format_id) {
+        return true;
+    }
+    // 只看第一节（正数）
+    let mut in_quotes = false;
+    let mut chars = format_code.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            _ if in_quotes => {}
+            '\\' | '_' | '*' => {
+                chars.next();
+            }
+            // [h]、[mm]、[ss] 这类经过时间也算作时间格式，货币、颜色和条件不算
+            '[' => {
+                let inner: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                if is_elapsed_time(&inner) {
+                    return true;
+                }
+            }
+            ';' => return false,
+            'y' | 'Y' | 'm' | 'M' | 'd' | 'D' | 'h' | 'H' | 's' | 'S' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Format code of a builtin number format id, as Excel shows it for the en-US locale.
+pub fn builtin_format_code(format_id: u32) -> Option<&'static str> {
+    Some(match format_id {
+        0 => "General",
+        1 => "0",
+        2 => "0.00",
+        3 => "#,##0",
+        4 => "#,##0.00",
+        9 => "0%",
+        10 => "0.00%",
+        11 => "0.00E+00",
+        12 => "# ?/?",
+        13 => "# ??/??",
+        14 => "m/d/yyyy",
+        15 => "d-mmm-yy",
+        16 => "d-mmm",
+        17 => "mmm-yy",
+        18 => "h:mm AM/PM",
+        19 => "h:mm:ss AM/PM",
+        20 => "h:mm",
+        21 => "h:mm:ss",
+        22 => "m/d/yyyy h:mm",
+        37 => "#,##0 ;(#,##0)",
+        38 => "#,##0 ;[Red](#,##0)",
+        39 => "#,##0.00;(#,##0.00)",
+        40 => "#,##0.00;[Red](#,##0.00)",
+        45 => "mm:ss",
+        46 => "[h]:mm:ss",
+        47 => "mm:ss.0",
+        48 => "##0.0E+0",
+        49 => "@",
+        _ => return None,
+    })
+}
+
+/// Decimal and grouping separators used when formatting numbers.
+#[derive(Clone, Copy)]
+pub struct NumberLocale {
+    pub decimal: char,
+    pub group: char,
+}
+
+impl Default for NumberLocale {
+    fn default() -> Self {
+        NumberLocale {
+            decimal: '.',
+            group: ',',
+        }
+    }
+}
+
+impl NumberLocale {
+    /// Separators of a BCP 47 locale tag such as "de-DE" or "fr", the en-US ones when unknown.
+    pub fn from_tag(tag: Option<&str>) -> NumberLocale {
+        let tag = tag
+            .unwrap_or_default()
+            .replace('_', "-")
+            .to_ascii_lowercase();
+        let mut parts = tag.split('-');
+        let language = parts.next().unwrap_or_default();
+        let region = parts.next_back().unwrap_or_default();
+        let (decimal, group) = match (language, region) {
+            // 瑞士与列支敦士登使用撇号分组
+            ("de" | "it" | "rm", "ch" | "li") => ('.', '\u{2019}'),
+            (
+                "de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" | "ro" | "hr" | "sl"
+                | "sr" | "vi",
+                _,
+            ) => (',', '.'),
+            (
+                "fr" | "ru" | "uk" | "pl" | "cs" | "sk" | "sv" | "nb" | "no" | "nn" | "fi" | "hu"
+                | "bg" | "lt" | "lv" | "et",
+                _,
+            ) => (',', '\u{a0}'),
+            _ => ('.', ','),
+        };
+        NumberLocale { decimal, group }
+    }
+}
+
+/// Split a format code into its `;` separated sections, ignoring quoted and escaped semicolons.
+fn split_sections(format_code: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (index, c) in format_code.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if !in_quotes => escaped = true,
+            ';' if !in_quotes => {
+                sections.push(&format_code[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    sections.push(&format_code[start..]);
+    sections
+}
+
+/// A bracketed condition such as `[>=100]`.
+fn section_condition(section: &str) -> Option<(String, f64)> {
+    let start = section.find('[')?;
+    let end = start + section[start..].find(']')?;
+    let inner = &section[start + 1..end];
+    let operator: String = inner
+        .chars()
+        .take_while(|c| matches!(c, '<' | '>' | '='))
+        .collect();
+    if operator.is_empty() {
+        return None;
+    }
+    let threshold = inner[operator.len()..].trim().parse().ok()?;
+    Some((operator, threshold))
+}
+
+fn condition_matches((operator, threshold): &(String, f64), value: f64) -> bool {
+    match operator.as_str() {
+        "<" => value < *threshold,
+        "<=" => value <= *threshold,
+        ">" => value > *threshold,
+        ">=" => value >= *threshold,
+        "<>" => value != *threshold,
+        _ => value == *threshold,
+    }
+}
+
+/// The section used for `value` and whether the minus sign still has to be added.
+fn pick_section(format_code: &str, value: f64) -> (&str, bool) {
+    let sections = split_sections(format_code);
+    // 带条件的节按顺序匹配，第一个不带条件的节作为兜底
+    if sections
+        .iter()
+        .any(|section| section_condition(section).is_some())
+    {
+        for section in &sections {
+            match section_condition(section) {
+                Some(condition) if condition_matches(&condition, value) => return (section, false),
+                Some(_) => {}
+                None => return (section, value < 0.0),
+            }
+        }
+        return (sections[0], value < 0.0);
+    }
+    match sections.len() {
+        1 => (sections[0], value < 0.0),
+        2 if value < 0.0 => (sections[1], false),
+        2 => (sections[0], false),
+        _ if value < 0.0 => (sections[1], false),
+        _ if value == 0.0 => (sections[2], false),
+        _ => (sections[0], false),
+    }
+}
+
+/// Excel's General format: integers as is, other numbers with up to 11 significant digits.
+pub fn format_general(value: f64, locale: &NumberLocale) -> String {
+    let text = general_text(value);
+    if locale.decimal == '.' {
+        text
+    } else {
+        text.replace('.', &locale.decimal.to_string())
+    }
+}
+
+fn general_text(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e11 {
+        return format!("{}", value as i64);
+    }
+    let abs = value.abs();
+    if abs != 0.0 && !(1e-9..1e11).contains(&abs) {
+        let formatted = format!("{:.5E}", value);
+        let (mantissa, exponent) = formatted.split_once('E').unwrap_or((&formatted, "0"));
+        let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        return format!(
+            "{}E{}{:02}",
+            mantissa,
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        );
+    }
+    let integer_digits = if abs >= 1.0 {
+        abs.log10().floor() as usize + 1
+    } else {
+        1
+    };
+    let decimals = 10usize.saturating_sub(integer_digits - 1).min(10);
+    let formatted = format!("{:.*}", decimals, value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+enum Token {
+    Literal(String),
+    /// A digit placeholder: '0', '#' or '?'.
+    Digit(char),
+    Point,
+    Comma,
+    Percent,
+    /// Scientific notation, `true` when the exponent always shows its sign (`E+`).
+    Exponent(bool),
+    /// A date or time part such as "yyyy", "mm", "AM/PM" or "[h]".
+    DatePart(String),
+}
+
+fn tokenize(section: &str) -> Vec<Token> {
+    let chars: Vec<char> = section.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                let text: String = chars[i + 1..].iter().take_while(|c| **c != '"').collect();
+                i += text.chars().count() + 2;
+                tokens.push(Token::Literal(text));
+                continue;
+            }
+            '\\' => {
+                if let Some(next) = chars.get(i + 1) {
+                    tokens.push(Token::Literal(next.to_string()));
+                }
+                i += 2;
+                continue;
+            }
+            // 对齐用的空白与填充字符
+            '_' => {
+                tokens.push(Token::Literal(" ".to_string()));
+                i += 2;
+                continue;
+            }
+            '*' => {
+                i += 2;
+                continue;
+            }
+            '[' => {
+                let inner: String = chars[i + 1..].iter().take_while(|c| **c != ']').collect();
+                i += inner.chars().count() + 2;
+                let lower = inner.to_ascii_lowercase();
+                if let Some(currency) = inner.strip_prefix('$') {
+                    // [$€-407]：货币符号与区域代码
+                    let symbol = currency.split('-').next().unwrap_or_default();
+                    tokens.push(Token::Literal(symbol.to_string()));
+                } else if is_elapsed_time(&inner) {
+                    tokens.push(Token::DatePart(format!("[{}]", lower)));
+                }
+                // 颜色和条件不影响文本
+                continue;
+            }
+            '0' | '#' | '?' => tokens.push(Token::Digit(c)),
+            '.' => tokens.push(Token::Point),
+            ',' => tokens.push(Token::Comma),
+            '%' => tokens.push(Token::Percent),
+            'E' | 'e' if matches!(chars.get(i + 1), Some('+' | '-')) => {
+                tokens.push(Token::Exponent(chars[i + 1] == '+'));
+                i += 2;
+                continue;
+            }
+            _ => {
+                let rest: String = chars[i..].iter().collect();
+                let upper = rest.to_ascii_uppercase();
+                if upper.starts_with("AM/PM") {
+                    tokens.push(Token::DatePart("AM/PM".to_string()));
+                    i += 5;
+                    continue;
+                }
+                if upper.starts_with("A/P") {
+                    tokens.push(Token::DatePart("A/P".to_string()));
+                    i += 3;
+                    continue;
+                }
+                let lower = c.to_ascii_lowercase();
+                if matches!(lower, 'y' | 'm' | 'd' | 'h' | 's') {
+                    let count = chars[i..]
+                        .iter()
+                        .take_while(|next| next.to_ascii_lowercase() == lower)
+                        .count();
+                    tokens.push(Token::DatePart(lower.to_string().repeat(count)));
+                    i += count;
+                    continue;
+                }
+                tokens.push(Token::Literal(c.to_string()));
+            }
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// Group the digits of an integer every three digits.
+fn group_thousands(digits: &str, separator: char) -> String {
+    let mut grouped = String::new();
+    for (index, c) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+fn format_digits(tokens: &[Token], value: f64, locale: &NumberLocale) -> String {
+    let point = tokens
+        .iter()
+        .position(|token| matches!(token, Token::Point));
+    let exponent = tokens
+        .iter()
+        .position(|token| matches!(token, Token::Exponent(_)));
+    let number_end = exponent.unwrap_or(tokens.len());
+    let integer_end = point.unwrap_or(number_end).min(number_end);
+    let placeholders = |range: std::ops::Range<usize>| -> Vec<char> {
+        tokens[range]
+            .iter()
+            .filter_map(|token| match token {
+                Token::Digit(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    };
+    let integer_placeholders = placeholders(0..integer_end);
+    let decimal_placeholders = match point {
+        Some(point) if point < number_end => placeholders(point + 1..number_end),
+        _ => Vec::new(),
+    };
+    let exponent_placeholders = match exponent {
+        Some(exponent) => placeholders(exponent + 1..tokens.len()),
+        None => Vec::new(),
+    };
+
+    // 数字占位符之间的逗号是千位分隔符，紧跟在最后一个整数占位符后的逗号每个除以 1000
+    let last_integer_digit = tokens[..integer_end]
+        .iter()
+        .rposition(|token| matches!(token, Token::Digit(_)));
+    let mut grouping = false;
+    let mut scale = 0;
+    for (index, token) in tokens[..integer_end].iter().enumerate() {
+        if matches!(token, Token::Comma) {
+            match last_integer_digit {
+                Some(last) if index < last => grouping = true,
+                Some(_) => scale += 1,
+                None => {}
+            }
+        }
+    }
+    let percents = tokens
+        .iter()
+        .filter(|token| matches!(token, Token::Percent))
+        .count();
+    let mut number = value.abs() * 100f64.powi(percents as i32) / 1000f64.powi(scale);
+
+    let mut exponent_value = 0;
+    if exponent.is_some() && number != 0.0 {
+        let integer_digits = integer_placeholders.len().max(1) as i32;
+        exponent_value = number.log10().floor() as i32 - (integer_digits - 1);
+        number /= 10f64.powi(exponent_value);
+    }
+
+    let formatted = format!("{:.*}", decimal_placeholders.len(), number);
+    let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+    let integer = if integer == "0" && !integer_placeholders.contains(&'0') {
+        ""
+    } else {
+        integer
+    };
+    let required = integer_placeholders.iter().filter(|c| **c == '0').count();
+    let integer = format!("{:0>width$}", integer, width = required);
+    let integer = if grouping {
+        group_thousands(&integer, locale.group)
+    } else {
+        integer
+    };
+    // 不分组时按占位符从右往左逐位填入，以支持 "(###) ###-####" 这类带分隔文字的格式；
+    // 多出的高位数字都写入第一个占位符
+    let mut integer_parts = vec![String::new(); integer_placeholders.len()];
+    if grouping || integer_placeholders.is_empty() {
+        if let Some(first) = integer_parts.first_mut() {
+            *first = integer.clone();
+        }
+    } else {
+        let mut digits: Vec<char> = integer.chars().collect();
+        for (index, placeholder) in integer_placeholders.iter().enumerate().rev() {
+            match digits.pop() {
+                Some(digit) => integer_parts[index].push(digit),
+                None if *placeholder == '?' => integer_parts[index].push(' '),
+                None => {}
+            }
+        }
+        integer_parts[0].insert_str(0, &digits.into_iter().collect::<String>());
+    }
+
+    // 小数部分末尾的 0：'#' 省略，'?' 以空格代替
+    let mut fraction: Vec<char> = fraction.chars().collect();
+    for (index, placeholder) in decimal_placeholders.iter().enumerate().rev() {
+        if fraction[index] != '0' {
+            break;
+        }
+        match placeholder {
+            '#' => {
+                fraction.pop();
+            }
+            '?' => fraction[index] = ' ',
+            _ => break,
+        }
+    }
+
+    let mut output = String::new();
+    let mut integer_index = 0;
+    let mut decimal_index = 0;
+    let mut exponent_written = false;
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Literal(text) => output.push_str(text),
+            Token::DatePart(text) => output.push_str(text),
+            Token::Percent => output.push('%'),
+            Token::Comma => {}
+            Token::Point => {
+                if point == Some(index) && index < number_end {
+                    output.push(locale.decimal);
+                }
+            }
+            Token::Digit(_) if index < integer_end => {
+                output.push_str(&integer_parts[integer_index]);
+                integer_index += 1;
+            }
+            Token::Digit(_) if index < number_end => {
+                if let Some(c) = fraction.get(decimal_index) {
+                    output.push(*c);
+                }
+                decimal_index += 1;
+            }
+            Token::Digit(_) => {
+                if !exponent_written {
+                    let digits = format!(
+                        "{:0>width$}",
+                        exponent_value.abs(),
+                        width = exponent_placeholders.len()
+                    );
+                    output.push_str(&digits);
+                    exponent_written = true;
+                }
+            }
+            Token::Exponent(always_sign) => {
+                output.push('E');
+                if exponent_value < 0 {
+                    output.push('-');
+                } else if *always_sign {
+                    output.push('+');
+                }
+            }
+        }
+    }
+    output
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const DAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Date and time of an Excel serial number in the 1900 date system.
+pub struct SerialDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millisecond: u32,
+    /// Day of the week, 0 for Sunday.
+    pub weekday: u32,
+}
+
+/// Convert an Excel serial number (days since 1899-12-31, counting the nonexistent
+/// 1900-02-29) into its calendar date and time.
+pub fn serial_to_datetime(serial: f64) -> SerialDateTime {
+    let total_ms = (serial * 86_400_000.0).round() as i64;
+    let mut days = total_ms.div_euclid(86_400_000);
+    let ms_of_day = total_ms.rem_euclid(86_400_000);
+    // Excel 把 1900 年当作闰年，序列号 60 之后的日期需要减去一天
+    if days >= 60 {
+        days -= 1;
+    }
+    // 以 1899-12-31 为第 0 天，换算为自 1970-01-01 起的天数
+    let unix_days = days - 25_568;
+    let weekday = (unix_days + 4).rem_euclid(7) as u32;
+    // Howard Hinnant 的 civil_from_days 算法
+    let z = unix_days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    SerialDateTime {
+        year,
+        month,
+        day,
+        hour: (ms_of_day / 3_600_000) as u32,
+        minute: (ms_of_day / 60_000 % 60) as u32,
+        second: (ms_of_day / 1000 % 60) as u32,
+        millisecond: (ms_of_day % 1000) as u32,
+        weekday,
+    }
+}
+
+/// ISO 8601 text of an Excel serial number: a date for whole numbers, a time below 1,
+/// and a date and time otherwise.
+pub fn serial_to_iso(serial: f64) -> String {
+    let datetime = serial_to_datetime((serial * 86_400.0).round() / 86_400.0);
+    let date = format!(
+        "{:04}-{:02}-{:02}",
+        datetime.year, datetime.month, datetime.day
+    );
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        datetime.hour, datetime.minute, datetime.second
+    );
+    if serial.fract() == 0.0 {
+        date
+    } else if (0.0..1.0).contains(&serial) {
+        time
+    } else {
+        format!("{}T{}", date, time)
+    }
+}
+
+/// Time of day of an ISO 8601 duration such as "PT12H30M00S", as used by ODS time cells.
+pub fn duration_to_time(duration: &str) -> Option<String> {
+    let rest = duration.strip_prefix("PT")?;
+    let (hours, rest) = rest.split_once('H')?;
+    let (minutes, rest) = rest.split_once('M')?;
+    let seconds = rest.strip_suffix('S')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    let seconds: f64 = seconds.parse().ok()?;
+    Some(format!(
+        "{:02}:{:02}:{:02}",
+        hours,
+        minutes,
+        seconds.round() as u32
+    ))
+}
+
+fn format_date(tokens: &[Token], value: f64, locale: &NumberLocale) -> String {
+    let has_meridiem = tokens
+        .iter()
+        .any(|token| matches!(token, Token::DatePart(part) if part == "AM/PM" || part == "A/P"));
+    // 秒的小数位（如 "ss.00"）决定舍入精度
+    let second_decimals = tokens
+        .iter()
+        .position(|token| matches!(token, Token::DatePart(part) if part.starts_with('s')))
+        .and_then(|index| match tokens.get(index + 1) {
+            Some(Token::Point) => Some(
+                tokens[index + 2..]
+                    .iter()
+                    .take_while(|token| matches!(token, Token::Digit('0')))
+                    .count(),
+            ),
+            _ => None,
+        });
+    let datetime = match second_decimals {
+        Some(_) => serial_to_datetime(value),
+        // 不显示毫秒时四舍五入到整秒
+        None => serial_to_datetime((value * 86_400.0).round() / 86_400.0),
+    };
+    let is_minute = |index: usize| {
+        // m 紧跟在 h 之后或紧接着 s 时表示分钟
+        let previous = tokens[..index]
+            .iter()
+            .rev()
+            .find(|token| matches!(token, Token::DatePart(_)));
+        let next = tokens[index + 1..]
+            .iter()
+            .find(|token| matches!(token, Token::DatePart(_)));
+        matches!(previous, Some(Token::DatePart(part)) if part.starts_with('h') || part.starts_with("[h"))
+            || matches!(next, Some(Token::DatePart(part)) if part.starts_with('s'))
+    };
+
+    let mut output = String::new();
+    let mut skip_fraction = false;
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::DatePart(part) => {
+                skip_fraction = false;
+                let text = match part.as_str() {
+                    "yy" | "y" => format!("{:02}", datetime.year.rem_euclid(100)),
+                    part if part.starts_with('y') => format!("{:04}", datetime.year),
+                    "m" | "mm" if is_minute(index) => {
+                        format!("{:0width$}", datetime.minute, width = part.len())
+                    }
+                    "m" => datetime.month.to_string(),
+                    "mm" => format!("{:02}", datetime.month),
+                    "mmm" => MONTH_NAMES[datetime.month as usize - 1][..3].to_string(),
+                    "mmmmm" => MONTH_NAMES[datetime.month as usize - 1][..1].to_string(),
+                    part if part.starts_with('m') => {
+                        MONTH_NAMES[datetime.month as usize - 1].to_string()
+                    }
+                    "d" => datetime.day.to_string(),
+                    "dd" => format!("{:02}", datetime.day),
+                    "ddd" => DAY_NAMES[datetime.weekday as usize][..3].to_string(),
+                    part if part.starts_with('d') => {
+                        DAY_NAMES[datetime.weekday as usize].to_string()
+                    }
+                    part if part.starts_with('h') => {
+                        let hour = if has_meridiem {
+                            (datetime.hour + 11) % 12 + 1
+                        } else {
+                            datetime.hour
+                        };
+                        format!("{:0width$}", hour, width = part.len().min(2))
+                    }
+                    part if part.starts_with('s') => {
+                        skip_fraction = second_decimals.is_some();
+                        format!("{:0width$}", datetime.second, width = part.len().min(2))
+                    }
+                    "AM/PM" => if datetime.hour < 12 { "AM" } else { "PM" }.to_string(),
+                    "A/P" => if datetime.hour < 12 { "A" } else { "P" }.to_string(),
+                    // 经过时间：[h]、[mm]、[ss]
+                    part => {
+                        let total_seconds = (value * 86_400.0).round() as i64;
+                        let elapsed = match part.chars().nth(1) {
+                            Some('h') => total_seconds / 3600,
+                            Some('m') => total_seconds / 60,
+                            _ => total_seconds,
+                        };
+                        format!("{:0width$}", elapsed, width = part.len() - 2)
+                    }
+                };
+                output.push_str(&text);
+            }
+            Token::Point if skip_fraction => {
+                let decimals = second_decimals.unwrap_or(0);
+                let fraction = format!("{:03}", datetime.millisecond);
+                output.push(locale.decimal);
+                output.push_str(&fraction[..decimals.min(3)]);
+            }
+            Token::Digit(_) if skip_fraction => {}
+            Token::Literal(text) => {
+                skip_fraction = false;
+                output.push_str(text);
+            }
+            Token::Digit(c) => output.push(*c),
+            Token::Point => output.push('.'),
+            Token::Comma => output.push(','),
+            Token::Percent => output.push('%'),
+            Token::Exponent(_) => {}
+        }
+    }
+    output
+}
+
+/// Best approximation of `fraction` (in `0..1`) with a denominator up to `max_denominator`.
+fn approximate_fraction(fraction: f64, max_denominator: u64) -> (u64, u64) {
+    let mut best = (0, 1);
+    let mut best_error = fraction;
+    for denominator in 1..=max_denominator.max(1) {
+        let numerator = (fraction * denominator as f64).round() as u64;
+        let error = (fraction - numerator as f64 / denominator as f64).abs();
+        if error < best_error - f64::EPSILON {
+            best = (numerator, denominator);
+            best_error = error;
+        }
+    }
+    best
+}
+
+/// Fraction formats such as "# ?/?", "# ??/??" and "?/8".
+fn format_fraction(tokens: &[Token], slash: usize, value: f64) -> String {
+    let numerator_start = tokens[..slash]
+        .iter()
+        .rposition(|token| !matches!(token, Token::Digit(_)))
+        .map_or(0, |index| index + 1);
+    let has_whole = tokens[..numerator_start]
+        .iter()
+        .any(|token| matches!(token, Token::Digit(_)));
+    let denominator_tokens = &tokens[slash + 1..];
+    let denominator_digits = denominator_tokens
+        .iter()
+        .take_while(|token| matches!(token, Token::Digit(_)))
+        .count();
+    let fixed_denominator: Option<u64> = match denominator_tokens.first() {
+        Some(Token::Literal(text)) if text.chars().all(|c| c.is_ascii_digit()) => {
+            let digits: String = denominator_tokens
+                .iter()
+                .map_while(|token| match token {
+                    Token::Literal(text) if text.chars().all(|c| c.is_ascii_digit()) => {
+                        Some(text.as_str())
+                    }
+                    Token::Digit('0') => Some("0"),
+                    _ => None,
+                })
+                .collect();
+            digits.parse().ok()
+        }
+        _ => None,
+    };
+
+    let value = value.abs();
+    let (whole, fraction) = if has_whole {
+        (value.trunc(), value.fract())
+    } else {
+        (0.0, value)
+    };
+    let (mut numerator, denominator) = match fixed_denominator {
+        Some(denominator) => ((fraction * denominator as f64).round() as u64, denominator),
+        None if has_whole => {
+            approximate_fraction(fraction, 10u64.pow(denominator_digits as u32) - 1)
+        }
+        None => {
+            let (numerator, denominator) =
+                approximate_fraction(fraction.fract(), 10u64.pow(denominator_digits as u32) - 1);
+            (
+                numerator + fraction.trunc() as u64 * denominator,
+                denominator,
+            )
+        }
+    };
+    let mut whole = whole as u64;
+    if has_whole && numerator == denominator {
+        whole += 1;
+        numerator = 0;
+    }
+
+    let mut output = String::new();
+    for token in &tokens[..numerator_start] {
+        if let Token::Literal(text) = token {
+            output.push_str(text);
+        }
+    }
+    if has_whole {
+        // 整数部分写在第一个占位符处
+        let prefix_len = tokens[..numerator_start]
+            .iter()
+            .take_while(|token| !matches!(token, Token::Digit(_)))
+            .map(|token| match token {
+                Token::Literal(text) => text.len(),
+                _ => 0,
+            })
+            .sum::<usize>();
+        let whole_text = if whole == 0 && numerator != 0 {
+            String::new()
+        } else {
+            whole.to_string()
+        };
+        output.insert_str(prefix_len, &whole_text);
+        if numerator == 0 {
+            return output.trim_end().to_string();
+        }
+    }
+    output.push_str(&format!("{}/{}", numerator, denominator));
+    output.trim_start().to_string()
+}
+
+/// Apply a number format code to a numeric value, giving the text Excel displays.
+pub fn format_number(value: f64, format_code: &str, locale: &NumberLocale) -> String {
+    let (section, negative) = pick_section(format_code, value);
+    let trimmed = section.trim();
+    if trimmed.eq_ignore_ascii_case("general") || trimmed.is_empty() || trimmed == "@" {
+        return format_general(value, locale);
+    }
+    let tokens = tokenize(section);
+    let is_date = tokens
+        .iter()
+        .any(|token| matches!(token, Token::DatePart(_)));
+    let slash = tokens.iter().enumerate().position(|(index, token)| {
+        matches!(token, Token::Literal(text) if text == "/")
+            && matches!(tokens.get(index.wrapping_sub(1)), Some(Token::Digit(_)))
+    });
+    let text = if is_date {
+        format_date(&tokens, value, locale)
+    } else if let Some(slash) = slash {
+        format_fraction(&tokens, slash, value)
+    } else if tokens.iter().any(|token| matches!(token, Token::Digit(_))) {
+        format_digits(&tokens, value, locale)
+    } else {
+        // 只有文字的节（如 "-"）原样输出
+        tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Literal(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    };
+    if negative && !is_date {
+        format!("-{}", text)
+    } else {
+        text
+    }
+}
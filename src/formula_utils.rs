@@ -0,0 +1,660 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use umya_spreadsheet::Worksheet;
+
+use crate::utils::parse_range;
+
+/// Largest range a formula may refer to, so that e.g. "A1:XFD1048576" doesn't stall the plugin.
+const MAX_RANGE_CELLS: u64 = 1 << 20;
+const ERRORS: [&str; 7] = [
+    "#NULL!", "#DIV/0!", "#VALUE!", "#REF!", "#NAME?", "#NUM!", "#N/A",
+];
+
+#[derive(Clone, PartialEq)]
+pub enum FormulaValue {
+    Blank,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(&'static str),
+}
+
+impl FormulaValue {
+    /// The value as a cell value string, the way Excel caches results.
+    pub fn to_cell_value(&self) -> String {
+        match self {
+            FormulaValue::Blank => String::new(),
+            FormulaValue::Number(number) => number_text(*number),
+            FormulaValue::Text(text) => text.clone(),
+            FormulaValue::Bool(value) => if *value { "TRUE" } else { "FALSE" }.to_string(),
+            FormulaValue::Error(error) => error.to_string(),
+        }
+    }
+
+    /// Data type of the value, as in `CellData::kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FormulaValue::Blank => "empty",
+            FormulaValue::Number(_) => "number",
+            FormulaValue::Text(_) => "string",
+            FormulaValue::Bool(_) => "bool",
+            FormulaValue::Error(_) => "error",
+        }
+    }
+
+    fn number(&self) -> Result<f64, &'static str> {
+        match self {
+            FormulaValue::Blank => Ok(0.0),
+            FormulaValue::Number(number) => Ok(*number),
+            FormulaValue::Bool(value) => Ok(if *value { 1.0 } else { 0.0 }),
+            FormulaValue::Text(text) => text.trim().parse().map_err(|_| "#VALUE!"),
+            FormulaValue::Error(error) => Err(error),
+        }
+    }
+
+    fn text(&self) -> Result<String, &'static str> {
+        match self {
+            FormulaValue::Error(error) => Err(error),
+            value => Ok(value.to_cell_value()),
+        }
+    }
+
+    fn boolean(&self) -> Result<bool, &'static str> {
+        match self {
+            FormulaValue::Blank => Ok(false),
+            FormulaValue::Number(number) => Ok(*number != 0.0),
+            FormulaValue::Bool(value) => Ok(*value),
+            FormulaValue::Text(text) if text.eq_ignore_ascii_case("true") => Ok(true),
+            FormulaValue::Text(text) if text.eq_ignore_ascii_case("false") => Ok(false),
+            FormulaValue::Text(_) => Err("#VALUE!"),
+            FormulaValue::Error(error) => Err(error),
+        }
+    }
+}
+
+fn number_text(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
+
+fn error_value(text: &str) -> FormulaValue {
+    FormulaValue::Error(
+        ERRORS
+            .iter()
+            .find(|error| error.eq_ignore_ascii_case(text))
+            .copied()
+            .unwrap_or("#VALUE!"),
+    )
+}
+
+/// Values of the cells a formula refers to, by (column, row).
+pub trait CellSource {
+    fn value(&mut self, column: u32, row: u32) -> FormulaValue;
+}
+
+/// Cells of an xlsx worksheet, evaluating the formulas without a cached result on demand.
+pub struct WorksheetCells<'a> {
+    worksheet: &'a Worksheet,
+    /// Cached results read from the sheet XML, keyed by (column, row).
+    cached: &'a HashMap<(u32, u32), String>,
+    /// Results of the formulas evaluated so far, keyed by (column, row). `None` marks a formula
+    /// being evaluated, so that circular references can be told apart from long chains.
+    evaluated: HashMap<(u32, u32), Option<FormulaValue>>,
+}
+
+impl<'a> WorksheetCells<'a> {
+    pub fn new(worksheet: &'a Worksheet, cached: &'a HashMap<(u32, u32), String>) -> Self {
+        WorksheetCells {
+            worksheet,
+            cached,
+            evaluated: HashMap::new(),
+        }
+    }
+
+    /// Result of the formula of the cell at (column, row), evaluated once and reused by the cells
+    /// referring to it. Circular references evaluate to `#REF!`.
+    pub fn evaluate(&mut self, column: u32, row: u32, formula: &str) -> FormulaValue {
+        match self.evaluated.get(&(column, row)) {
+            Some(Some(result)) => return result.clone(),
+            Some(None) => return FormulaValue::Error("#REF!"),
+            None => {}
+        }
+        self.evaluated.insert((column, row), None);
+        let result = evaluate_formula(formula, self);
+        self.evaluated.insert((column, row), Some(result.clone()));
+        result
+    }
+}
+
+impl CellSource for WorksheetCells<'_> {
+    fn value(&mut self, column: u32, row: u32) -> FormulaValue {
+        let cell = match self.worksheet.get_cell((column, row)) {
+            Some(cell) => cell,
+            None => return FormulaValue::Blank,
+        };
+        let mut value = cell.get_value().to_string();
+        if value.is_empty() && !cell.get_formula().is_empty() {
+            match self.cached.get(&(column, row)) {
+                Some(cached) => value = cached.clone(),
+                None => return self.evaluate(column, row, cell.get_formula()),
+            }
+        }
+        if value.is_empty() {
+            return FormulaValue::Blank;
+        }
+        match cell.get_data_type() {
+            "n" => value
+                .parse()
+                .map_or(FormulaValue::Text(value), FormulaValue::Number),
+            "b" => FormulaValue::Bool(value == "1" || value.eq_ignore_ascii_case("true")),
+            "e" => error_value(&value),
+            _ => FormulaValue::Text(value),
+        }
+    }
+}
+
+enum Token {
+    Number(f64),
+    Text(String),
+    /// A cell or range reference such as "A1", "$B$2" or "A1:C3".
+    Reference(String),
+    /// A function name or TRUE/FALSE.
+    Name(String),
+    Operator(String),
+    Open,
+    Close,
+    Separator,
+}
+
+fn is_reference(text: &str) -> bool {
+    let text = text.trim_start_matches('$');
+    let letters = text.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+    let rest = text[letters..].trim_start_matches('$');
+    (1..=3).contains(&letters) && !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit())
+}
+
+fn tokenize(formula: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // 科学计数法，如 1E+3
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut end = i + 1;
+                    if matches!(chars.get(end), Some('+' | '-')) {
+                        end += 1;
+                    }
+                    if chars.get(end).is_some_and(|c| c.is_ascii_digit()) {
+                        i = end;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push(Token::Number(text.parse().ok()?));
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i)? {
+                        '"' if chars.get(i + 1) == Some(&'"') => {
+                            text.push('"');
+                            i += 2;
+                        }
+                        '"' => break,
+                        c => {
+                            text.push(*c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push(Token::Text(text));
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            ',' | ';' => {
+                tokens.push(Token::Separator);
+                i += 1;
+            }
+            '<' | '>' => {
+                let operator = match chars.get(i + 1) {
+                    Some('=') => format!("{}=", c),
+                    Some('>') if c == '<' => "<>".to_string(),
+                    _ => c.to_string(),
+                };
+                i += operator.len();
+                tokens.push(Token::Operator(operator));
+            }
+            '+' | '-' | '*' | '/' | '^' | '&' | '%' | '=' => {
+                tokens.push(Token::Operator(c.to_string()));
+                i += 1;
+            }
+            _ if c.is_ascii_alphabetic() || c == '$' || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '$' | '_' | '.'))
+                {
+                    i += 1;
+                }
+                let mut text: String = chars[start..i].iter().collect();
+                // 其他工作表的引用（Sheet1!A1）不支持
+                if chars.get(i) == Some(&'!') {
+                    return None;
+                }
+                if chars.get(i) == Some(&'(') {
+                    tokens.push(Token::Name(text.to_ascii_uppercase()));
+                } else if is_reference(&text) {
+                    if chars.get(i) == Some(&':') {
+                        let end: String = chars[i + 1..]
+                            .iter()
+                            .take_while(|c| c.is_ascii_alphanumeric() || **c == '$')
+                            .collect();
+                        if !is_reference(&end) {
+                            return None;
+                        }
+                        i += end.len() + 1;
+                        text = format!("{}:{}", text, end);
+                    }
+                    tokens.push(Token::Reference(text.to_ascii_uppercase()));
+                } else {
+                    tokens.push(Token::Name(text.to_ascii_uppercase()));
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(tokens)
+}
+
+/// An evaluated operand: a single value, or the values of a range in row-major order.
+enum Operand {
+    Value(FormulaValue),
+    Range(Vec<FormulaValue>),
+}
+
+impl Operand {
+    /// The operand as a single value; a range only counts when it holds a single cell.
+    fn scalar(self) -> FormulaValue {
+        match self {
+            Operand::Value(value) => value,
+            Operand::Range(mut values) if values.len() == 1 => values.remove(0),
+            Operand::Range(_) => FormulaValue::Error("#VALUE!"),
+        }
+    }
+}
+
+fn compare(left: &FormulaValue, right: &FormulaValue) -> Ordering {
+    use FormulaValue::*;
+    // Excel 的排序：数字 < 文本 < 逻辑值，空白按对方的类型取 0 或 ""
+    let rank = |value: &FormulaValue| match value {
+        Number(_) | Blank => 0,
+        Text(_) => 1,
+        _ => 2,
+    };
+    match (left, right) {
+        (Text(left), Blank) => left.to_lowercase().cmp(&String::new()),
+        (Blank, Text(right)) => String::new().cmp(&right.to_lowercase()),
+        (Text(left), Text(right)) => left.to_lowercase().cmp(&right.to_lowercase()),
+        (Bool(left), Bool(right)) => left.cmp(right),
+        (Number(_) | Blank, Number(_) | Blank) => {
+            let left = left.number().unwrap_or(0.0);
+            let right = right.number().unwrap_or(0.0);
+            left.partial_cmp(&right).unwrap_or(Ordering::Equal)
+        }
+        _ => rank(left).cmp(&rank(right)),
+    }
+}
+
+fn binary(operator: &str, left: FormulaValue, right: FormulaValue) -> FormulaValue {
+    for value in [&left, &right] {
+        if let FormulaValue::Error(error) = value {
+            return FormulaValue::Error(error);
+        }
+    }
+    if operator == "&" {
+        return FormulaValue::Text(left.to_cell_value() + &right.to_cell_value());
+    }
+    if matches!(operator, "=" | "<>" | "<" | "<=" | ">" | ">=") {
+        let ordering = compare(&left, &right);
+        return FormulaValue::Bool(match operator {
+            "=" => ordering == Ordering::Equal,
+            "<>" => ordering != Ordering::Equal,
+            "<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            _ => ordering != Ordering::Less,
+        });
+    }
+    let (left, right) = match (left.number(), right.number()) {
+        (Ok(left), Ok(right)) => (left, right),
+        (Err(error), _) | (_, Err(error)) => return FormulaValue::Error(error),
+    };
+    let result = match operator {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" if right == 0.0 => return FormulaValue::Error("#DIV/0!"),
+        "/" => left / right,
+        _ => left.powf(right),
+    };
+    if result.is_finite() {
+        FormulaValue::Number(result)
+    } else {
+        FormulaValue::Error("#NUM!")
+    }
+}
+
+/// The numbers of the arguments of SUM, AVERAGE and the like: values given directly are converted,
+/// while text and logical values within ranges are skipped.
+fn numbers(arguments: Vec<Operand>) -> Result<Vec<f64>, &'static str> {
+    let mut numbers = Vec::new();
+    for argument in arguments {
+        match argument {
+            Operand::Value(value) => numbers.push(value.number()?),
+            Operand::Range(values) => {
+                for value in values {
+                    match value {
+                        FormulaValue::Number(number) => numbers.push(number),
+                        FormulaValue::Error(error) => return Err(error),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+fn flatten(arguments: Vec<Operand>) -> Vec<FormulaValue> {
+    arguments
+        .into_iter()
+        .flat_map(|argument| match argument {
+            Operand::Value(value) => vec![value],
+            Operand::Range(values) => values,
+        })
+        .collect()
+}
+
+fn call(name: &str, mut arguments: Vec<Operand>) -> Result<FormulaValue, &'static str> {
+    let count = arguments.len();
+    let mut argument = |index: usize| -> FormulaValue {
+        match arguments.get_mut(index) {
+            Some(operand) => {
+                std::mem::replace(operand, Operand::Value(FormulaValue::Blank)).scalar()
+            }
+            None => FormulaValue::Blank,
+        }
+    };
+    Ok(match name {
+        "IF" => {
+            let condition = argument(0).boolean()?;
+            match (condition, count) {
+                (true, _) => argument(1),
+                (false, 3..) => argument(2),
+                (false, _) => FormulaValue::Bool(false),
+            }
+        }
+        "IFERROR" => match argument(0) {
+            FormulaValue::Error(_) => argument(1),
+            value => value,
+        },
+        "NOT" => FormulaValue::Bool(!argument(0).boolean()?),
+        "ABS" => FormulaValue::Number(argument(0).number()?.abs()),
+        "ROUND" => {
+            let number = argument(0).number()?;
+            let factor = 10f64.powi(argument(1).number()? as i32);
+            FormulaValue::Number((number * factor).round() / factor)
+        }
+        "LEN" => FormulaValue::Number(argument(0).text()?.chars().count() as f64),
+        "UPPER" => FormulaValue::Text(argument(0).text()?.to_uppercase()),
+        "LOWER" => FormulaValue::Text(argument(0).text()?.to_lowercase()),
+        "TRIM" => FormulaValue::Text(
+            argument(0)
+                .text()?
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        "SUM" => FormulaValue::Number(numbers(arguments)?.iter().sum()),
+        "PRODUCT" => FormulaValue::Number(numbers(arguments)?.iter().product()),
+        "AVERAGE" => {
+            let numbers = numbers(arguments)?;
+            if numbers.is_empty() {
+                return Err("#DIV/0!");
+            }
+            FormulaValue::Number(numbers.iter().sum::<f64>() / numbers.len() as f64)
+        }
+        "MIN" => FormulaValue::Number(
+            numbers(arguments)?
+                .into_iter()
+                .reduce(f64::min)
+                .unwrap_or(0.0),
+        ),
+        "MAX" => FormulaValue::Number(
+            numbers(arguments)?
+                .into_iter()
+                .reduce(f64::max)
+                .unwrap_or(0.0),
+        ),
+        "COUNT" => FormulaValue::Number(
+            flatten(arguments)
+                .iter()
+                .filter(|value| matches!(value, FormulaValue::Number(_)))
+                .count() as f64,
+        ),
+        "COUNTA" => FormulaValue::Number(
+            flatten(arguments)
+                .iter()
+                .filter(|value| !matches!(value, FormulaValue::Blank))
+                .count() as f64,
+        ),
+        "AND" | "OR" => {
+            let mut values = Vec::new();
+            for value in flatten(arguments) {
+                match value {
+                    FormulaValue::Blank | FormulaValue::Text(_) => {}
+                    value => values.push(value.boolean()?),
+                }
+            }
+            if values.is_empty() {
+                return Err("#VALUE!");
+            }
+            FormulaValue::Bool(if name == "AND" {
+                values.iter().all(|value| *value)
+            } else {
+                values.iter().any(|value| *value)
+            })
+        }
+        "CONCAT" | "CONCATENATE" => {
+            let mut text = String::new();
+            for value in flatten(arguments) {
+                text.push_str(&value.text()?);
+            }
+            FormulaValue::Text(text)
+        }
+        _ => return Err("#NAME?"),
+    })
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    cells: &'a mut dyn CellSource,
+}
+
+impl Parser<'_> {
+    fn peek_operator(&self, operators: &[&str]) -> Option<String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(operator)) if operators.contains(&operator.as_str()) => {
+                Some(operator.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn binary_level(
+        &mut self,
+        operators: &[&str],
+        next: fn(&mut Self) -> Option<Operand>,
+    ) -> Option<Operand> {
+        let mut left = next(self)?;
+        while let Some(operator) = self.peek_operator(operators) {
+            self.position += 1;
+            let right = next(self)?;
+            left = Operand::Value(binary(&operator, left.scalar(), right.scalar()));
+        }
+        Some(left)
+    }
+
+    fn comparison(&mut self) -> Option<Operand> {
+        self.binary_level(&["=", "<>", "<", "<=", ">", ">="], Self::concatenation)
+    }
+
+    fn concatenation(&mut self) -> Option<Operand> {
+        self.binary_level(&["&"], Self::additive)
+    }
+
+    fn additive(&mut self) -> Option<Operand> {
+        self.binary_level(&["+", "-"], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> Option<Operand> {
+        self.binary_level(&["*", "/"], Self::power)
+    }
+
+    fn power(&mut self) -> Option<Operand> {
+        self.binary_level(&["^"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Option<Operand> {
+        if let Some(operator) = self.peek_operator(&["+", "-"]) {
+            self.position += 1;
+            let value = self.unary()?.scalar();
+            return Some(Operand::Value(match operator.as_str() {
+                "-" => binary("-", FormulaValue::Number(0.0), value),
+                _ => value,
+            }));
+        }
+        let mut operand = self.primary()?;
+        while self.peek_operator(&["%"]).is_some() {
+            self.position += 1;
+            operand = Operand::Value(binary("/", operand.scalar(), FormulaValue::Number(100.0)));
+        }
+        Some(operand)
+    }
+
+    fn primary(&mut self) -> Option<Operand> {
+        let token = self.tokens.get(self.position)?;
+        self.position += 1;
+        match token {
+            Token::Number(number) => Some(Operand::Value(FormulaValue::Number(*number))),
+            Token::Text(text) => Some(Operand::Value(FormulaValue::Text(text.clone()))),
+            Token::Open => {
+                let operand = self.comparison()?;
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Some(operand)
+                    }
+                    _ => None,
+                }
+            }
+            Token::Reference(reference) => {
+                let ((start_col, start_row), (end_col, end_row)) = parse_range(reference)?;
+                if !reference.contains(':') {
+                    return Some(Operand::Value(self.cells.value(start_col, start_row)));
+                }
+                let size = (end_col - start_col + 1) as u64 * (end_row - start_row + 1) as u64;
+                if size > MAX_RANGE_CELLS {
+                    return Some(Operand::Value(FormulaValue::Error("#REF!")));
+                }
+                let mut values = Vec::new();
+                for row in start_row..=end_row {
+                    for col in start_col..=end_col {
+                        values.push(self.cells.value(col, row));
+                    }
+                }
+                Some(Operand::Range(values))
+            }
+            Token::Name(name) => {
+                let name = name.clone();
+                if !matches!(self.tokens.get(self.position), Some(Token::Open)) {
+                    return Some(Operand::Value(match name.as_str() {
+                        "TRUE" => FormulaValue::Bool(true),
+                        "FALSE" => FormulaValue::Bool(false),
+                        _ => FormulaValue::Error("#NAME?"),
+                    }));
+                }
+                self.position += 1;
+                let mut arguments = Vec::new();
+                if !matches!(self.tokens.get(self.position), Some(Token::Close)) {
+                    loop {
+                        // 省略的参数视为空白
+                        if matches!(
+                            self.tokens.get(self.position),
+                            Some(Token::Separator | Token::Close)
+                        ) {
+                            arguments.push(Operand::Value(FormulaValue::Blank));
+                        } else {
+                            arguments.push(self.comparison()?);
+                        }
+                        match self.tokens.get(self.position) {
+                            Some(Token::Separator) => self.position += 1,
+                            _ => break,
+                        }
+                    }
+                }
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => self.position += 1,
+                    _ => return None,
+                }
+                Some(Operand::Value(
+                    call(&name, arguments).unwrap_or_else(FormulaValue::Error),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Evaluate a formula ("=SUM(A1:A3)*2" or without the "=") against the cells of its sheet.
+/// Supports arithmetic, comparison and `&` operators, cell and range references within the
+/// sheet, and common functions; anything else evaluates to "#NAME?".
+pub fn evaluate_formula(formula: &str, cells: &mut dyn CellSource) -> FormulaValue {
+    let formula = formula.trim().trim_start_matches('=');
+    let tokens = match tokenize(formula) {
+        Some(tokens) if !tokens.is_empty() => tokens,
+        _ => return FormulaValue::Error("#NAME?"),
+    };
+    let token_count = tokens.len();
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        cells,
+    };
+    match parser.comparison() {
+        Some(operand) if parser.position == token_count => operand.scalar(),
+        _ => FormulaValue::Error("#NAME?"),
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::archive_utils::Archive;
+use crate::data_structures::{CellData, CellImage, RowData, TableData};
+use crate::xml_utils::XmlNode;
+
+/// Pictures keyed by the (column, row) of the cell holding their top left corner.
+pub type CellImages = HashMap<(u32, u32), Vec<CellImage>>;
+
+/// Drawing units (EMU) per point.
+const EMU_PER_PT: f64 = 12700.0;
+
+fn emu(node: Option<&XmlNode>, name: &str) -> f64 {
+    node.and_then(|node| node.attr(name))
+        .and_then(|value| value.parse::<f64>().ok())
+        .map_or(0.0, |value| value / EMU_PER_PT)
+}
+
+/// Cell (1-based column and row) of an `<xdr:from>` or `<xdr:to>` marker, with the offset into
+/// it in pt.
+fn marker(marker: &XmlNode) -> Option<((u32, u32), (f64, f64))> {
+    let number = |name: &str| {
+        marker
+            .child(name)?
+            .text_content()
+            .trim()
+            .parse::<u32>()
+            .ok()
+    };
+    let offset = |name: &str| {
+        marker
+            .child(name)
+            .and_then(|offset| offset.text_content().trim().parse::<f64>().ok())
+            .map_or(0.0, |offset| offset / EMU_PER_PT)
+    };
+    Some((
+        (number("col")? + 1, number("row")? + 1),
+        (offset("colOff"), offset("rowOff")),
+    ))
+}
+
+/// Format of an image part from its extension, e.g. "png" or "jpeg".
+fn image_format(path: &str) -> String {
+    let extension = path.rsplit_once('.').map_or("", |(_, extension)| extension);
+    match extension.to_ascii_lowercase().as_str() {
+        "jpg" | "jpe" => "jpeg".to_string(),
+        extension => extension.to_string(),
+    }
+}
+
+/// Pictures of the sheet's drawing, anchored to one cell or spanning two. Pictures positioned
+/// in absolute coordinates, within groups or beyond `bounds` are skipped.
+pub fn read_cell_images(
+    archive: &mut Archive,
+    sheet_index: usize,
+    bounds: (u32, u32),
+) -> Result<CellImages, String> {
+    let mut images = CellImages::new();
+    let sheet_path = match archive.sheet_path(sheet_index)? {
+        Some(sheet_path) => sheet_path,
+        None => return Ok(images),
+    };
+    let drawing_path = archive
+        .relationships(&sheet_path)?
+        .into_iter()
+        .find(|rel| rel.rel_type.ends_with("/drawing"))
+        .map(|rel| rel.target);
+    let Some(drawing_path) = drawing_path else {
+        return Ok(images);
+    };
+    let drawing = match archive.read_xml(&drawing_path)? {
+        Some(drawing) => drawing,
+        None => return Ok(images),
+    };
+    let rels = archive.relationships(&drawing_path)?;
+    let anchors = drawing
+        .children
+        .iter()
+        .filter(|anchor| anchor.name == "twoCellAnchor" || anchor.name == "oneCellAnchor");
+    for anchor in anchors {
+        let (Some(from), Some(picture)) = (anchor.child("from"), anchor.child("pic")) else {
+            continue;
+        };
+        let Some(((column, row), (x, y))) = marker(from) else {
+            continue;
+        };
+        if column > bounds.0 || row > bounds.1 {
+            continue;
+        }
+        let target = picture
+            .child("blipFill")
+            .and_then(|fill| fill.child("blip"))
+            .and_then(|blip| blip.attr("r:embed"))
+            .and_then(|rel_id| rels.iter().find(|rel| rel.id == rel_id))
+            .map(|rel| rel.target.clone());
+        let Some(target) = target else {
+            continue;
+        };
+        let Some(data) = archive.read_part_bytes(&target)? else {
+            continue;
+        };
+        let properties = picture.child("nvPicPr").and_then(|pr| pr.child("cNvPr"));
+        let text = |name: &str| {
+            properties
+                .and_then(|properties| properties.attr(name))
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty())
+        };
+        // 尺寸取自图片的变换，缺少时取单元格锚点上记录的尺寸
+        let extent = picture
+            .child("spPr")
+            .and_then(|properties| properties.child("xfrm"))
+            .and_then(|transform| transform.child("ext"))
+            .or_else(|| anchor.child("ext"));
+        // 终点恰在单元格起始处时不占用该单元格
+        let (colspan, rowspan) = match anchor.child("to").and_then(marker) {
+            Some(((end_column, end_row), (end_x, end_y))) => {
+                let span = |start: u32, end: u32, offset: f64| {
+                    let end = if offset == 0.0 && end > start {
+                        end - 1
+                    } else {
+                        end
+                    };
+                    end.saturating_sub(start) + 1
+                };
+                (span(column, end_column, end_x), span(row, end_row, end_y))
+            }
+            None => (1, 1),
+        };
+        images.entry((column, row)).or_default().push(CellImage {
+            format: image_format(&target),
+            data,
+            name: text("name"),
+            description: text("descr"),
+            x,
+            y,
+            width: emu(extent, "cx"),
+            height: emu(extent, "cy"),
+            colspan,
+            rowspan,
+        });
+    }
+    Ok(images)
+}
+
+/// Attach the pictures left over after reading the cells of rows `first_row` to `last_row`,
+/// adding empty cells where no cell holds their anchor. A picture anchored inside a merged range
+/// goes to its first cell.
+pub fn attach_cell_images(
+    table_data: &mut TableData,
+    images: CellImages,
+    first_row: u32,
+    last_row: u32,
+) {
+    let mut images: Vec<_> = images
+        .into_iter()
+        .filter(|((_, row), _)| (first_row..=last_row).contains(row))
+        .collect();
+    images.sort_by_key(|(position, _)| *position);
+    for ((column, row_num), cell_images) in images {
+        let (column, row_num) = table_data
+            .merged_cells
+            .iter()
+            .find(|merge| {
+                (merge.start.row..=merge.end.row).contains(&row_num)
+                    && (merge.start.column..=merge.end.column).contains(&column)
+            })
+            .map_or((column, row_num), |merge| {
+                (merge.start.column, merge.start.row)
+            });
+        let index = match table_data
+            .rows
+            .binary_search_by_key(&row_num, |row| row.row_number)
+        {
+            Ok(index) => index,
+            Err(index) => {
+                table_data.rows.insert(
+                    index,
+                    RowData {
+                        row_number: row_num,
+                        cells: Vec::new(),
+                    },
+                );
+                index
+            }
+        };
+        let row = &mut table_data.rows[index];
+        let index = match row.cells.binary_search_by_key(&column, |cell| cell.column) {
+            Ok(index) => index,
+            Err(index) => {
+                row.cells.insert(
+                    index,
+                    CellData {
+                        kind: Some("empty".to_string()),
+                        value: String::new(),
+                        column,
+                        ..Default::default()
+                    },
+                );
+                index
+            }
+        };
+        row.cells[index]
+            .images
+            .get_or_insert_with(Vec::new)
+            .extend(cell_images);
+    }
+}
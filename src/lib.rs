@@ -1,34 +1,88 @@
 #![cfg_attr(feature = "typst-plugin", allow(missing_docs))]
 
-use core::num::NonZeroU32;
+use core::sync::atomic::{AtomicU64, Ordering};
 use getrandom::{register_custom_getrandom, Error};
 
-// Some application-specific error code
-const MY_CUSTOM_ERROR_CODE: u32 = Error::CUSTOM_START + 42;
-pub fn always_fail(_buf: &mut [u8]) -> Result<(), Error> {
-    let code = NonZeroU32::new(MY_CUSTOM_ERROR_CODE).unwrap();
-    Err(Error::from(code))
+// typst 插件没有系统随机源，使用固定种子的 SplitMix64，保证每次编译结果一致
+const RANDOM_SEED: u64 = 0x5245_584c_6c65_6e54;
+static RANDOM_STATE: AtomicU64 = AtomicU64::new(RANDOM_SEED);
+
+/// Deterministic `getrandom` source for the typst sandbox.
+pub fn seeded_random(buf: &mut [u8]) -> Result<(), Error> {
+    for chunk in buf.chunks_mut(8) {
+        let mut z = RANDOM_STATE
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+    }
+    Ok(())
 }
 
-register_custom_getrandom!(always_fail);
+register_custom_getrandom!(seeded_random);
 
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
 use umya_spreadsheet::{reader, Cell, Spreadsheet};
 use wasm_minimal_protocol::*;
 
 wasm_minimal_protocol::initiate_protocol!();
 
+mod archive_utils;
+mod border_utils;
+mod calamine_utils;
+mod color_utils;
+mod conditional_utils;
 mod data_structures;
+mod detect_utils;
+mod external_utils;
+mod format_utils;
+mod formula_utils;
+mod image_utils;
+mod metadata_utils;
+mod ods_utils;
+mod options;
+mod pivot_utils;
+mod style_utils;
+mod table_style_utils;
+mod transform_utils;
 mod utils;
+mod workbook_utils;
 mod worksheet_utils;
+mod writer_utils;
 mod cell_utils;
+mod csv_utils;
+mod xml_utils;
 // mod tests;
 
+use archive_utils::*;
+use border_utils::*;
+use calamine_utils::*;
+use conditional_utils::*;
 use data_structures::*;
+use detect_utils::*;
+use external_utils::*;
+use format_utils::*;
+use formula_utils::*;
+use image_utils::*;
+use metadata_utils::*;
+use ods_utils::*;
+use options::*;
+use pivot_utils::*;
+use style_utils::*;
+use table_style_utils::*;
+use transform_utils::*;
 use utils::*;
+use workbook_utils::*;
 use worksheet_utils::*;
+use writer_utils::*;
 use cell_utils::*;
+use csv_utils::*;
 
+/// Convert a sheet with the default options. Kept with its original six arguments; options
+/// are passed to `to_typst_v2`.
 #[cfg_attr(feature = "typst-plugin", wasm_func)]
 pub fn to_typst(
     bytes: &[u8],
@@ -38,14 +92,45 @@ pub fn to_typst(
     parse_bg_color: &[u8],
     parse_font_style: &[u8],
 ) -> Result<Vec<u8>, String> {
-    let file = Cursor::new(bytes);
-    let book: Spreadsheet = reader::xlsx::read_reader(file, true)
-        .map_err(|e| format!("Failed to read Excel file: {}", e))?;
-    // parse string -> bytes -> usize
-    let sheet_index: usize = String::from_utf8(sheet_index.to_vec())
-        .map_err(|e| format!("Failed to parse sheet index: {}", e))?
-        .parse()
-        .map_err(|e| format!("Failed to parse sheet index: {}", e))?;
+    // 每次转换从同一种子开始，结果不受之前调用的影响
+    RANDOM_STATE.store(RANDOM_SEED, Ordering::Relaxed);
+    let hash = content_hash(&[
+        bytes,
+        sheet_index,
+        parse_alignment,
+        parse_border,
+        parse_bg_color,
+        parse_font_style,
+    ]);
+    let options = ParseOptions::default();
+    let result = (|| -> Result<Vec<u8>, PluginError> {
+        // 工作表可以用序号或名称指定，名称不随标签页重新排序而变化
+        let sheet = String::from_utf8(sheet_index.to_vec())
+            .map_err(|e| format!("Failed to parse sheet index: {}", e))?;
+        let sheet_index: usize = match sheet.parse() {
+            Ok(sheet_index) => sheet_index,
+            Err(_) => resolve_sheet_name(bytes, &sheet)
+                .map_err(|e| PluginError::new("sheet_not_found", e))?,
+        };
+        let flags = parse_style_flags(
+            parse_alignment,
+            parse_border,
+            parse_bg_color,
+            parse_font_style,
+        )
+        .map_err(|e| PluginError::new("invalid_options", e))?;
+        convert(bytes, sheet_index, flags, &options, hash)
+    })();
+    error_response(result, &options)
+}
+
+/// The boolean positional arguments of `to_typst`.
+fn parse_style_flags(
+    parse_alignment: &[u8],
+    parse_border: &[u8],
+    parse_bg_color: &[u8],
+    parse_font_style: &[u8],
+) -> Result<StyleFlags, String> {
     let parse_alignment: bool = String::from_utf8(parse_alignment.to_vec())
         .map_err(|e| format!("Failed to parse parse_alignment: {}", e))?
         .parse()
@@ -62,30 +147,420 @@ pub fn to_typst(
         .map_err(|e| format!("Failed to parse parse_font_style: {}", e))?
         .parse()
         .map_err(|e| format!("Failed to parse parse_font_style: {}", e))?;
+    Ok(StyleFlags {
+        alignment: parse_alignment,
+        border: parse_border,
+        bg_color: parse_bg_color,
+        font: parse_font_style,
+    })
+}
+
+/// Options of `to_typst_v2` as a CBOR encoded dictionary: `sheet_index`, the style flags
+/// (`parse_alignment`, `parse_border`, `parse_bg_color`, `parse_font_style`) and every
+/// `ParseOptions` field, all of them optional.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn to_typst_v2(bytes: &[u8], options: &[u8]) -> Result<Vec<u8>, String> {
+    RANDOM_STATE.store(RANDOM_SEED, Ordering::Relaxed);
+    let (options, previous_hash) = split_previous_hash_cbor(options)?;
+    let hash = content_hash(&[bytes, &options]);
+    let mut options = parse_cbor_options(&options)?;
+    if previous_hash.as_deref() == Some(hash.as_str()) {
+        return unchanged_response(hash, &options.parse);
+    }
+    let flags = options.flags();
+    let result = (|| -> Result<Vec<u8>, PluginError> {
+        let sheet_index = if let Some(name) = &options.defined_name {
+            let (sheet_index, range) = resolve_defined_name(bytes, name)
+                .map_err(|e| PluginError::new("name_not_found", e))?;
+            options.parse.range = Some(range);
+            sheet_index
+        } else {
+            match &options.sheet_name {
+                Some(name) => resolve_sheet_name(bytes, name)
+                    .map_err(|e| PluginError::new("sheet_not_found", e))?,
+                None => options.sheet_index,
+            }
+        };
+        convert(bytes, sheet_index, flags, &options.parse, hash)
+    })();
+    error_response(result, &options.parse)
+}
+
+/// Convert a table (list object) of an xlsx workbook, found by name on any sheet, styled by its
+/// table style. Takes the same CBOR options as `to_typst_v2`, except that the sheet and range
+/// come from the table; its header and totals rows become the header and footer unless
+/// `header_rows` or `footer_rows` are given.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn table_to_typst(bytes: &[u8], table_name: &[u8], options: &[u8]) -> Result<Vec<u8>, String> {
+    RANDOM_STATE.store(RANDOM_SEED, Ordering::Relaxed);
+    let (options, previous_hash) = split_previous_hash_cbor(options)?;
+    let hash = content_hash(&[bytes, table_name, &options]);
+    let mut options = parse_cbor_options(&options)?;
+    if previous_hash.as_deref() == Some(hash.as_str()) {
+        return unchanged_response(hash, &options.parse);
+    }
+    let flags = options.flags();
+    let result = (|| -> Result<Vec<u8>, PluginError> {
+        let table_name = String::from_utf8(table_name.to_vec()).map_err(|e| {
+            PluginError::new("invalid_options", format!("Failed to parse table name: {}", e))
+        })?;
+        let mut workbook = Workbook::open(bytes, &options.parse)?;
+        let (sheet_index, list_object) = match &mut workbook {
+            Workbook::Xlsx { archive, .. } => find_list_object(archive, &table_name),
+            // 表格（列表对象）只存在于 xlsx 文件中
+            _ => Err(format!("Failed to get table {:?}", table_name)),
+        }
+        .map_err(|e| PluginError::new("table_not_found", e))?;
+        let parse = &mut options.parse;
+        parse.range = Some(list_object.range.clone());
+        parse.header_rows = parse
+            .header_rows
+            .or(Some(list_object.header_rows).filter(|rows| *rows > 0));
+        parse.footer_rows = parse
+            .footer_rows
+            .or(Some(list_object.totals_rows).filter(|rows| *rows > 0));
+        let mut sheet =
+            workbook.read_list_object(sheet_index, &list_object, flags, &options.parse)?;
+        if let Some(metadata) = sheet.table_data.metadata.as_mut() {
+            metadata.table_name = Some(list_object.name.clone());
+        }
+        sheet.table_data.list_object = Some(list_object);
+        Ok(finish_table(sheet, &options.parse, flags.border, hash)?)
+    })();
+    error_response(result, &options.parse)
+}
+
+/// Convert every sheet of a workbook in one pass, returning the tables keyed by sheet name.
+/// Takes the same CBOR options as `to_typst_v2`; `sheet_index` and `sheet_name` are ignored.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn all_sheets_to_typst(bytes: &[u8], options: &[u8]) -> Result<Vec<u8>, String> {
+    RANDOM_STATE.store(RANDOM_SEED, Ordering::Relaxed);
+    let (options, previous_hash) = split_previous_hash_cbor(options)?;
+    let hash = content_hash(&[bytes, &options]);
+    let options = parse_cbor_options(&options)?;
+    if previous_hash.as_deref() == Some(hash.as_str()) {
+        return unchanged_response(hash, &options.parse);
+    }
+    let flags = options.flags();
+    let result = (|| -> Result<Vec<u8>, PluginError> {
+        // 工作簿只解析一次，各工作表共用样式、主题和外部链接
+        let mut workbook = Workbook::open(bytes, &options.parse)?;
+        let mut tables = Vec::new();
+        for sheet in workbook.sheet_names()? {
+            let sheet_table = workbook
+                .read_sheet(sheet.index, flags, &options.parse)
+                .map_err(|e| e.in_sheet(Some(sheet.name.clone())))?;
+            let mut table_data = process_table(sheet_table, &options.parse, flags.border)
+                .map_err(|e| PluginError::from(e).in_sheet(Some(sheet.name.clone())))?;
+            table_data.hash = Some(hash.clone());
+            tables.push((sheet.name, table_data));
+        }
+        Ok(serialize_output(&SheetTables(tables), &options.parse)?)
+    })();
+    error_response(result, &options.parse)
+}
+
+/// Convert a sheet of an xlsx, xlsb, xls or ods workbook into table data.
+fn convert(
+    bytes: &[u8],
+    sheet_index: usize,
+    flags: StyleFlags,
+    options: &ParseOptions,
+    hash: String,
+) -> Result<Vec<u8>, PluginError> {
+    let mut workbook = Workbook::open(bytes, options)?;
+    // 序号超出范围时与名称找不到时报告相同的错误
+    if let Ok(sheets) = workbook.sheet_names() {
+        if !sheets.iter().any(|sheet| sheet.index == sheet_index) {
+            return Err(PluginError::new(
+                "sheet_not_found",
+                format!(
+                    "Failed to get worksheet {}, the workbook has {} sheets",
+                    sheet_index,
+                    sheets.len()
+                ),
+            ));
+        }
+    }
+    // 出错时附上工作表名称，便于定位
+    let sheet_name = |workbook: &mut Workbook| {
+        workbook
+            .sheet_names()
+            .ok()
+            .and_then(|sheets| sheets.into_iter().find(|sheet| sheet.index == sheet_index))
+            .map(|sheet| sheet.name)
+    };
+    let sheet = match workbook.read_sheet(sheet_index, flags, options) {
+        Ok(sheet) => sheet,
+        Err(e) => return Err(e.in_sheet(sheet_name(&mut workbook))),
+    };
+    Ok(finish_table(sheet, options, flags.border, hash)?)
+}
+
+/// Return a failed conversion as data when `structured_errors` is set, or else fail the call
+/// with the readable message.
+fn error_response(
+    result: Result<Vec<u8>, PluginError>,
+    options: &ParseOptions,
+) -> Result<Vec<u8>, String> {
+    match result {
+        Ok(output) => Ok(output),
+        Err(error) if options.structured_errors => {
+            serialize_output(&ErrorResponse { error }, options)
+        }
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// A sheet as read from the workbook, before the optional passes of `process_table`.
+struct SheetTable {
+    table_data: TableData,
+    /// Rows holding SUM formulas, a hint for `detect_totals`.
+    sum_rows: HashSet<u32>,
+    /// Width of columns without an explicit width, used when padding rows.
+    default_width: f64,
+}
+
+/// A workbook opened once, so that several of its sheets can be read without parsing it again.
+enum Workbook<'a> {
+    Xls(&'a [u8]),
+    Xlsb(&'a [u8], Archive<'a>),
+    Ods(Archive<'a>),
+    Xlsx {
+        book: Box<Spreadsheet>,
+        archive: Archive<'a>,
+        parts: Box<XlsxParts>,
+    },
+}
+
+/// Workbook-wide xlsx parts read from the raw XML, shared by all sheets.
+struct XlsxParts {
+    raw_styles: RawStyles,
+    raw_theme: RawTheme,
+    external_links: ExternalLinks,
+}
+
+impl<'a> Workbook<'a> {
+    fn open(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, PluginError> {
+        // 旧版 .xls 不是 zip 压缩包，在打开压缩包之前识别
+        if is_xls(bytes) {
+            return Ok(Workbook::Xls(bytes));
+        }
+        // 先检查压缩包的大小限制，再交给 umya-spreadsheet 完整解压
+        let defaults = ArchiveLimits::default();
+        let mut archive = Archive::new(
+            bytes,
+            &ArchiveLimits {
+                max_decompressed_size: options
+                    .max_decompressed_size
+                    .unwrap_or(defaults.max_decompressed_size),
+                max_parts: options.max_parts.unwrap_or(defaults.max_parts),
+            },
+        )
+        .map_err(|e| PluginError::new("invalid_workbook", e))?;
+        if is_xlsb(&mut archive) {
+            return Ok(Workbook::Xlsb(bytes, archive));
+        }
+        if is_ods(&mut archive)? {
+            return Ok(Workbook::Ods(archive));
+        }
+        let book = reader::xlsx::read_reader(Cursor::new(bytes), true).map_err(|e| {
+            PluginError::new("invalid_workbook", format!("Failed to read Excel file: {}", e))
+        })?;
+        let mut raw_theme = RawTheme::read(&mut archive)?;
+        raw_theme.keep_alpha = options.keep_alpha;
+        let parts = XlsxParts {
+            raw_styles: RawStyles::read(&mut archive)?,
+            raw_theme,
+            external_links: ExternalLinks::read(&mut archive)?,
+        };
+        Ok(Workbook::Xlsx {
+            book: Box::new(book),
+            archive,
+            parts: Box::new(parts),
+        })
+    }
+
+    fn sheet_names(&mut self) -> Result<Vec<SheetName>, String> {
+        match self {
+            Workbook::Xls(bytes) => read_sheet_names(bytes),
+            Workbook::Xlsb(bytes, _) => read_xlsb_sheet_names(bytes),
+            Workbook::Ods(archive) | Workbook::Xlsx { archive, .. } => {
+                read_archive_sheet_names(archive)
+            }
+        }
+    }
+
+    fn read_sheet(
+        &mut self,
+        sheet_index: usize,
+        flags: StyleFlags,
+        options: &ParseOptions,
+    ) -> Result<SheetTable, PluginError> {
+        let flags = if options.values_only {
+            StyleFlags {
+                alignment: false,
+                border: false,
+                bg_color: false,
+                font: false,
+            }
+        } else {
+            flags
+        };
+        match self {
+            Workbook::Xls(bytes) => {
+                let (mut table_data, sum_rows) = read_xls_table(bytes, sheet_index, options)?;
+                warn_unsupported(&mut table_data, "xls", flags, options);
+                Ok(values_sheet(table_data, sum_rows, EXCEL_DEFAULT_COLUMN_WIDTH, options)?)
+            }
+            Workbook::Xlsb(bytes, archive) => {
+                let (mut table_data, sum_rows) =
+                    read_xlsb_table(bytes, archive, sheet_index, options)?;
+                warn_unsupported(&mut table_data, "xlsb", flags, options);
+                Ok(values_sheet(table_data, sum_rows, EXCEL_DEFAULT_COLUMN_WIDTH, options)?)
+            }
+            Workbook::Ods(archive) => {
+                let (mut table_data, sum_rows) =
+                    read_ods_table(archive, sheet_index, &flags, options.raw_values)?;
+                warn_unsupported(&mut table_data, "ods", flags, options);
+                Ok(values_sheet(table_data, sum_rows, ODS_DEFAULT_COLUMN_WIDTH, options)?)
+            }
+            Workbook::Xlsx {
+                book,
+                archive,
+                parts,
+            } => read_xlsx_sheet(book, archive, parts, sheet_index, None, flags, options),
+        }
+    }
+
+    /// Read the sheet of a table (list object), styled by its table style.
+    fn read_list_object(
+        &mut self,
+        sheet_index: usize,
+        list_object: &ListObject,
+        flags: StyleFlags,
+        options: &ParseOptions,
+    ) -> Result<SheetTable, PluginError> {
+        match self {
+            Workbook::Xlsx {
+                book,
+                archive,
+                parts,
+            } => read_xlsx_sheet(
+                book,
+                archive,
+                parts,
+                sheet_index,
+                Some(list_object),
+                flags,
+                options,
+            ),
+            _ => self.read_sheet(sheet_index, flags, options),
+        }
+    }
+}
+
+/// Read a sheet of an xlsx workbook through umya-spreadsheet and the raw XML parts.
+fn read_xlsx_sheet(
+    book: &Spreadsheet,
+    archive: &mut Archive,
+    parts: &XlsxParts,
+    sheet_index: usize,
+    list_object: Option<&ListObject>,
+    flags: StyleFlags,
+    options: &ParseOptions,
+) -> Result<SheetTable, PluginError> {
+    let XlsxParts {
+        raw_styles,
+        raw_theme,
+        external_links,
+    } = parts;
+    let StyleFlags {
+        alignment: parse_alignment,
+        border: parse_border,
+        bg_color: parse_bg_color,
+        font: parse_font_style,
+    } = flags;
     let worksheet = book
         .get_sheet(&sheet_index)
         .ok_or_else(|| "Failed to get worksheet".to_string())?;
 
     let (max_col, max_row) = get_table_dimensions(worksheet)?;
 
-    let mut table_data = TableData {
-        dimensions: TableDimensions {
-            columns: Vec::new(),
-            rows: Vec::new(),
-            max_columns: Some(max_col),
-            max_rows: Some(max_row),
-        },
-        rows: Vec::new(),
-        merged_cells: Vec::new(),
-    };
+    let mut table_data = TableData::new(max_col, max_row);
+    table_data.dimensions.column_overrides =
+        get_column_overrides(&options.column_widths, max_col)?;
+    table_data.dimensions.uniform_row_height = get_uniform_row_height(&options.row_height)?;
 
     // 处理表格尺寸
 
     let properties = worksheet.get_sheet_format_properties();
-    table_data.dimensions.columns =
-        get_column_widths(worksheet, max_col, *properties.get_default_column_width());
+    let sheet_xml = archive.read_sheet_xml(sheet_index)?;
+    table_data.show_gridlines = Some(get_show_gridlines(&sheet_xml));
+    let cell_meta = read_cell_meta(
+        archive,
+        sheet_index,
+        &sheet_xml,
+        (max_col, max_row),
+        &options.cell_meta,
+    )?;
+    let mut cell_comments = if options.parse_comments {
+        read_cell_comments(archive, sheet_index, (max_col, max_row))?
+    } else {
+        HashMap::new()
+    };
+    let mut cell_phonetics = if options.parse_phonetic {
+        read_cell_phonetics(archive, &sheet_xml, (max_col, max_row))?
+    } else {
+        HashMap::new()
+    };
+    let mut cell_validations = if options.parse_validation {
+        read_cell_validations(book, &sheet_xml, worksheet.get_name(), (max_col, max_row))
+    } else {
+        HashMap::new()
+    };
+    let mut cell_images = if options.parse_images {
+        read_cell_images(archive, sheet_index, (max_col, max_row))?
+    } else {
+        HashMap::new()
+    };
+    table_data.metadata = Some(read_table_metadata(
+        archive,
+        sheet_index,
+        worksheet.get_name(),
+    )?);
+    let style_indices = get_cell_style_indices(&sheet_xml);
+    let cached_values = get_cached_formula_values(&sheet_xml);
+    let mut missing_results = Vec::new();
+    let mut error_cells = Vec::new();
+    let mut unsupported_formulas = Vec::new();
+    let mut formula_cells = WorksheetCells::new(worksheet, &cached_values);
+    let locale = NumberLocale::from_tag(options.locale.as_deref());
+    let column_spans = get_column_spans(&sheet_xml);
+    let row_styles = get_row_style_indices(&sheet_xml);
+    table_data.dimensions.columns = get_column_widths(
+        &column_spans,
+        max_col,
+        *properties.get_default_column_width(),
+    );
     table_data.dimensions.rows =
         get_row_heights(worksheet, max_row, *properties.get_default_row_height());
+    table_data.hidden_rows = get_hidden_rows(&sheet_xml, max_row);
+    table_data.hidden_columns = get_hidden_columns(&column_spans, max_col);
+    table_data.filtered_rows = get_filtered_rows(&sheet_xml, &table_data.hidden_rows);
+    table_data.row_outline_levels = get_row_outline_levels(&sheet_xml, max_row);
+    let (frozen_cols, frozen_rows) = get_frozen_panes(&sheet_xml);
+    table_data.frozen_cols = Some(frozen_cols).filter(|cols| *cols > 0);
+    table_data.frozen_rows = Some(frozen_rows).filter(|rows| *rows > 0);
+    table_data.page_setup = get_page_setup(&sheet_xml);
+    table_data.header_footer = get_header_footer(&sheet_xml);
+    table_data.page_breaks = get_page_breaks(&sheet_xml, max_row);
+    table_data.print_area = read_sheet_defined_name(archive, sheet_index, "_xlnm.Print_Area")?
+        .and_then(|refers_to| print_area_range(&refers_to));
+    table_data.print_title_rows =
+        read_sheet_defined_name(archive, sheet_index, "_xlnm.Print_Titles")?
+            .and_then(|refers_to| print_title_rows(&refers_to));
+    table_data.column_outline_levels = get_column_outline_levels(&column_spans, max_col);
 
     // 处理合并单元格
     for merge_cell in worksheet.get_merge_cells() {
@@ -107,7 +582,19 @@ pub fn to_typst(
         });
     }
     // 处理行数据
-    for row_num in 1..=max_row {
+    // 只输出一部分行时，窗口之外的行不必逐个单元格读取样式
+    // 删除行的选项会重新编号，条件格式的色阶、数据条等要按整个区域计算，这两种情况读取全部行，
+    // 窗口只在 process_table 中应用
+    let crop_range = options.crop_range(table_data.print_area.as_deref());
+    let row_offset = match crop_range.and_then(parse_range) {
+        Some(((_, start_row), _)) => start_row - 1,
+        None => 0,
+    };
+    let (first_row, last_row) = options
+        .row_window(max_row.saturating_sub(row_offset))
+        .filter(|_| !options.removes_rows() && !options.conditional_formats)
+        .map_or((1, max_row), |(first, last)| (first + row_offset, last + row_offset));
+    for row_num in first_row..=last_row {
         let row = worksheet.get_collection_by_row(&row_num);
         let mut row_data = RowData {
             row_number: row_num,
@@ -134,37 +621,126 @@ pub fn to_typst(
 
             if !is_merged {
                 if let Some(Some(cell)) = col_cell_map.get((col_num - 1) as usize) {
+                    // 没有单独样式的单元格继承整行或整列的样式
+                    let (xf_index, inherited_style) =
+                        match style_indices.get(&(col_num, row_num)) {
+                            Some(xf) => (Some(*xf), None),
+                            None => {
+                                let xf = get_inherited_style_index(
+                                    &row_styles,
+                                    &column_spans,
+                                    col_num,
+                                    row_num,
+                                );
+                                let style = xf.and_then(|_| {
+                                    get_inherited_style(worksheet, &row_styles, col_num, row_num)
+                                });
+                                (xf, style)
+                            }
+                        };
+                    let style = inherited_style.unwrap_or_else(|| cell.get_style());
+                    // 图案填充以两种颜色的混合色作为填充色，渐变填充以平均色作为填充色
+                    let (pattern_fill, gradient_fill) = if parse_bg_color {
+                        let raw_fill = xf_index.and_then(|xf| raw_styles.fill(xf));
+                        (
+                            get_cell_pattern_fill(style, book, raw_theme),
+                            get_cell_gradient_fill(raw_fill, raw_theme),
+                        )
+                    } else {
+                        (None, None)
+                    };
                     let cell_style = if parse_alignment || parse_font_style {
                         Some(CellStyle {
                             alignment: if parse_alignment {
-                                get_cell_alignment(cell)
+                                get_cell_alignment(
+                                    style,
+                                    xf_index.and_then(|xf| raw_styles.cell_xf(xf)),
+                                )
                             } else {
                                 None
                             },
                             border: if parse_border {
-                                get_cell_border(cell)
+                                get_cell_border(style, book, raw_theme)
                             } else {
                                 None
                             },
-                            color: if parse_bg_color {
-                                get_cell_bg_color(cell, &book)
-                            } else {
-                                None
+                            color: match (&gradient_fill, &pattern_fill) {
+                                (Some((_, average)), _) => Some(average.clone()),
+                                (None, Some((_, blend))) => Some(blend.clone()),
+                                _ if parse_bg_color => get_cell_bg_color(style, book, raw_theme),
+                                _ => None,
                             },
                             font: if parse_font_style {
-                                get_cell_font_style(cell, &book)
+                                let raw_font = xf_index.and_then(|xf| raw_styles.font(xf));
+                                get_cell_font_style(style, book, raw_font, raw_theme)
                             } else {
                                 None
                             },
+                            pattern: pattern_fill.map(|(pattern, _)| pattern),
+                            gradient: gradient_fill.map(|(gradient, _)| gradient),
                         })
                     } else {
                         None
                     };
 
+                    if cell.get_raw_value().is_error() {
+                        error_cells.push(cell.get_coordinate().to_string());
+                    }
+                    // 引用其他工作簿的单元格没有缓存值时，取外部链接中的缓存值
+                    let mut value = cell_value(cell, options)?;
+                    let mut evaluated = None;
+                    if value.is_empty() && !cell.get_formula().is_empty() {
+                        if let Some(external) = external_links.value(cell.get_formula()) {
+                            value = external;
+                        } else if let Some(cached) = cached_values.get(&(col_num, row_num)) {
+                            value = cached.clone();
+                        } else if options.evaluate_formulas {
+                            let result = formula_cells.evaluate(col_num, row_num, cell.get_formula());
+                            if result == FormulaValue::Error("#NAME?") {
+                                unsupported_formulas.push(cell.get_coordinate().to_string());
+                            }
+                            value = result.to_cell_value();
+                            evaluated = Some(result);
+                        }
+                    }
+                    // 没有缓存结果的公式单元格输出为空，并记入警告
+                    let missing_result =
+                        evaluated.is_none() && value.is_empty() && !cell.get_formula().is_empty();
+                    if missing_result {
+                        missing_results.push(cell.get_coordinate().to_string());
+                    }
+                    // 计算出的结果按结果的类型确定数据类型
+                    let kind = match &evaluated {
+                        Some(FormulaValue::Number(_)) => number_kind(style),
+                        Some(result) => result.kind(),
+                        None => xlsx_cell_kind(cell, style, &value),
+                    };
+                    let typed = TypedValue::from_kind(kind, &value);
+                    if !options.raw_values {
+                        value = display_value(style, kind, value, &locale);
+                    }
+                    let formula = Some(cell.get_formula())
+                        .filter(|formula| !formula.is_empty())
+                        .map(|formula| format!("={}", formula));
                     row_data.cells.push(CellData {
-                        value: cell_value(cell)?,
+                        typed,
+                        formula,
+                        missing_result: Some(true).filter(|_| missing_result),
+                        runs: if parse_font_style {
+                            get_rich_text_runs(cell, book, raw_theme)
+                        } else {
+                            None
+                        },
+                        kind: Some(kind.to_string()),
+                        value,
                         column: col_num,
                         style: cell_style,
+                        meta: cell_meta.get(&(col_num, row_num)).cloned(),
+                        comment: cell_comments.remove(&(col_num, row_num)),
+                        phonetic: cell_phonetics.remove(&(col_num, row_num)),
+                        validation: cell_validations.remove(&(col_num, row_num)),
+                        images: cell_images.remove(&(col_num, row_num)),
+                        ..Default::default()
                     });
                 }
             }
@@ -174,10 +750,394 @@ pub fn to_typst(
             table_data.rows.push(row_data);
         }
     }
+    table_data.warn_cells(
+        "missing_result",
+        "Formula results not cached, recalculate and save the workbook",
+        missing_results,
+    );
+    table_data.warn_cells("error_cell", "Error cells emitted as text", error_cells);
+    if options.parse_pivots {
+        let pivot_cells = read_pivot_cells(archive, sheet_index, &locale)?;
+        fill_pivot_cells(&mut table_data, pivot_cells, first_row, last_row);
+    }
+    // 锚点处没有单元格的图片放进补上的空单元格
+    attach_cell_images(&mut table_data, cell_images, first_row, last_row);
+    table_data.warn_cells(
+        "unsupported_formula",
+        "Formulas using unsupported functions or references evaluated to #NAME?",
+        unsupported_formulas,
+    );
+
+    // 表格样式在条件格式之下，先合并
+    if let Some(list_object) = list_object.filter(|_| !options.values_only) {
+        apply_table_style(
+            &mut table_data,
+            list_object,
+            raw_styles,
+            raw_theme,
+            parse_bg_color,
+            parse_font_style,
+        );
+    }
+    if options.conditional_formats && !options.values_only {
+        let rules = get_conditional_rules(&sheet_xml);
+        apply_conditional_formats(
+            &mut table_data,
+            &rules,
+            raw_styles,
+            raw_theme,
+            parse_bg_color,
+            parse_font_style,
+        );
+    }
+    Ok(SheetTable {
+        table_data,
+        sum_rows: get_sum_rows(worksheet),
+        default_width: *properties.get_default_column_width(),
+    })
+}
+
+/// Convert delimited text (CSV, TSV) into the same table data as `to_typst`.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn csv_to_typst(bytes: &[u8], options: &[u8]) -> Result<Vec<u8>, String> {
+    let (options, previous_hash) = split_previous_hash(options)?;
+    let hash = content_hash(&[bytes, options.as_bytes()]);
+    let mut options = parse_options(options.as_bytes())?;
+    if previous_hash.as_deref() == Some(hash.as_str()) {
+        return unchanged_response(hash, &options);
+    }
+    if options.header && options.header_rows.is_none() {
+        options.header_rows = Some(1);
+    }
+    let result = (|| -> Result<Vec<u8>, PluginError> {
+        let table_data = read_csv_table(
+            bytes,
+            options.delimiter.as_deref().unwrap_or(","),
+            options.quote.as_deref().unwrap_or("\""),
+        )?;
+        let sheet =
+            values_sheet(table_data, HashSet::new(), EXCEL_DEFAULT_COLUMN_WIDTH, &options)?;
+        Ok(finish_table(sheet, &options, false, hash)?)
+    })();
+    error_response(result, &options)
+}
+
+/// List the worksheets of a workbook as TOML, each with its index and name.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn sheet_names(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let sheets = read_sheet_names(bytes)?;
+    let toml_string = toml::to_string(&SheetList { sheets })
+        .map_err(|e| format!("Failed to serialize to TOML: {}", e))?;
+    Ok(Vec::from(toml_string.as_bytes()))
+}
+
+/// Workbook-level metadata (sheets with used range, visibility and tab color, defined names),
+/// encoded in the `output_format` of the TOML `options`.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn workbook_info(bytes: &[u8], options: &[u8]) -> Result<Vec<u8>, String> {
+    let options = parse_options(options)?;
+    let result = read_workbook_info(bytes)
+        .map_err(|e| PluginError::new("invalid_workbook", e))
+        .and_then(|info| Ok(serialize_output(&info, &options)?));
+    error_response(result, &options)
+}
+
+/// Color scheme of the workbook theme (dark, light, accent and hyperlink colors), encoded in the
+/// `output_format` of the TOML `options`.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn theme_colors(bytes: &[u8], options: &[u8]) -> Result<Vec<u8>, String> {
+    let options = parse_options(options)?;
+    let result = read_theme_colors(bytes)
+        .map_err(|e| PluginError::new("invalid_workbook", e))
+        .and_then(|colors| Ok(serialize_output(&colors, &options)?));
+    error_response(result, &options)
+}
+
+/// Convert TOML encoded `TableData` back into an xlsx file.
+#[cfg_attr(feature = "typst-plugin", wasm_func)]
+pub fn from_typst(data: &[u8]) -> Result<Vec<u8>, String> {
+    let data = String::from_utf8(data.to_vec())
+        .map_err(|e| format!("Failed to parse table data: {}", e))?;
+    let table_data: TableData =
+        toml::from_str(&data).map_err(|e| format!("Failed to parse table data: {}", e))?;
+    write_xlsx(&table_data)
+}
+
+/// Tiny response returned instead of the table when the hash matches `previous_hash`.
+fn unchanged_response(hash: String, options: &ParseOptions) -> Result<Vec<u8>, String> {
+    serialize_output(
+        &UnchangedData {
+            unchanged: true,
+            hash,
+        },
+        options,
+    )
+}
+
+/// Serialize a response in the `output_format` of the options, TOML by default.
+fn serialize_output<T: serde::Serialize>(
+    value: &T,
+    options: &ParseOptions,
+) -> Result<Vec<u8>, String> {
+    match options.output_format.as_deref().unwrap_or("toml") {
+        "toml" => {
+            let toml_string = toml::to_string(value)
+                .map_err(|e| format!("Failed to serialize to TOML: {}", e))?;
+            Ok(Vec::from(toml_string.as_bytes()))
+        }
+        "cbor" => {
+            let mut buffer = Vec::new();
+            ciborium::into_writer(value, &mut buffer)
+                .map_err(|e| format!("Failed to serialize to CBOR: {}", e))?;
+            Ok(buffer)
+        }
+        "json" => serde_json::to_vec(value)
+            .map_err(|e| format!("Failed to serialize to JSON: {}", e)),
+        format => Err(format!("Invalid output_format: {}", format)),
+    }
+}
 
-    let toml_string =
-        toml::to_string(&table_data).map_err(|e| format!("Failed to serialize to TOML: {}", e))?;
+/// Warn about the requested options the reader of a non-xlsx format ignores.
+fn warn_unsupported(
+    table_data: &mut TableData,
+    format: &str,
+    flags: StyleFlags,
+    options: &ParseOptions,
+) {
+    let mut unsupported = Vec::new();
+    // ods 读取样式，xls 与 xlsb 只读取值
+    let parses_styles = flags.alignment || flags.border || flags.bg_color || flags.font;
+    if parses_styles && format != "ods" {
+        unsupported.push("styles");
+    }
+    if !options.cell_meta.is_empty() {
+        unsupported.push("cell_meta");
+    }
+    if options.parse_comments {
+        unsupported.push("parse_comments");
+    }
+    if options.parse_phonetic {
+        unsupported.push("parse_phonetic");
+    }
+    if options.parse_pivots {
+        unsupported.push("parse_pivots");
+    }
+    if options.parse_validation {
+        unsupported.push("parse_validation");
+    }
+    if options.parse_images {
+        unsupported.push("parse_images");
+    }
+    if options.conditional_formats {
+        unsupported.push("conditional_formats");
+    }
+    if options.evaluate_formulas {
+        unsupported.push("evaluate_formulas");
+    }
+    if !unsupported.is_empty() {
+        table_data.warn(
+            "unsupported_option",
+            format!(
+                "Not supported for {} files: {}",
+                format,
+                unsupported.join(", ")
+            ),
+            Vec::new(),
+        );
+    }
+}
+
+fn values_sheet(
+    mut table_data: TableData,
+    sum_rows: HashSet<u32>,
+    default_width: f64,
+    options: &ParseOptions,
+) -> Result<SheetTable, String> {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    table_data.dimensions.column_overrides =
+        get_column_overrides(&options.column_widths, max_col)?;
+    table_data.dimensions.uniform_row_height = get_uniform_row_height(&options.row_height)?;
+    Ok(SheetTable {
+        table_data,
+        sum_rows,
+        default_width,
+    })
+}
 
-    let buffer = Vec::from(toml_string.as_bytes());
-    Ok(buffer)
-}
\ No newline at end of file
+/// Run the optional passes over a parsed table and serialize it.
+fn finish_table(
+    sheet: SheetTable,
+    options: &ParseOptions,
+    parse_border: bool,
+    hash: String,
+) -> Result<Vec<u8>, String> {
+    let mut table_data = process_table(sheet, options, parse_border)?;
+    table_data.hash = Some(hash);
+    serialize_output(&table_data, options)
+}
+
+/// Row numbers within `first..=last`, renumbered from 1 the way `crop_table` renumbers rows.
+fn crop_rows(rows: HashSet<u32>, first: u32, last: u32) -> HashSet<u32> {
+    rows.into_iter()
+        .filter(|row_num| (first..=last).contains(row_num))
+        .map(|row_num| row_num - first + 1)
+        .collect()
+}
+
+/// Row numbers renumbered by `skip_hidden` or `skip_filtered`, removed rows dropped.
+fn renumber_rows(rows: HashSet<u32>, numbers: &HashMap<u32, u32>) -> HashSet<u32> {
+    rows.into_iter()
+        .filter_map(|row_num| numbers.get(&row_num).copied())
+        .collect()
+}
+
+/// Run the optional passes over a parsed table.
+fn process_table(
+    sheet: SheetTable,
+    options: &ParseOptions,
+    parse_border: bool,
+) -> Result<TableData, String> {
+    let SheetTable {
+        mut table_data,
+        mut sum_rows,
+        default_width,
+    } = sheet;
+    let crop_range = options
+        .crop_range(table_data.print_area.as_deref())
+        .map(|range| range.to_string());
+    if let Some(range) = crop_range {
+        let (start, end) = parse_range(&range).ok_or_else(|| format!("Invalid range: {}", range))?;
+        crop_table(&mut table_data, start, end);
+        sum_rows = crop_rows(sum_rows, start.1, end.1);
+    }
+    if options.respect_filter {
+        let rows = skip_filtered(&mut table_data);
+        sum_rows = renumber_rows(sum_rows, &rows);
+    }
+    match options.hidden.as_deref().unwrap_or("include") {
+        "include" => {
+            table_data.hidden_rows.clear();
+            table_data.hidden_columns.clear();
+        }
+        "mark" => {}
+        "skip" => {
+            let rows = skip_hidden(&mut table_data);
+            sum_rows = renumber_rows(sum_rows, &rows);
+        }
+        hidden => return Err(format!("Invalid hidden: {}", hidden)),
+    }
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    if let Some((first, last)) = options.row_window(max_row) {
+        let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+        crop_table(&mut table_data, (1, first), (max_col, last));
+        sum_rows = crop_rows(sum_rows, first, last);
+    }
+    if !options.typed_values {
+        clear_typed_values(&mut table_data);
+    }
+    if !options.parse_formula {
+        clear_formulas(&mut table_data);
+    }
+    if !options.font_map.is_empty() {
+        apply_font_map(&mut table_data, &options.font_map);
+    }
+    if let Some(column_count) = options.normalize_columns {
+        normalize_columns(&mut table_data, column_count, default_width, parse_border);
+    }
+    if options.detect_orientation || options.auto_transpose {
+        detect_orientation(&mut table_data);
+    }
+    if options.transpose {
+        transpose_table(&mut table_data);
+        if let Some(orientation) = table_data.orientation.as_mut() {
+            orientation.transposed = true;
+        }
+    } else if options.auto_transpose {
+        if let Some(orientation) = table_data.orientation.as_mut() {
+            if orientation.header_axis == "column" {
+                orientation.transposed = true;
+                transpose_table(&mut table_data);
+            }
+        }
+    }
+    // 转置后 SUM 公式所在的行变成了列，不再作为合计行的依据
+    if table_data
+        .orientation
+        .as_ref()
+        .is_some_and(|orientation| orientation.transposed)
+        || options.transpose
+    {
+        sum_rows.clear();
+    }
+    if options.detect_headings {
+        detect_headings(&mut table_data);
+    }
+    if options.detect_header_groups {
+        detect_header_groups(&mut table_data);
+    }
+    if options.detect_totals {
+        detect_totals(&mut table_data, &sum_rows);
+    }
+    if options.detect_banding {
+        detect_banding(&mut table_data);
+    }
+    if options.gridlines && parse_border {
+        add_gridlines(&mut table_data);
+    }
+    if options.resolve_border_conflicts {
+        resolve_border_conflicts(&mut table_data);
+    }
+    if options.detect_frame {
+        detect_frame(&mut table_data);
+    }
+    if options.collapse_borders {
+        collapse_borders(&mut table_data);
+    }
+    if options.stroke_expressions {
+        add_stroke_expressions(&mut table_data);
+    }
+
+    if options.style_classes {
+        assign_style_classes(&mut table_data)?;
+    }
+    if options.blank_runs {
+        encode_blank_runs(&mut table_data);
+    }
+    // 打印标题从第一行开始时作为表头，在每页重复
+    let header_rows = options
+        .header_rows
+        .or(table_data
+            .print_title_rows
+            .filter(|titles| titles.first == 1)
+            .map(|titles| titles.last))
+        .or(table_data.frozen_rows.filter(|_| options.frozen_header));
+    if let Some(header_rows) = header_rows {
+        split_header_rows(&mut table_data, header_rows);
+    }
+    if let Some(footer_rows) = options.footer_rows {
+        split_footer_rows(&mut table_data, footer_rows);
+    }
+    if options.keyed_rows {
+        table_data.records = keyed_records(&table_data);
+    }
+    if options.split_page_breaks {
+        split_page_breaks(&mut table_data);
+    }
+    if let Some(chunk_size) = options.chunk_rows {
+        chunk_rows(&mut table_data, chunk_size);
+    }
+    if let Some(max_width) = &options.max_width {
+        let max_width =
+            length_to_pt(max_width).ok_or_else(|| format!("Invalid max_width: {}", max_width))?;
+        let key_columns = options
+            .key_columns
+            .or(table_data.frozen_cols.filter(|_| options.frozen_header))
+            .unwrap_or(1);
+        split_columns(&mut table_data, max_width, key_columns);
+    }
+    if options.values_only {
+        encode_value_grid(&mut table_data, false);
+    }
+
+    Ok(table_data)
+}
@@ -0,0 +1,472 @@
+use std::collections::{BTreeMap, HashMap};
+
+use umya_spreadsheet::Spreadsheet;
+
+use crate::archive_utils::Archive;
+use crate::cell_utils::normalize_line_breaks;
+use crate::data_structures::{Comment, DataValidation, TableMetadata};
+use crate::utils::{parse_cell_reference, parse_range};
+use crate::workbook_utils::split_reference;
+use crate::xml_utils::XmlNode;
+
+/// Auxiliary data of the cells keyed by (column, row).
+pub type CellMeta = HashMap<(u32, u32), BTreeMap<String, String>>;
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+
+/// Alt text of the first table (list object) on the sheet, stored in the `x14:table` extension.
+fn table_alt_text(table: &XmlNode) -> (Option<String>, Option<String>) {
+    let ext = table
+        .child("extLst")
+        .into_iter()
+        .flat_map(|ext_list| ext_list.children_named("ext"))
+        .find_map(|ext| ext.child("table"));
+    (
+        non_empty(ext.and_then(|ext| ext.attr("altText"))),
+        non_empty(ext.and_then(|ext| ext.attr("altTextSummary"))),
+    )
+}
+
+/// Title and description for tagging the table, from the sheet's table alt text,
+/// falling back to the document properties.
+pub fn read_table_metadata(
+    archive: &mut Archive,
+    sheet_index: usize,
+    sheet_name: &str,
+) -> Result<TableMetadata, String> {
+    let mut table = None;
+    if let Some(sheet_path) = archive.sheet_path(sheet_index)? {
+        let target = archive
+            .relationships(&sheet_path)?
+            .into_iter()
+            .find(|rel| rel.rel_type.ends_with("/table"))
+            .map(|rel| rel.target);
+        if let Some(target) = target {
+            table = archive.read_xml(&target)?;
+        }
+    }
+    let core = archive.read_xml("docProps/core.xml")?;
+
+    let (alt_title, alt_description) = table.as_ref().map(table_alt_text).unwrap_or_default();
+    let table_name = table
+        .as_ref()
+        .and_then(|table| non_empty(table.attr("displayName").or(table.attr("name"))));
+    let core_text = |name: &str| {
+        core.as_ref()
+            .and_then(|core| core.child(name))
+            .and_then(|node| non_empty(Some(&node.text_content())))
+    };
+    Ok(TableMetadata {
+        title: alt_title.or_else(|| core_text("title")),
+        description: alt_description.or_else(|| core_text("description")),
+        sheet_name: non_empty(Some(sheet_name)),
+        table_name,
+    })
+}
+
+/// Cells (column, row) of the ranges in `sqref` within `bounds` (max column, max row).
+fn sqref_cells(
+    sqref: &str,
+    (max_col, max_row): (u32, u32),
+) -> impl Iterator<Item = (u32, u32)> + '_ {
+    // 验证规则常覆盖整列，只记录表格范围内的单元格
+    sqref.split_whitespace().filter_map(parse_range).flat_map(
+        move |((start_col, start_row), (end_col, end_row))| {
+            (start_row..=end_row.min(max_row)).flat_map(move |row_num| {
+                (start_col..=end_col.min(max_col)).map(move |col_num| (col_num, row_num))
+            })
+        },
+    )
+}
+
+fn insert_meta(
+    meta: &mut CellMeta,
+    bounds: (u32, u32),
+    sqref: &str,
+    key: &str,
+    value: String,
+) {
+    for position in sqref_cells(sqref, bounds) {
+        meta.entry(position)
+            .or_default()
+            .insert(key.to_string(), value.clone());
+    }
+}
+
+/// Per-cell auxiliary data keyed by (column, row), from the selected `sources`.
+/// Only cells within `bounds` (max column, max row) are recorded.
+pub fn read_cell_meta(
+    archive: &mut Archive,
+    sheet_index: usize,
+    sheet_xml: &XmlNode,
+    bounds: (u32, u32),
+    sources: &[String],
+) -> Result<CellMeta, String> {
+    let mut meta = CellMeta::new();
+    if sources.is_empty() {
+        return Ok(meta);
+    }
+    let rels = match archive.sheet_path(sheet_index)? {
+        Some(sheet_path) => archive.relationships(&sheet_path)?,
+        None => Vec::new(),
+    };
+    for source in sources {
+        match source.as_str() {
+            "comment" => {
+                let target = rels
+                    .iter()
+                    .find(|rel| rel.rel_type.ends_with("/comments"))
+                    .map(|rel| rel.target.clone());
+                let comments = match target {
+                    Some(target) => archive.read_xml(&target)?,
+                    None => None,
+                };
+                let comments = comments
+                    .iter()
+                    .filter_map(|comments| comments.child("commentList"))
+                    .flat_map(|list| list.children_named("comment"));
+                for comment in comments {
+                    let text = comment.child("text").map(|text| text.text_content());
+                    if let (Some(reference), Some(text)) = (comment.attr("ref"), text) {
+                        insert_meta(&mut meta, bounds, reference, "comment", text);
+                    }
+                }
+            }
+            "hyperlink" => {
+                let links = sheet_xml
+                    .children_named("hyperlinks")
+                    .flat_map(|links| links.children_named("hyperlink"));
+                for link in links {
+                    // 外部链接通过关系给出地址，内部链接使用 location
+                    let target = link
+                        .attr("r:id")
+                        .and_then(|rel_id| rels.iter().find(|rel| rel.id == rel_id))
+                        .map(|rel| rel.target.clone())
+                        .or_else(|| link.attr("location").map(|location| format!("#{}", location)));
+                    if let (Some(reference), Some(target)) = (link.attr("ref"), target) {
+                        insert_meta(&mut meta, bounds, reference, "hyperlink", target);
+                    }
+                }
+            }
+            "validation" => {
+                let validations = sheet_xml
+                    .children_named("dataValidations")
+                    .flat_map(|validations| validations.children_named("dataValidation"));
+                for validation in validations {
+                    let message = non_empty(validation.attr("prompt"))
+                        .or_else(|| non_empty(validation.attr("error")));
+                    if let (Some(sqref), Some(message)) = (validation.attr("sqref"), message) {
+                        insert_meta(&mut meta, bounds, sqref, "validation", message);
+                    }
+                }
+            }
+            _ => return Err(format!("Invalid cell_meta source: {}", source)),
+        }
+    }
+    Ok(meta)
+}
+
+/// Items of a list validation: the literal list (`"Yes,No"`), or the non-empty cells of the range
+/// or defined name it refers to, `None` for sources like `INDIRECT(...)`.
+fn list_values(formula: &str, book: &Spreadsheet, sheet_name: &str) -> Option<Vec<String>> {
+    let formula = formula.trim().trim_start_matches('=');
+    if let Some(list) = formula
+        .strip_prefix('"')
+        .and_then(|list| list.strip_suffix('"'))
+    {
+        return Some(
+            list.split(',')
+                .map(|item| item.trim().to_string())
+                .collect(),
+        );
+    }
+    // 工作表级的名称优先于工作簿级的名称
+    let worksheet = book.get_sheet_by_name(sheet_name)?;
+    let address = worksheet
+        .get_defined_names()
+        .iter()
+        .chain(book.get_defined_names())
+        .find(|name| name.get_name().eq_ignore_ascii_case(formula))
+        .map(|name| name.get_address());
+    let reference = address.as_deref().unwrap_or(formula);
+    let (sheet, range) = split_reference(reference)
+        .unwrap_or_else(|| (sheet_name.to_string(), reference.replace('$', "")));
+    let ((start_col, start_row), (end_col, end_row)) = parse_range(&range)?;
+    let source = book.get_sheet_by_name(&sheet)?;
+    let mut values = Vec::new();
+    for row_num in start_row..=end_row {
+        for col_num in start_col..=end_col {
+            let value = source
+                .get_cell((col_num, row_num))
+                .map(|cell| normalize_line_breaks(&cell.get_value()))
+                .unwrap_or_default();
+            if !value.is_empty() {
+                values.push(value);
+            }
+        }
+    }
+    Some(values)
+}
+
+/// A `<dataValidation>` of the sheet or of its `x14` extension, which keeps its formulas and
+/// ranges in `<xm:f>` and `<xm:sqref>` children.
+fn data_validation(
+    validation: &XmlNode,
+    book: &Spreadsheet,
+    sheet_name: &str,
+) -> Option<(String, DataValidation)> {
+    let formula = |name: &str| {
+        let formula = validation.child(name)?;
+        let text = formula.child("f").unwrap_or(formula).text_content();
+        non_empty(Some(&text))
+    };
+    let sqref = validation
+        .attr("sqref")
+        .map(|sqref| sqref.to_string())
+        .or_else(|| validation.child("sqref").map(|sqref| sqref.text_content()))?;
+    let kind = validation.attr("type").unwrap_or("none");
+    let kind = if kind == "none" { "any" } else { kind };
+    let operator = match kind {
+        "whole" | "decimal" | "date" | "time" | "textLength" => {
+            Some(validation.attr("operator").unwrap_or("between").to_string())
+        }
+        _ => None,
+    };
+    let formula1 = formula("formula1");
+    // 只有介于与未介于两种比较使用第二个边界
+    let formula2 = formula("formula2")
+        .filter(|_| matches!(operator.as_deref(), Some("between") | Some("notBetween")));
+    let values = match (kind, &formula1) {
+        ("list", Some(formula1)) => list_values(formula1, book, sheet_name),
+        _ => None,
+    };
+    let validation_rule = DataValidation {
+        kind: kind.to_string(),
+        operator,
+        formula1,
+        formula2,
+        values,
+        allow_blank: matches!(validation.attr("allowBlank"), Some("1") | Some("true")),
+        prompt_title: non_empty(validation.attr("promptTitle")),
+        prompt: non_empty(validation.attr("prompt")),
+        error_title: non_empty(validation.attr("errorTitle")),
+        error: non_empty(validation.attr("error")),
+    };
+    Some((sqref, validation_rule))
+}
+
+/// Data validation rules keyed by (column, row), including the rules Excel writes to the `x14`
+/// extension for lists on other sheets. Only cells within `bounds` are recorded.
+pub fn read_cell_validations(
+    book: &Spreadsheet,
+    sheet_xml: &XmlNode,
+    sheet_name: &str,
+    bounds: (u32, u32),
+) -> HashMap<(u32, u32), DataValidation> {
+    let extension = sheet_xml
+        .children_named("extLst")
+        .flat_map(|ext_list| ext_list.children_named("ext"));
+    let validations = sheet_xml
+        .children_named("dataValidations")
+        .chain(extension.flat_map(|ext| ext.children_named("dataValidations")))
+        .flat_map(|validations| validations.children_named("dataValidation"));
+    let mut cell_validations = HashMap::new();
+    for (sqref, validation) in
+        validations.filter_map(|validation| data_validation(validation, book, sheet_name))
+    {
+        for position in sqref_cells(&sqref, bounds) {
+            cell_validations.insert(position, validation.clone());
+        }
+    }
+    cell_validations
+}
+
+/// Comments keyed by (column, row): threaded comments (Excel 365) with their replies, and legacy
+/// notes for the cells without a thread. Only cells within `bounds` are recorded.
+pub fn read_cell_comments(
+    archive: &mut Archive,
+    sheet_index: usize,
+    bounds: (u32, u32),
+) -> Result<HashMap<(u32, u32), Comment>, String> {
+    let mut comments = HashMap::new();
+    let rels = match archive.sheet_path(sheet_index)? {
+        Some(sheet_path) => archive.relationships(&sheet_path)?,
+        None => return Ok(comments),
+    };
+    let part = |suffix: &str| {
+        rels.iter()
+            .find(|rel| rel.rel_type.ends_with(suffix))
+            .map(|rel| rel.target.clone())
+    };
+    let legacy = match part("/comments") {
+        Some(target) => archive.read_xml(&target)?,
+        None => None,
+    };
+    let threaded = match part("/threadedComment") {
+        Some(target) => archive.read_xml(&target)?,
+        None => None,
+    };
+    let in_bounds = |reference: &str| {
+        let (col_num, row_num) = parse_cell_reference(reference);
+        Some((col_num, row_num)).filter(|_| col_num <= bounds.0 && row_num <= bounds.1)
+    };
+
+    if let Some(legacy) = legacy {
+        let authors: Vec<String> = legacy
+            .child("authors")
+            .into_iter()
+            .flat_map(|authors| authors.children_named("author"))
+            .map(|author| author.text_content())
+            .collect();
+        let notes = legacy
+            .children_named("commentList")
+            .flat_map(|list| list.children_named("comment"));
+        for note in notes {
+            let position = note.attr("ref").and_then(in_bounds);
+            if let (Some(position), Some(text)) = (position, note.child("text")) {
+                let author = note
+                    .attr("authorId")
+                    .and_then(|id| id.parse::<usize>().ok())
+                    .and_then(|id| authors.get(id))
+                    .and_then(|author| non_empty(Some(author)));
+                comments.insert(
+                    position,
+                    Comment {
+                        text: text.text_content(),
+                        author,
+                        timestamp: None,
+                        resolved: None,
+                        replies: Vec::new(),
+                    },
+                );
+            }
+        }
+    }
+
+    if let Some(threaded) = threaded {
+        // 作者列表在工作簿级的 persons 部件中
+        let persons_part = archive
+            .relationships("xl/workbook.xml")?
+            .into_iter()
+            .find(|rel| rel.rel_type.ends_with("/person"))
+            .map(|rel| rel.target);
+        let persons = match persons_part {
+            Some(target) => archive.read_xml(&target)?,
+            None => None,
+        };
+        let person_name = |id: Option<&str>| {
+            persons.as_ref().and_then(|persons| {
+                persons
+                    .children_named("person")
+                    .find(|person| person.attr("id") == id)
+                    .and_then(|person| non_empty(person.attr("displayName")))
+            })
+        };
+        let mut threads: HashMap<String, (u32, u32)> = HashMap::new();
+        for entry in threaded.children_named("threadedComment") {
+            let position = match entry.attr("ref").and_then(in_bounds) {
+                Some(position) => position,
+                None => continue,
+            };
+            let comment = Comment {
+                text: entry
+                    .child("text")
+                    .map(|text| text.text_content())
+                    .unwrap_or_default(),
+                author: person_name(entry.attr("personId")),
+                timestamp: non_empty(entry.attr("dT")),
+                resolved: None,
+                replies: Vec::new(),
+            };
+            // 回复通过 parentId 指向线程的第一条评论，并且排在它之后
+            let thread = entry
+                .attr("parentId")
+                .and_then(|parent| threads.get(parent))
+                .and_then(|position| comments.get_mut(position));
+            match thread {
+                Some(thread) => thread.replies.push(comment),
+                None => {
+                    let resolved = matches!(entry.attr("done"), Some("1") | Some("true"));
+                    if let Some(id) = entry.attr("id") {
+                        threads.insert(id.to_string(), position);
+                    }
+                    // 线程取代旧版批注中为兼容而保存的副本
+                    comments.insert(
+                        position,
+                        Comment {
+                            resolved: Some(resolved),
+                            ..comment
+                        },
+                    );
+                }
+            }
+        }
+    }
+    Ok(comments)
+}
+
+/// Phonetic text of a string item (`<si>` or `<is>`), its `<rPh>` runs joined in order.
+fn phonetic_text(item: &XmlNode) -> Option<String> {
+    let text: String = item
+        .children_named("rPh")
+        .filter_map(|run| run.child("t"))
+        .map(|t| t.text_content())
+        .collect();
+    Some(text).filter(|text| !text.is_empty())
+}
+
+/// Phonetic guides (furigana) of the string cells, keyed by (column, row). Only cells within
+/// `bounds` are recorded.
+pub fn read_cell_phonetics(
+    archive: &mut Archive,
+    sheet_xml: &XmlNode,
+    bounds: (u32, u32),
+) -> Result<HashMap<(u32, u32), String>, String> {
+    let shared_part = archive
+        .relationships("xl/workbook.xml")?
+        .into_iter()
+        .find(|rel| rel.rel_type.ends_with("/sharedStrings"))
+        .map(|rel| rel.target);
+    let shared_strings = match shared_part {
+        Some(target) => archive.read_xml(&target)?,
+        None => None,
+    };
+    let shared: Vec<Option<String>> = shared_strings
+        .iter()
+        .flat_map(|strings| strings.children_named("si"))
+        .map(phonetic_text)
+        .collect();
+
+    let mut phonetics = HashMap::new();
+    let cells = sheet_xml
+        .children_named("sheetData")
+        .flat_map(|data| data.children_named("row"))
+        .flat_map(|row| row.children_named("c"));
+    for cell in cells {
+        let Some(reference) = cell.attr("r") else {
+            continue;
+        };
+        let (col_num, row_num) = parse_cell_reference(reference);
+        if col_num > bounds.0 || row_num > bounds.1 {
+            continue;
+        }
+        let phonetic = match cell.attr("t") {
+            Some("s") => cell
+                .child("v")
+                .and_then(|v| v.text.trim().parse::<usize>().ok())
+                .and_then(|index| shared.get(index).cloned().flatten()),
+            // 内联字符串直接携带读音
+            Some("inlineStr") => cell.child("is").and_then(phonetic_text),
+            _ => None,
+        };
+        if let Some(phonetic) = phonetic {
+            phonetics.insert((col_num, row_num), phonetic);
+        }
+    }
+    Ok(phonetics)
+}
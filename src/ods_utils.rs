@@ -0,0 +1,628 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::archive_utils::Archive;
+use crate::cell_utils::{dashed_stroke, solid_stroke};
+use crate::data_structures::{
+    Alignment, Border, BorderStrokes, CellData, CellStyle, FontStyle, MergedCell, Position,
+    RowData, RowRange, Stroke, TableData, TableMetadata, TypedValue,
+};
+use crate::options::StyleFlags;
+use crate::workbook_utils::print_area_range;
+use crate::utils::{length_to_pt, number_to_column};
+use crate::xml_utils::XmlNode;
+
+const ODS_MIMETYPE: &str = "application/vnd.oasis.opendocument.spreadsheet";
+/// Default font size of LibreOffice Calc in pt.
+const DEFAULT_FONT_SIZE: f64 = 10.0;
+/// Left margin of one indent level in pt.
+const INDENT_WIDTH: f64 = 10.0;
+/// Default column width of LibreOffice Calc (2.258cm) in characters.
+pub const ODS_DEFAULT_COLUMN_WIDTH: f64 = 8.89;
+
+/// Whether the archive is an OpenDocument spreadsheet rather than an xlsx workbook.
+pub fn is_ods(archive: &mut Archive) -> Result<bool, String> {
+    Ok(archive
+        .read_part("mimetype")?
+        .is_some_and(|mimetype| mimetype.trim() == ODS_MIMETYPE))
+}
+
+/// Automatic styles of `content.xml` by name.
+struct OdsStyles<'a> {
+    styles: HashMap<&'a str, &'a XmlNode>,
+}
+
+impl<'a> OdsStyles<'a> {
+    fn new(content: &'a XmlNode) -> Self {
+        let styles = content
+            .children_named("automatic-styles")
+            .flat_map(|styles| styles.children_named("style"))
+            .filter_map(|style| Some((style.attr("style:name")?, style)))
+            .collect();
+        OdsStyles { styles }
+    }
+
+    fn property(&self, name: Option<&str>, properties: &str, key: &str) -> Option<&'a str> {
+        self.styles.get(name?)?.child(properties)?.attr(key)
+    }
+}
+
+/// Convert an ODF length ("2.258cm", "0.178in") to pt.
+fn odf_length(value: &str) -> Option<f64> {
+    length_to_pt(value.trim())
+}
+
+fn odf_color(value: &str) -> Option<String> {
+    match value.trim() {
+        "transparent" => Some(String::new()),
+        color => color
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .map(|hex| hex.to_uppercase()),
+    }
+}
+
+/// Rotation angle ("90", "45deg") in degrees counterclockwise, from -180 to 180.
+fn odf_angle(value: &str) -> Option<i32> {
+    let angle = value.trim().trim_end_matches("deg").parse::<f64>().ok()?;
+    let angle = angle.round().rem_euclid(360.0) as i32;
+    Some(if angle > 180 { angle - 360 } else { angle })
+}
+
+/// Superscript or subscript from a `style:text-position` value like "super 58%" or "-33% 58%".
+fn odf_text_position(value: &str) -> Option<String> {
+    let position = value.split_whitespace().next()?;
+    let raised = match position {
+        "super" => true,
+        "sub" => false,
+        offset => {
+            let offset = offset.strip_suffix('%')?.parse::<f64>().ok()?;
+            if offset == 0.0 {
+                return None;
+            }
+            offset > 0.0
+        }
+    };
+    Some(if raised { "superscript" } else { "subscript" }.to_string())
+}
+
+/// Border side from a `fo:border` value like "0.06pt solid #000000".
+fn odf_border(value: Option<&str>) -> (bool, Option<Stroke>) {
+    let value = match value.map(str::trim) {
+        Some(value) if value != "none" && !value.is_empty() => value,
+        _ => return (false, None),
+    };
+    let width = value.split_whitespace().find_map(length_to_pt).unwrap_or(0.75);
+    let color = value
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix('#'))
+        .filter(|hex| hex.len() == 6)
+        .map(|hex| hex.to_uppercase());
+    let stroke = if value.contains("double") {
+        Some(Stroke {
+            style: "double".to_string(),
+            thickness: 0.5,
+            gap: Some(1.0),
+            dash: None,
+            color: None,
+        })
+    } else if value.contains("dashed") {
+        dashed_stroke("dashed", 0.75, &[3.0, 1.5])
+    } else if value.contains("dotted") {
+        dashed_stroke("dotted", 0.75, &[0.75, 0.75])
+    } else if width < 0.5 {
+        solid_stroke("hair", 0.25)
+    } else if width < 1.25 {
+        solid_stroke("thin", 0.75)
+    } else if width < 2.0 {
+        solid_stroke("medium", 1.5)
+    } else {
+        solid_stroke("thick", 2.25)
+    };
+    (true, stroke.map(|stroke| Stroke { color, ..stroke }))
+}
+
+fn cell_style(styles: &OdsStyles, name: Option<&str>, flags: &StyleFlags) -> Option<CellStyle> {
+    if !flags.alignment && !flags.font {
+        return None;
+    }
+    let cell_prop = |key: &str| styles.property(name, "table-cell-properties", key);
+    let text_prop = |key: &str| styles.property(name, "text-properties", key);
+    let alignment = if flags.alignment {
+        let horizontal = match styles.property(name, "paragraph-properties", "fo:text-align") {
+            Some("start") | Some("left") => "left",
+            Some("center") => "center",
+            Some("end") | Some("right") => "right",
+            _ => "default",
+        };
+        let vertical = match cell_prop("style:vertical-align") {
+            Some("top") => "top",
+            Some("middle") => "center",
+            Some("bottom") => "bottom",
+            _ => "default",
+        };
+        Some(Alignment {
+            horizontal: horizontal.to_string(),
+            vertical: vertical.to_string(),
+            wrap: cell_prop("fo:wrap-option") == Some("wrap"),
+            rotation: cell_prop("style:rotation-angle")
+                .and_then(odf_angle)
+                .unwrap_or(0),
+            stacked: cell_prop("style:direction") == Some("ttb"),
+            east_asian_vertical: matches!(
+                styles.property(name, "paragraph-properties", "style:writing-mode"),
+                Some("tb-rl") | Some("tb")
+            ),
+            // ODF 以左边距表示缩进
+            indent: styles
+                .property(name, "paragraph-properties", "fo:margin-left")
+                .and_then(odf_length)
+                .map_or(0, |margin| (margin / INDENT_WIDTH).round().max(0.0) as u32),
+        })
+    } else {
+        None
+    };
+    let border = if flags.border {
+        // 单独设置的边优先于 fo:border
+        let side = |key: &str| odf_border(cell_prop(key).or_else(|| cell_prop("fo:border")));
+        let (left, left_stroke) = side("fo:border-left");
+        let (right, right_stroke) = side("fo:border-right");
+        let (top, top_stroke) = side("fo:border-top");
+        let (bottom, bottom_stroke) = side("fo:border-bottom");
+        let (diagonal_up, up_stroke) = odf_border(cell_prop("style:diagonal-bl-tr"));
+        let (diagonal_down, down_stroke) = odf_border(cell_prop("style:diagonal-tl-br"));
+        let strokes = BorderStrokes {
+            left: left_stroke,
+            right: right_stroke,
+            top: top_stroke,
+            bottom: bottom_stroke,
+            diagonal: up_stroke.or(down_stroke),
+        };
+        Some(Border {
+            left,
+            right,
+            top,
+            bottom,
+            diagonal_up,
+            diagonal_down,
+            stroke: None,
+            strokes: (strokes != BorderStrokes::default()).then_some(strokes),
+        })
+    } else {
+        None
+    };
+    let color = if flags.bg_color {
+        cell_prop("fo:background-color").and_then(odf_color)
+    } else {
+        None
+    };
+    let font = if flags.font {
+        let weight = text_prop("fo:font-weight").unwrap_or("normal");
+        Some(FontStyle {
+            name: text_prop("style:font-name").map(|name| name.to_string()),
+            east_asian_name: text_prop("style:font-name-asian").map(|name| name.to_string()),
+            complex_script_name: text_prop("style:font-name-complex")
+                .map(|name| name.to_string()),
+            scheme: None,
+            bold: weight == "bold" || weight.parse::<u32>().is_ok_and(|weight| weight >= 600),
+            italic: matches!(text_prop("fo:font-style"), Some("italic") | Some("oblique")),
+            size: text_prop("fo:font-size")
+                .and_then(odf_length)
+                .unwrap_or(DEFAULT_FONT_SIZE),
+            color: text_prop("fo:color").and_then(odf_color).filter(|c| !c.is_empty()),
+            underline: text_prop("style:text-underline-style").is_some_and(|s| s != "none"),
+            underline_style: text_prop("style:text-underline-style")
+                .filter(|style| *style != "none")
+                .map(|_| match text_prop("style:text-underline-type") {
+                    Some("double") => "double".to_string(),
+                    _ => "single".to_string(),
+                }),
+            strike: text_prop("style:text-line-through-style").is_some_and(|s| s != "none"),
+            vertical_align: text_prop("style:text-position").and_then(odf_text_position),
+            outline: text_prop("style:text-outline") == Some("true"),
+            shadow: text_prop("fo:text-shadow").is_some_and(|s| s != "none"),
+            condense: false,
+            extend: false,
+        })
+    } else {
+        None
+    };
+    Some(CellStyle {
+        alignment,
+        border,
+        color,
+        font,
+        pattern: None,
+        gradient: None,
+    })
+}
+
+fn cell_value(cell: &XmlNode) -> String {
+    let typed = match cell.attr("office:value-type") {
+        Some("float") | Some("percentage") | Some("currency") => cell.attr("office:value"),
+        Some("boolean") => match cell.attr("office:boolean-value") {
+            Some("true") => Some("TRUE"),
+            Some(_) => Some("FALSE"),
+            None => None,
+        },
+        Some("date") => cell.attr("office:date-value"),
+        Some("time") => cell.attr("office:time-value"),
+        _ => None,
+    };
+    match typed {
+        Some(value) => value.to_string(),
+        None => display_text(cell),
+    }
+}
+
+/// The text shown in the cell, formatted by the cell's data style.
+fn display_text(cell: &XmlNode) -> String {
+    cell.children_named("p")
+        .map(paragraph_text)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text of a `text:p` element, with line breaks, tabs and runs of spaces expanded.
+fn paragraph_text(node: &XmlNode) -> String {
+    let mut text = node.text.clone();
+    for child in &node.children {
+        match child.name.as_str() {
+            "line-break" => text.push('\n'),
+            "tab" => text.push('\t'),
+            "s" => {
+                let count = child.attr("text:c").and_then(|c| c.parse().ok()).unwrap_or(1);
+                text.push_str(&" ".repeat(count));
+            }
+            _ => text.push_str(&paragraph_text(child)),
+        }
+        text.push_str(&child.tail);
+    }
+    text
+}
+
+/// An OpenFormula expression such as "of:=SUM([.A1:.A3])" in the A1 notation of Excel.
+fn ods_formula(formula: &str) -> String {
+    let formula = formula.split_once(':').map_or(formula, |(namespace, rest)| {
+        if namespace.chars().all(|c| c.is_ascii_alphabetic()) {
+            rest
+        } else {
+            formula
+        }
+    });
+    // 引用写作 [.A1] 或 [Sheet1.A1:.B2]，去掉方括号和表内引用前的点
+    let mut result = String::new();
+    let mut in_reference = false;
+    let mut previous = ' ';
+    for c in formula.chars() {
+        match c {
+            '[' if !in_reference => in_reference = true,
+            ']' if in_reference => in_reference = false,
+            '.' if in_reference && matches!(previous, '[' | ':') => {}
+            '.' if in_reference => result.push('!'),
+            _ => result.push(c),
+        }
+        previous = c;
+    }
+    result
+}
+
+fn cell_kind(cell: &XmlNode, value: &str) -> &'static str {
+    if value.is_empty() {
+        return "empty";
+    }
+    match cell.attr("office:value-type") {
+        Some("float") | Some("percentage") | Some("currency") => "number",
+        Some("boolean") => "bool",
+        Some("date") | Some("time") => "date",
+        _ => "string",
+    }
+}
+
+fn repeated(node: &XmlNode, key: &str) -> u32 {
+    node.attr(key)
+        .and_then(|count| count.parse::<u32>().ok())
+        .unwrap_or(1)
+        .max(1)
+}
+
+/// The `table-row` (or `table-column`) elements of `node` in order, with the number of
+/// enclosing groups as their outline level and whether they are header rows (or columns),
+/// which repeat on every printed page.
+fn collect_grouped<'a>(
+    node: &'a XmlNode,
+    kind: &str,
+    level: u32,
+    header: bool,
+    out: &mut Vec<(&'a XmlNode, u32, bool)>,
+) {
+    for child in &node.children {
+        let Some(name) = child.name.strip_prefix("table-") else {
+            continue;
+        };
+        if name == kind {
+            out.push((child, level, header));
+        } else if name.strip_suffix("-group") == Some(kind) {
+            collect_grouped(child, kind, level + 1, header, out);
+        } else if name == format!("header-{}s", kind) {
+            collect_grouped(child, kind, level, true, out);
+        } else if name.strip_suffix('s') == Some(kind) {
+            collect_grouped(child, kind, level, header, out);
+        }
+    }
+}
+
+/// First descendant `element` of `node` whose `config:name` is `name`, for settings.xml.
+fn find_config<'a>(node: &'a XmlNode, element: &str, name: &str) -> Option<&'a XmlNode> {
+    node.children.iter().find_map(|child| {
+        if child.name == element && child.attr("config:name") == Some(name) {
+            Some(child)
+        } else {
+            find_config(child, element, name)
+        }
+    })
+}
+
+/// Read a sheet of `content.xml` into `TableData`, along with the rows holding SUM formulas.
+pub fn read_ods_table(
+    archive: &mut Archive,
+    sheet_index: usize,
+    flags: &StyleFlags,
+    raw_values: bool,
+) -> Result<(TableData, HashSet<u32>), String> {
+    let content = archive
+        .read_xml("content.xml")?
+        .ok_or_else(|| "Failed to read content.xml".to_string())?;
+    let styles = OdsStyles::new(&content);
+    let sheet = content
+        .child("body")
+        .and_then(|body| body.child("spreadsheet"))
+        .and_then(|spreadsheet| spreadsheet.children_named("table").nth(sheet_index))
+        .ok_or_else(|| "Failed to get worksheet".to_string())?;
+
+    // 表头行组中的行与普通行一样按顺序排列
+    let mut row_nodes = Vec::new();
+    collect_grouped(sheet, "row", 0, false, &mut row_nodes);
+    let mut column_nodes = Vec::new();
+    collect_grouped(sheet, "column", 0, false, &mut column_nodes);
+
+    let mut rows = Vec::new();
+    let mut row_heights: HashMap<u32, f64> = HashMap::new();
+    let mut hidden_rows = Vec::new();
+    let mut filtered_rows = Vec::new();
+    let mut merged_cells = Vec::new();
+    let mut sum_rows = HashSet::new();
+    let mut missing_results = Vec::new();
+    let mut max_col = 0;
+    let mut row_num = 0;
+    let mut row_levels = BTreeMap::new();
+    let mut header_rows = Vec::new();
+    let mut page_breaks = Vec::new();
+    for (row_node, level, header) in row_nodes {
+        let row_property = |key: &str| {
+            styles.property(row_node.attr("table:style-name"), "table-row-properties", key)
+        };
+        let height = row_property("style:row-height").and_then(odf_length);
+        let row_repeat = repeated(row_node, "table:number-rows-repeated");
+        // 分页符位于行之前，记为上一行之后
+        if row_num > 0 && row_property("fo:break-before") == Some("page") {
+            page_breaks.push(row_num);
+        }
+        if header && row_repeat <= 1024 {
+            header_rows.extend(row_num + 1..=row_num + row_repeat);
+        }
+        if level > 0 && row_repeat <= 1024 {
+            for offset in 1..=row_repeat {
+                row_levels.insert(row_num + offset, level);
+            }
+        }
+        // 被筛选掉的行同样不显示
+        let hidden = matches!(row_node.attr("table:visibility"), Some("collapse") | Some("filter"));
+        if hidden && row_repeat <= 1024 {
+            hidden_rows.extend(row_num + 1..=row_num + row_repeat);
+            if row_node.attr("table:visibility") == Some("filter") {
+                filtered_rows.extend(row_num + 1..=row_num + row_repeat);
+            }
+        }
+        let has_content = row_node.children_named("table-cell").any(|cell| {
+            !cell_value(cell).is_empty()
+                || cell.attr("table:number-columns-spanned").is_some()
+                || cell.attr("table:formula").is_some()
+        });
+        // 末尾的空行通常重复上百万次，空行只推进行号，不逐行展开
+        if !has_content {
+            if let Some(height) = height.filter(|_| row_repeat <= 1024) {
+                for offset in 1..=row_repeat {
+                    row_heights.insert(row_num + offset, height);
+                }
+            }
+            row_num += row_repeat;
+            continue;
+        }
+        for _ in 0..row_repeat {
+            row_num += 1;
+            if let Some(height) = height {
+                row_heights.insert(row_num, height);
+            }
+            let mut cells = Vec::new();
+            let mut col_num = 0;
+            for cell in &row_node.children {
+                if cell.name != "table-cell" && cell.name != "covered-table-cell" {
+                    continue;
+                }
+                let col_repeat = repeated(cell, "table:number-columns-repeated");
+                let value = cell_value(cell);
+                let kind = cell_kind(cell, &value);
+                let typed = TypedValue::from_kind(kind, &value);
+                // 没有缓存结果的公式单元格输出为空，并记入警告
+                let missing_result = value.is_empty() && cell.attr("table:formula").is_some();
+                if missing_result {
+                    missing_results.push(format!("{}{}", number_to_column(col_num + 1), row_num));
+                }
+                // 默认输出按数据样式格式化后的文本
+                let value = match display_text(cell) {
+                    text if !raw_values && !text.is_empty() => text,
+                    _ => value,
+                };
+                if cell.name == "covered-table-cell" || (value.is_empty() && !missing_result) {
+                    let spans = cell.attr("table:number-columns-spanned").is_some()
+                        || cell.attr("table:number-rows-spanned").is_some();
+                    if !spans {
+                        col_num += col_repeat;
+                        continue;
+                    }
+                }
+                for _ in 0..col_repeat.min(1024) {
+                    col_num += 1;
+                    max_col = max_col.max(col_num);
+                    let col_span = repeated(cell, "table:number-columns-spanned");
+                    let row_span = repeated(cell, "table:number-rows-spanned");
+                    if col_span > 1 || row_span > 1 {
+                        let (end_col, end_row) = (col_num + col_span - 1, row_num + row_span - 1);
+                        max_col = max_col.max(end_col);
+                        merged_cells.push(MergedCell::new(
+                            Position {
+                                row: row_num,
+                                column: col_num,
+                            },
+                            Position {
+                                row: end_row,
+                                column: end_col,
+                            },
+                        ));
+                    }
+                    if cell
+                        .attr("table:formula")
+                        .map(|formula| formula.to_ascii_uppercase())
+                        .is_some_and(|formula| {
+                            formula.contains("SUM(") || formula.contains("SUBTOTAL(")
+                        })
+                    {
+                        sum_rows.insert(row_num);
+                    }
+                    cells.push(CellData {
+                        value: value.clone(),
+                        column: col_num,
+                        style: cell_style(&styles, cell.attr("table:style-name"), flags),
+                        kind: Some(kind.to_string()),
+                        typed: typed.clone(),
+                        formula: cell.attr("table:formula").map(ods_formula),
+                        missing_result: Some(true).filter(|_| missing_result),
+                        ..Default::default()
+                    });
+                }
+                if col_repeat > 1024 {
+                    col_num += col_repeat - 1024;
+                }
+            }
+            if !cells.is_empty() {
+                rows.push(RowData {
+                    row_number: row_num,
+                    cells,
+                });
+            }
+        }
+    }
+    let max_row = rows
+        .iter()
+        .map(|row| row.row_number)
+        .chain(merged_cells.iter().map(|mc| mc.end.row))
+        .max()
+        .unwrap_or(0);
+    let row_heights = (1..=max_row)
+        .map(|row_num| row_heights.get(&row_num).copied().unwrap_or(0.0))
+        .collect();
+
+    let mut column_widths = Vec::new();
+    let mut hidden_columns = Vec::new();
+    let mut column_levels = BTreeMap::new();
+    for (column, level, _) in column_nodes {
+        let width = styles
+            .property(
+                column.attr("table:style-name"),
+                "table-column-properties",
+                "style:column-width",
+            )
+            .and_then(odf_length)
+            // 与 xlsx 的列宽单位（字符数）一致
+            .map(|pt| pt / 7.2)
+            .unwrap_or(ODS_DEFAULT_COLUMN_WIDTH);
+        let count = repeated(column, "table:number-columns-repeated");
+        let count = count.min(max_col.saturating_sub(column_widths.len() as u32));
+        let first = column_widths.len() as u32 + 1;
+        if column.attr("table:visibility") == Some("collapse") {
+            hidden_columns.extend(first..first + count);
+        }
+        if level > 0 {
+            column_levels.extend((first..first + count).map(|col_num| (col_num.to_string(), level)));
+        }
+        column_widths.extend(std::iter::repeat_n(width, count as usize));
+    }
+    column_widths.resize(max_col as usize, ODS_DEFAULT_COLUMN_WIDTH);
+
+    let mut table_data = TableData::new(max_col, max_row);
+    table_data.dimensions.columns = column_widths;
+    table_data.dimensions.rows = row_heights;
+    table_data.rows = rows;
+    table_data.merged_cells = merged_cells;
+    hidden_rows.retain(|row_num| *row_num <= max_row);
+    filtered_rows.retain(|row_num| *row_num <= max_row);
+    table_data.hidden_rows = hidden_rows;
+    table_data.filtered_rows = filtered_rows;
+    header_rows.retain(|row_num| *row_num <= max_row);
+    page_breaks.retain(|row_num| *row_num < max_row);
+    table_data.page_breaks = page_breaks;
+    table_data.print_title_rows = header_rows
+        .first()
+        .zip(header_rows.last())
+        .map(|(first, last)| RowRange {
+            first: *first,
+            last: *last,
+        });
+    table_data.row_outline_levels = row_levels
+        .into_iter()
+        .filter(|(row_num, _)| *row_num <= max_row)
+        .map(|(row_num, level)| (row_num.to_string(), level))
+        .collect();
+    table_data.column_outline_levels = column_levels;
+    table_data.hidden_columns = hidden_columns;
+
+    // 冻结窗格保存在 settings.xml 的视图设置中，按工作表名称查找
+    let settings = archive.read_xml("settings.xml")?;
+    let view = settings.as_ref().zip(sheet.attr("table:name")).and_then(|(settings, name)| {
+        find_config(settings, "config-item-map-named", "Tables")
+            .and_then(|tables| find_config(tables, "config-item-map-entry", name))
+    });
+    let frozen = |mode: &str, position: &str| {
+        let item = |key: &str| {
+            view.and_then(|view| find_config(view, "config-item", key))
+                .and_then(|item| item.text.trim().parse::<u32>().ok())
+        };
+        // 拆分方式 2 表示冻结
+        item(position).filter(|count| item(mode) == Some(2) && *count > 0)
+    };
+    table_data.frozen_cols = frozen("HorizontalSplitMode", "HorizontalSplitPosition");
+    table_data.frozen_rows = frozen("VerticalSplitMode", "VerticalSplitPosition");
+
+    table_data.print_area = sheet.attr("table:print-ranges").and_then(print_area_range);
+
+    let meta = archive.read_xml("meta.xml")?;
+    let meta_text = |name: &str| {
+        meta.as_ref()
+            .and_then(|meta| meta.child("meta"))
+            .and_then(|meta| meta.child(name))
+            .map(|node| node.text_content().trim().to_string())
+            .filter(|text| !text.is_empty())
+    };
+    table_data.metadata = Some(TableMetadata {
+        title: meta_text("title"),
+        description: meta_text("description"),
+        sheet_name: sheet.attr("table:name").map(|name| name.to_string()),
+        table_name: None,
+    });
+    table_data.warn_cells(
+        "missing_result",
+        "Formula results not cached, recalculate and save the workbook",
+        missing_results,
+    );
+    Ok((table_data, sum_rows))
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// Which style properties to parse, the boolean positional arguments of `to_typst`.
+#[derive(Clone, Copy)]
+pub struct StyleFlags {
+    pub alignment: bool,
+    pub border: bool,
+    pub bg_color: bool,
+    pub font: bool,
+}
+
+/// Extra parse options, passed from typst as a TOML encoded dictionary.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ParseOptions {
+    /// Column width overrides, keyed by column letter ("B") or 1-based index ("2").
+    /// Values are typst lengths ("3cm", "40pt", "1fr") or "auto".
+    pub column_widths: BTreeMap<String, String>,
+    /// A single height ("auto" or a typst length) used for every row instead of the parsed ones.
+    pub row_height: Option<String>,
+    /// Emit borders shared by a whole row or column once instead of on every cell.
+    pub collapse_borders: bool,
+    /// Emit a border running around the whole table as a table-level frame.
+    pub detect_frame: bool,
+    /// Make disagreeing edges of adjacent cells consistent, the heavier border wins.
+    pub resolve_border_conflicts: bool,
+    /// Replace alternating row fills with a `banding` hint.
+    pub detect_banding: bool,
+    /// Flag leading rows styled differently from the body as heading rows.
+    pub detect_headings: bool,
+    /// Build a tree of multi-level column headers from horizontal merges in the top rows.
+    pub detect_header_groups: bool,
+    /// Flag trailing rows that look like totals (labels, SUM formulas, double rules, bold).
+    pub detect_totals: bool,
+    /// Report whether the labels run across the first row or down the first column.
+    pub detect_orientation: bool,
+    /// Transpose tables whose labels run down the first column.
+    pub auto_transpose: bool,
+    /// Always transpose the table, so rows become columns.
+    pub transpose: bool,
+    /// Rows and columns hidden in the sheet: `"include"` them like visible ones (default),
+    /// `"skip"` them or `"mark"` them in `hidden_rows` and `hidden_columns`.
+    pub hidden: Option<String>,
+    /// Only convert the rows passing the autofilter of the sheet, as shown on screen.
+    pub respect_filter: bool,
+    /// Only convert the print area of the sheet, unless `range` is given.
+    pub use_print_area: bool,
+    /// Only convert the cells of this A1 range, e.g. "B2:F40"; the region is renumbered from A1.
+    pub range: Option<String>,
+    /// First row to emit (1-based, counted within `range` when given), for windows of huge sheets.
+    pub start_row: Option<u32>,
+    /// Maximum number of rows to emit, starting at `start_row`.
+    pub max_rows: Option<u32>,
+    /// Pad or truncate every row to this many columns, so the grid is rectangular.
+    pub normalize_columns: Option<u32>,
+    /// Use the frozen rows of the sheet as header rows and its frozen columns as key columns,
+    /// unless `header_rows` or `key_columns` are given.
+    pub frozen_header: bool,
+    /// Number of leading rows moved into the `header` section.
+    pub header_rows: Option<u32>,
+    /// Number of trailing rows moved into the `footer` section.
+    pub footer_rows: Option<u32>,
+    /// Emit the data type (`kind`) and the typed value (`typed`) of every cell.
+    pub typed_values: bool,
+    /// Emit the stored value of numeric cells instead of the text produced by their number format.
+    pub raw_values: bool,
+    /// Emit the formula of every formula cell as `formula`.
+    pub parse_formula: bool,
+    /// Evaluate common formulas whose result isn't cached in the file.
+    pub evaluate_formulas: bool,
+    /// Emit error cells (e.g. `#DIV/0!`) as their error code instead of failing the conversion.
+    pub tolerate_errors: bool,
+    /// Text emitted for error cells instead of their error code, when `tolerate_errors` is set.
+    pub error_placeholder: Option<String>,
+    /// Return failures as data (`error` with `code`, `message`, `sheet` and `cell`) instead of
+    /// failing the plugin call.
+    pub structured_errors: bool,
+    /// Font families replacing those of the workbook, keyed by family name (case-insensitive) or
+    /// by theme font scheme ("major", "minor").
+    pub font_map: BTreeMap<String, String>,
+    /// Emit translucent fill, font and border colors as "RRGGBBAA" instead of dropping the alpha.
+    pub keep_alpha: bool,
+    /// Locale of the decimal and grouping separators of formatted numbers, e.g. "de-DE".
+    pub locale: Option<String>,
+    /// Draw the gridlines of sheets that display them as hairlines on the sides without a border.
+    pub gridlines: bool,
+    /// Emit each border as a ready-made Typst stroke dictionary (`stroke`) besides the side flags.
+    pub stroke_expressions: bool,
+    /// Skip all style extraction and emit the values as a plain grid (`values`) plus merges.
+    pub values_only: bool,
+    /// Also emit each body row as a record keyed by column header.
+    pub keyed_rows: bool,
+    /// Split tables wider than this absolute typst length into stacked blocks of columns.
+    pub max_width: Option<String>,
+    /// Number of leading columns repeated in every block when splitting wide tables.
+    pub key_columns: Option<u32>,
+    /// Split the body into segments at the manual page breaks of the sheet, each on its own page.
+    pub split_page_breaks: bool,
+    /// Split the body into segments of at most this many rows, each repeating the header rows.
+    pub chunk_rows: Option<u32>,
+    /// Evaluate conditional formatting rules and merge the formatting of matching rules into cell styles.
+    #[serde(alias = "parse_conditional")]
+    pub conditional_formats: bool,
+    /// Describe empty rows and cells as runs, so sparse sheets keep their geometry.
+    pub blank_runs: bool,
+    /// Sources of the per-cell `meta` map: `"comment"`, `"hyperlink"` and `"validation"`.
+    pub cell_meta: Vec<String>,
+    /// Emit the comments of xlsx cells as `comment`, threads with author, timestamp and replies.
+    pub parse_comments: bool,
+    /// Emit the phonetic guide (furigana) of Japanese xlsx text as `phonetic`.
+    pub parse_phonetic: bool,
+    /// Fill the cells of xlsx pivot tables the sheet leaves empty from their pivot caches.
+    pub parse_pivots: bool,
+    /// Emit the data validation rules of xlsx cells as `validation`, with the values of lists.
+    pub parse_validation: bool,
+    /// Attach the pictures of xlsx drawings to the cells they are anchored to as `images`.
+    pub parse_images: bool,
+    /// Maximum total decompressed size of the xlsx parts in bytes, 512 MiB by default.
+    pub max_decompressed_size: Option<u64>,
+    /// Maximum number of parts in the xlsx archive, 10000 by default.
+    pub max_parts: Option<usize>,
+    /// Field delimiter of `csv_to_typst`, "," by default. Use "\t" for TSV.
+    pub delimiter: Option<String>,
+    /// Quote character of `csv_to_typst`, "\"" by default.
+    pub quote: Option<String>,
+    /// Treat the first line of a CSV file as the header, the same as `header_rows = 1`.
+    pub header: bool,
+    /// Emit deduplicated styles as named classes instead of per-cell styles.
+    pub style_classes: bool,
+    /// Encoding of the returned table data: `"toml"` (default), `"cbor"` or `"json"`.
+    pub output_format: Option<String>,
+}
+
+impl ParseOptions {
+    /// Text of an error cell with the given error code, `None` when errors fail the conversion.
+    pub fn error_text(&self, code: &str) -> Option<String> {
+        if !self.tolerate_errors {
+            return None;
+        }
+        Some(
+            self.error_placeholder
+                .clone()
+                .unwrap_or_else(|| code.to_string()),
+        )
+    }
+
+    /// The rows selected by `start_row` and `max_rows` as an inclusive pair, for a table of `row_count` rows.
+    pub fn row_window(&self, row_count: u32) -> Option<(u32, u32)> {
+        if self.start_row.is_none() && self.max_rows.is_none() {
+            return None;
+        }
+        let first = self.start_row.unwrap_or(1).max(1);
+        let last = match self.max_rows {
+            Some(max_rows) => first.saturating_add(max_rows).saturating_sub(1).min(row_count),
+            None => row_count,
+        };
+        Some((first, last))
+    }
+
+    /// The region to convert: `range`, or else the print area of the sheet with `use_print_area`.
+    pub fn crop_range<'a>(&'a self, print_area: Option<&'a str>) -> Option<&'a str> {
+        self.range
+            .as_deref()
+            .or(print_area.filter(|_| self.use_print_area))
+    }
+
+    /// Whether a pass before the row window removes rows and renumbers the rest.
+    pub fn removes_rows(&self) -> bool {
+        self.respect_filter || self.hidden.as_deref() == Some("skip")
+    }
+}
+
+pub fn parse_options(options: &[u8]) -> Result<ParseOptions, String> {
+    let options = String::from_utf8(options.to_vec())
+        .map_err(|e| format!("Failed to parse options: {}", e))?;
+    toml::from_str(&options).map_err(|e| format!("Failed to parse options: {}", e))
+}
+
+/// Take `previous_hash` out of the options, returning the remaining options re-encoded as TOML.
+/// The hash of a conversion covers the remaining options only, so it stays stable between calls.
+pub fn split_previous_hash(options: &[u8]) -> Result<(String, Option<String>), String> {
+    let options = String::from_utf8(options.to_vec())
+        .map_err(|e| format!("Failed to parse options: {}", e))?;
+    let mut table: toml::Table =
+        toml::from_str(&options).map_err(|e| format!("Failed to parse options: {}", e))?;
+    let previous_hash = match table.remove("previous_hash") {
+        Some(toml::Value::String(hash)) => Some(hash),
+        Some(value) => return Err(format!("Invalid previous_hash: {}", value)),
+        None => None,
+    };
+    let options =
+        toml::to_string(&table).map_err(|e| format!("Failed to parse options: {}", e))?;
+    Ok((options, previous_hash))
+}
+
+/// Options of `to_typst_v2`: the positional arguments of `to_typst` and the extra parse options
+/// in a single dictionary, each with a default.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ConvertOptions {
+    pub sheet_index: usize,
+    /// Name of the worksheet to convert, taking precedence over `sheet_index`.
+    pub sheet_name: Option<String>,
+    /// Defined name (named range) to convert, selecting both the sheet and the range.
+    pub defined_name: Option<String>,
+    pub parse_alignment: bool,
+    pub parse_border: bool,
+    pub parse_bg_color: bool,
+    pub parse_font_style: bool,
+    #[serde(flatten)]
+    pub parse: ParseOptions,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            sheet_index: 0,
+            sheet_name: None,
+            defined_name: None,
+            parse_alignment: true,
+            parse_border: true,
+            parse_bg_color: true,
+            parse_font_style: true,
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+impl ConvertOptions {
+    pub fn flags(&self) -> StyleFlags {
+        StyleFlags {
+            alignment: self.parse_alignment,
+            border: self.parse_border,
+            bg_color: self.parse_bg_color,
+            font: self.parse_font_style,
+        }
+    }
+}
+
+pub fn parse_cbor_options(options: &[u8]) -> Result<ConvertOptions, String> {
+    if options.is_empty() {
+        return Ok(ConvertOptions::default());
+    }
+    ciborium::from_reader(options).map_err(|e| format!("Failed to parse options: {}", e))
+}
+
+/// CBOR counterpart of `split_previous_hash`, returning the remaining options re-encoded as CBOR.
+pub fn split_previous_hash_cbor(options: &[u8]) -> Result<(Vec<u8>, Option<String>), String> {
+    if options.is_empty() {
+        return Ok((Vec::new(), None));
+    }
+    let value: ciborium::Value =
+        ciborium::from_reader(options).map_err(|e| format!("Failed to parse options: {}", e))?;
+    let mut entries = match value {
+        ciborium::Value::Map(entries) => entries,
+        _ => return Err("Failed to parse options: expected a dictionary".to_string()),
+    };
+    let mut previous_hash = None;
+    if let Some(index) = entries
+        .iter()
+        .position(|(key, _)| key.as_text() == Some("previous_hash"))
+    {
+        previous_hash = match entries.remove(index).1 {
+            ciborium::Value::Text(hash) => Some(hash),
+            value => return Err(format!("Invalid previous_hash: {:?}", value)),
+        };
+    }
+    let mut remaining = Vec::new();
+    ciborium::into_writer(&ciborium::Value::Map(entries), &mut remaining)
+        .map_err(|e| format!("Failed to parse options: {}", e))?;
+    Ok((remaining, previous_hash))
+}
@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+
+use crate::archive_utils::Archive;
+use crate::data_structures::{CellData, RowData, TableData, TypedValue};
+use crate::format_utils::{format_general, NumberLocale};
+use crate::utils::parse_range;
+use crate::xml_utils::XmlNode;
+
+/// Computed cells keyed by (column, row), each value with its kind, "number" or "string".
+pub type PivotCells = HashMap<(u32, u32), (String, &'static str)>;
+
+/// A value of the pivot cache, either shared by the records of a field or stored inline.
+#[derive(PartialEq)]
+enum CacheValue {
+    Number(f64),
+    Text(String),
+    Blank,
+}
+
+impl CacheValue {
+    fn read(node: &XmlNode) -> CacheValue {
+        let val = node.attr("v").unwrap_or_default();
+        match node.name.as_str() {
+            "n" => val.parse().map_or(CacheValue::Blank, CacheValue::Number),
+            "m" => CacheValue::Blank,
+            "b" => CacheValue::Text(
+                if val == "1" || val == "true" {
+                    "TRUE"
+                } else {
+                    "FALSE"
+                }
+                .to_string(),
+            ),
+            // 日期为 ISO 8601 文本，没有时间部分时只显示日期
+            "d" => CacheValue::Text(val.trim_end_matches("T00:00:00").to_string()),
+            _ => CacheValue::Text(val.to_string()),
+        }
+    }
+
+    fn label(&self, locale: &NumberLocale) -> String {
+        match self {
+            CacheValue::Number(number) => format_general(*number, locale),
+            CacheValue::Text(text) => text.clone(),
+            CacheValue::Blank => "(blank)".to_string(),
+        }
+    }
+}
+
+/// The fields and records of a pivot cache, the records with shared items resolved.
+struct PivotCache {
+    names: Vec<String>,
+    shared: Vec<Vec<CacheValue>>,
+    records: Vec<Vec<CacheValue>>,
+}
+
+impl PivotCache {
+    fn read(definition: &XmlNode, records: &XmlNode) -> PivotCache {
+        let fields: Vec<&XmlNode> = definition
+            .child("cacheFields")
+            .into_iter()
+            .flat_map(|fields| fields.children_named("cacheField"))
+            .collect();
+        let shared: Vec<Vec<CacheValue>> = fields
+            .iter()
+            .map(|field| {
+                field
+                    .child("sharedItems")
+                    .map(|items| items.children.iter().map(CacheValue::read).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let records = records
+            .children_named("r")
+            .map(|record| {
+                record
+                    .children
+                    .iter()
+                    .enumerate()
+                    .map(|(field, value)| match value.name.as_str() {
+                        // x 引用字段的共享项，其余为内联的值
+                        "x" => value
+                            .attr("v")
+                            .and_then(|index| index.parse::<usize>().ok())
+                            .and_then(|index| shared.get(field)?.get(index))
+                            .map_or(CacheValue::Blank, |item| match item {
+                                CacheValue::Number(number) => CacheValue::Number(*number),
+                                CacheValue::Text(text) => CacheValue::Text(text.clone()),
+                                CacheValue::Blank => CacheValue::Blank,
+                            }),
+                        _ => CacheValue::read(value),
+                    })
+                    .collect()
+            })
+            .collect();
+        PivotCache {
+            names: fields
+                .iter()
+                .map(|field| field.attr("name").unwrap_or_default().to_string())
+                .collect(),
+            shared,
+            records,
+        }
+    }
+}
+
+/// A row or column of a pivot table layout (`<i>` of `rowItems` or `colItems`).
+struct PivotItem {
+    /// "data" for members, "grand" for the grand total, "blank" for empty lines, anything else
+    /// for subtotals.
+    kind: String,
+    /// Item index into the pivot field items, for each field down to this item.
+    path: Vec<usize>,
+    /// Depth of the first member shown on this line, the ones above repeat the previous line.
+    repeated: usize,
+    data_field: Option<usize>,
+}
+
+fn pivot_items(items: Option<&XmlNode>) -> Vec<PivotItem> {
+    let mut previous: Vec<usize> = Vec::new();
+    let mut result = Vec::new();
+    for item in items
+        .into_iter()
+        .flat_map(|items| items.children_named("i"))
+    {
+        let number = |name: &str| {
+            item.attr(name)
+                .and_then(|value| value.parse::<usize>().ok())
+        };
+        let repeated = number("r").unwrap_or(0).min(previous.len());
+        let mut path = previous[..repeated].to_vec();
+        path.extend(
+            item.children_named("x")
+                .map(|x| x.attr("v").and_then(|v| v.parse().ok()).unwrap_or(0)),
+        );
+        let kind = item.attr("t").unwrap_or("data").to_string();
+        if kind != "grand" && kind != "blank" {
+            previous = path.clone();
+        }
+        result.push(PivotItem {
+            kind,
+            path,
+            repeated,
+            data_field: number("i"),
+        });
+    }
+    // 没有 rowItems 或 colItems 时整个轴只有一项
+    if result.is_empty() {
+        result.push(PivotItem {
+            kind: "data".to_string(),
+            path: Vec::new(),
+            repeated: 0,
+            data_field: None,
+        });
+    }
+    result
+}
+
+/// A data field of the pivot table: caption, source field and summary function.
+struct DataField {
+    name: String,
+    field: usize,
+    subtotal: String,
+}
+
+fn summarize(values: &[&CacheValue], subtotal: &str) -> Option<f64> {
+    let numbers: Vec<f64> = values
+        .iter()
+        .filter_map(|value| match value {
+            CacheValue::Number(number) => Some(*number),
+            _ => None,
+        })
+        .collect();
+    let count = numbers.len() as f64;
+    let mean = numbers.iter().sum::<f64>() / count;
+    let squares = numbers
+        .iter()
+        .map(|number| (number - mean).powi(2))
+        .sum::<f64>();
+    match subtotal {
+        "count" => Some(
+            values
+                .iter()
+                .filter(|value| **value != &CacheValue::Blank)
+                .count() as f64,
+        ),
+        "countNums" => Some(count),
+        _ if numbers.is_empty() => None,
+        "average" => Some(mean),
+        "max" => numbers.iter().copied().reduce(f64::max),
+        "min" => numbers.iter().copied().reduce(f64::min),
+        "product" => Some(numbers.iter().product()),
+        "stdDev" if count > 1.0 => Some((squares / (count - 1.0)).sqrt()),
+        "stdDevp" => Some((squares / count).sqrt()),
+        "var" if count > 1.0 => Some(squares / (count - 1.0)),
+        "varp" => Some(squares / count),
+        "stdDev" | "var" => None,
+        _ => Some(numbers.iter().sum()),
+    }
+}
+
+/// Cells of a pivot table computed from its cache.
+fn pivot_cells(pivot: &XmlNode, cache: &PivotCache, locale: &NumberLocale) -> Option<PivotCells> {
+    let location = pivot.child("location")?;
+    let ((left, top), _) = parse_range(location.attr("ref")?)?;
+    let offset = |name: &str| {
+        location
+            .attr(name)
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(0)
+    };
+    let (first_header_row, first_data_row, first_data_col) = (
+        offset("firstHeaderRow"),
+        offset("firstDataRow"),
+        offset("firstDataCol"),
+    );
+    // 透视字段的项按序号引用缓存字段的共享项
+    let field_items: Vec<Vec<Option<usize>>> = pivot
+        .child("pivotFields")
+        .into_iter()
+        .flat_map(|fields| fields.children_named("pivotField"))
+        .map(|field| {
+            field
+                .child("items")
+                .into_iter()
+                .flat_map(|items| items.children_named("item"))
+                .map(|item| item.attr("x").and_then(|x| x.parse().ok()))
+                .collect()
+        })
+        .collect();
+    let axis_fields = |name: &str| -> Vec<i64> {
+        pivot
+            .child(name)
+            .into_iter()
+            .flat_map(|fields| fields.children_named("field"))
+            .filter_map(|field| field.attr("x").and_then(|x| x.parse().ok()))
+            .collect()
+    };
+    let (row_fields, col_fields) = (axis_fields("rowFields"), axis_fields("colFields"));
+    let data_fields: Vec<DataField> = pivot
+        .child("dataFields")
+        .into_iter()
+        .flat_map(|fields| fields.children_named("dataField"))
+        .filter_map(|field| {
+            let source = field.attr("fld")?.parse::<usize>().ok()?;
+            Some(DataField {
+                name: field
+                    .attr("name")
+                    .map(|name| name.to_string())
+                    .or_else(|| cache.names.get(source).cloned())?,
+                field: source,
+                subtotal: field.attr("subtotal").unwrap_or("sum").to_string(),
+            })
+        })
+        .collect();
+    let row_items = pivot_items(pivot.child("rowItems"));
+    let col_items = pivot_items(pivot.child("colItems"));
+
+    // 数据字段伪字段（-2）的项为数据字段序号，其余字段的项换算为缓存中的值
+    let member = |field: i64, item: usize| -> Option<Result<usize, (usize, &CacheValue)>> {
+        if field == -2 {
+            return Some(Ok(item));
+        }
+        let field = usize::try_from(field).ok()?;
+        let shared = (*field_items.get(field)?.get(item)?)?;
+        Some(Err((field, cache.shared.get(field)?.get(shared)?)))
+    };
+    let label = |field: i64, item: usize| match member(field, item)? {
+        Ok(data_field) => data_fields.get(data_field).map(|field| field.name.clone()),
+        Err((_, value)) => Some(value.label(locale)),
+    };
+    let item_label = |fields: &[i64], item: &PivotItem| match item.kind.as_str() {
+        "grand" => Some("Grand Total".to_string()),
+        "blank" => None,
+        kind => {
+            let depth = item.path.len().checked_sub(1)?;
+            let text = label(*fields.get(depth)?, item.path[depth])?;
+            Some(if kind == "data" {
+                text
+            } else {
+                format!("{} Total", text)
+            })
+        }
+    };
+
+    let mut cells = HashMap::new();
+    let mut put = |column: u32, row: u32, value: String, kind: &'static str| {
+        cells.entry((column, row)).or_insert((value, kind));
+    };
+    // 行标签：压缩形式只有一列，表格形式每个字段一列
+    let compact = first_data_col <= 1;
+    for (index, item) in row_items.iter().enumerate() {
+        let row = top + first_data_row + index as u32;
+        if compact || item.kind != "data" {
+            let depth = if compact {
+                0
+            } else {
+                item.path.len().saturating_sub(1) as u32
+            };
+            if let Some(text) = item_label(&row_fields, item) {
+                put(
+                    left + depth.min(first_data_col.saturating_sub(1)),
+                    row,
+                    text,
+                    "string",
+                );
+            }
+            continue;
+        }
+        for depth in item.repeated..item.path.len() {
+            let text = row_fields
+                .get(depth)
+                .and_then(|field| label(*field, item.path[depth]));
+            if let Some(text) = text {
+                put(left + depth as u32, row, text, "string");
+            }
+        }
+    }
+    // 列标签从表头行开始，每层字段一行
+    for (index, item) in col_items.iter().enumerate() {
+        let column = left + first_data_col + index as u32;
+        let header = top + first_data_row.saturating_sub(1);
+        if col_fields.is_empty() {
+            let data_field = item.data_field.unwrap_or(0);
+            if let Some(field) = data_fields.get(data_field) {
+                put(column, header, field.name.clone(), "string");
+            }
+            continue;
+        }
+        if item.kind != "data" {
+            if let Some(text) = item_label(&col_fields, item) {
+                let depth = item.path.len().saturating_sub(1) as u32;
+                put(column, top + first_header_row + depth, text, "string");
+            }
+            continue;
+        }
+        for depth in item.repeated..item.path.len() {
+            let text = col_fields
+                .get(depth)
+                .and_then(|field| label(*field, item.path[depth]));
+            if let Some(text) = text {
+                put(
+                    column,
+                    top + first_header_row + depth as u32,
+                    text,
+                    "string",
+                );
+            }
+        }
+    }
+    if first_data_row >= 1 {
+        let header = top + first_data_row - 1;
+        if compact {
+            let caption = pivot.attr("rowHeaderCaption").unwrap_or("Row Labels");
+            put(left, header, caption.to_string(), "string");
+        } else {
+            for (depth, field) in row_fields.iter().enumerate() {
+                if let Some(name) = usize::try_from(*field)
+                    .ok()
+                    .and_then(|f| cache.names.get(f))
+                {
+                    put(left + depth as u32, header, name.clone(), "string");
+                }
+            }
+        }
+    }
+    if !col_fields.is_empty() && first_header_row >= 1 {
+        let caption = pivot.attr("colHeaderCaption").unwrap_or("Column Labels");
+        put(
+            left + first_data_col,
+            top + first_header_row - 1,
+            caption.to_string(),
+            "string",
+        );
+        if let [data_field] = data_fields.as_slice() {
+            put(
+                left,
+                top + first_header_row - 1,
+                data_field.name.clone(),
+                "string",
+            );
+        }
+    }
+
+    // 数据区域：按行列两个方向的成员筛选缓存记录，再按数据字段的汇总方式计算
+    for (row_index, row_item) in row_items.iter().enumerate() {
+        for (col_index, col_item) in col_items.iter().enumerate() {
+            if row_item.kind == "blank" || col_item.kind == "blank" {
+                continue;
+            }
+            let mut data_field = row_item.data_field.or(col_item.data_field).unwrap_or(0);
+            let mut filters = Vec::new();
+            for (fields, item) in [(&row_fields, row_item), (&col_fields, col_item)] {
+                if item.kind == "grand" {
+                    continue;
+                }
+                for (depth, index) in item.path.iter().enumerate() {
+                    match fields.get(depth).and_then(|field| member(*field, *index)) {
+                        Some(Ok(index)) => data_field = index,
+                        Some(Err(filter)) => filters.push(filter),
+                        None => {}
+                    }
+                }
+            }
+            let data_field = match data_fields.get(data_field) {
+                Some(data_field) => data_field,
+                None => continue,
+            };
+            let values: Vec<&CacheValue> = cache
+                .records
+                .iter()
+                .filter(|record| {
+                    filters
+                        .iter()
+                        .all(|(field, value)| record.get(*field) == Some(*value))
+                })
+                .filter_map(|record| record.get(data_field.field))
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+            if let Some(result) = summarize(&values, &data_field.subtotal) {
+                put(
+                    left + first_data_col + col_index as u32,
+                    top + first_data_row + row_index as u32,
+                    format_general(result, locale),
+                    "number",
+                );
+            }
+        }
+    }
+    Some(cells)
+}
+
+/// Cells of the pivot tables on a sheet computed from their pivot caches. Pivot tables whose
+/// cache doesn't save its records are left out.
+pub fn read_pivot_cells(
+    archive: &mut Archive,
+    sheet_index: usize,
+    locale: &NumberLocale,
+) -> Result<PivotCells, String> {
+    let mut cells = HashMap::new();
+    let sheet_path = match archive.sheet_path(sheet_index)? {
+        Some(sheet_path) => sheet_path,
+        None => return Ok(cells),
+    };
+    let pivots: Vec<String> = archive
+        .relationships(&sheet_path)?
+        .into_iter()
+        .filter(|rel| rel.rel_type.ends_with("/pivotTable"))
+        .map(|rel| rel.target)
+        .collect();
+    for pivot_path in pivots {
+        let pivot = match archive.read_xml(&pivot_path)? {
+            Some(pivot) => pivot,
+            None => continue,
+        };
+        let definition_path = archive
+            .relationships(&pivot_path)?
+            .into_iter()
+            .find(|rel| rel.rel_type.ends_with("/pivotCacheDefinition"))
+            .map(|rel| rel.target);
+        let definition = match definition_path {
+            Some(path) => archive
+                .read_xml(&path)?
+                .map(|definition| (path, definition)),
+            None => None,
+        };
+        let (definition_path, definition) = match definition {
+            Some(definition) => definition,
+            None => continue,
+        };
+        let records_path = archive
+            .relationships(&definition_path)?
+            .into_iter()
+            .find(|rel| rel.rel_type.ends_with("/pivotCacheRecords"))
+            .map(|rel| rel.target);
+        let records = match records_path {
+            Some(path) => archive.read_xml(&path)?,
+            None => None,
+        };
+        if let Some(records) = records {
+            let cache = PivotCache::read(&definition, &records);
+            cells.extend(pivot_cells(&pivot, &cache, locale).unwrap_or_default());
+        }
+    }
+    Ok(cells)
+}
+
+/// Put the computed pivot cells into the rows `first_row..=last_row` of the table, where the
+/// sheet doesn't store a value.
+pub fn fill_pivot_cells(
+    table_data: &mut TableData,
+    cells: PivotCells,
+    first_row: u32,
+    last_row: u32,
+) {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let mut cells: Vec<_> = cells
+        .into_iter()
+        .filter(|((column, row), _)| *column <= max_col && (first_row..=last_row).contains(row))
+        .collect();
+    cells.sort_by_key(|(position, _)| *position);
+    for ((column, row_num), (value, kind)) in cells {
+        // 合并区域中被覆盖的单元格不输出
+        let covered = table_data.merged_cells.iter().any(|merge| {
+            (merge.start.row..=merge.end.row).contains(&row_num)
+                && (merge.start.column..=merge.end.column).contains(&column)
+                && (merge.start.row, merge.start.column) != (row_num, column)
+        });
+        if covered {
+            continue;
+        }
+        let index = match table_data
+            .rows
+            .binary_search_by_key(&row_num, |row| row.row_number)
+        {
+            Ok(index) => index,
+            Err(index) => {
+                table_data.rows.insert(
+                    index,
+                    RowData {
+                        row_number: row_num,
+                        cells: Vec::new(),
+                    },
+                );
+                index
+            }
+        };
+        let row = &mut table_data.rows[index];
+        let typed = TypedValue::from_kind(kind, &value);
+        match row.cells.binary_search_by_key(&column, |cell| cell.column) {
+            Ok(index) => {
+                let cell = &mut row.cells[index];
+                if cell.value.is_empty() {
+                    cell.value = value;
+                    cell.kind = Some(kind.to_string());
+                    cell.typed = typed;
+                }
+            }
+            Err(index) => row.cells.insert(
+                index,
+                CellData {
+                    typed,
+                    kind: Some(kind.to_string()),
+                    value,
+                    column,
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::archive_utils::Archive;
+use crate::data_structures::ThemeColors;
+use crate::xml_utils::XmlNode;
+
+/// Raw `xl/styles.xml`, for style properties umya-spreadsheet doesn't expose.
+pub struct RawStyles {
+    styles: Option<XmlNode>,
+}
+
+impl RawStyles {
+    pub fn read(archive: &mut Archive) -> Result<RawStyles, String> {
+        let path = archive
+            .relationships("xl/workbook.xml")?
+            .into_iter()
+            .find(|rel| rel.rel_type.ends_with("/styles"))
+            .map(|rel| rel.target)
+            .unwrap_or_else(|| "xl/styles.xml".to_string());
+        Ok(RawStyles {
+            styles: archive.read_xml(&path)?,
+        })
+    }
+
+    /// The `<xf>` record of `cellXfs` referenced by a cell's `s` attribute.
+    pub fn cell_xf(&self, xf_index: usize) -> Option<&XmlNode> {
+        self.styles
+            .as_ref()?
+            .child("cellXfs")?
+            .children_named("xf")
+            .nth(xf_index)
+    }
+
+    /// Differential format referenced by conditional formatting rules.
+    pub fn dxf(&self, dxf_id: usize) -> Option<&XmlNode> {
+        self.styles
+            .as_ref()?
+            .child("dxfs")?
+            .children_named("dxf")
+            .nth(dxf_id)
+    }
+
+    /// Custom table style of `tableStyles`, by name.
+    pub fn table_style(&self, name: &str) -> Option<&XmlNode> {
+        self.styles
+            .as_ref()?
+            .child("tableStyles")?
+            .children_named("tableStyle")
+            .find(|style| style.attr("name") == Some(name))
+    }
+
+    pub fn fill(&self, xf_index: usize) -> Option<&XmlNode> {
+        let fill_id = self.cell_xf(xf_index)?.attr("fillId")?.parse::<usize>().ok()?;
+        self.styles
+            .as_ref()?
+            .child("fills")?
+            .children_named("fill")
+            .nth(fill_id)
+    }
+
+    pub fn font(&self, xf_index: usize) -> Option<&XmlNode> {
+        let font_id = self.cell_xf(xf_index)?.attr("fontId")?.parse::<usize>().ok()?;
+        self.styles
+            .as_ref()?
+            .child("fonts")?
+            .children_named("font")
+            .nth(font_id)
+    }
+}
+
+/// Fonts of a theme font scheme (`majorFont` or `minorFont`), per script.
+pub struct ThemeFonts {
+    pub latin: Option<String>,
+    pub east_asian: Option<String>,
+    pub complex_script: Option<String>,
+}
+
+/// Excel's default palette of indexed colors, used by old workbooks instead of RGB values.
+const INDEXED_COLORS: [&str; 64] = [
+    "000000", "FFFFFF", "FF0000", "00FF00", "0000FF", "FFFF00", "FF00FF", "00FFFF",
+    "000000", "FFFFFF", "FF0000", "00FF00", "0000FF", "FFFF00", "FF00FF", "00FFFF",
+    "800000", "008000", "000080", "808000", "800080", "008080", "C0C0C0", "808080",
+    "9999FF", "993366", "FFFFCC", "CCFFFF", "660066", "FF8080", "0066CC", "CCCCFF",
+    "000080", "FF00FF", "FFFF00", "00FFFF", "800080", "800000", "008080", "0000FF",
+    "00CCFF", "CCFFFF", "CCFFCC", "FFFF99", "99CCFF", "FF99CC", "CC99FF", "FFCC99",
+    "3366FF", "33CCCC", "99CC00", "FFCC00", "FF9900", "FF6600", "666699", "969696",
+    "003366", "339966", "003300", "333300", "993300", "993366", "333399", "333333",
+];
+
+/// Raw theme part, for the font scheme and theme colors, together with the workbook's palette of
+/// indexed colors.
+pub struct RawTheme {
+    theme: Option<XmlNode>,
+    /// Custom `indexedColors` of `styles.xml`, replacing the default palette when present.
+    palette: Vec<String>,
+    /// Keep the alpha channel of translucent cell colors, set from the `keep_alpha` option.
+    pub keep_alpha: bool,
+}
+
+impl RawTheme {
+    pub fn read(archive: &mut Archive) -> Result<RawTheme, String> {
+        let path = archive
+            .relationships("xl/workbook.xml")?
+            .into_iter()
+            .find(|rel| rel.rel_type.ends_with("/theme"))
+            .map(|rel| rel.target)
+            .unwrap_or_else(|| "xl/theme/theme1.xml".to_string());
+        let styles = RawStyles::read(archive)?;
+        let palette = styles
+            .styles
+            .as_ref()
+            .and_then(|styles| styles.child("colors"))
+            .and_then(|colors| colors.child("indexedColors"))
+            .map(|colors| {
+                colors
+                    .children_named("rgbColor")
+                    .map(|color| {
+                        let rgb = color.attr("rgb").unwrap_or_default().to_uppercase();
+                        rgb.get(rgb.len().saturating_sub(6)..).unwrap_or_default().to_string()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(RawTheme {
+            theme: archive.read_xml(&path)?,
+            palette,
+            keep_alpha: false,
+        })
+    }
+
+    /// Palette color by the index used in `<color indexed="..."/>`. The system colors (64 and
+    /// up) are automatic and give `None`.
+    pub fn indexed_color(&self, index: usize) -> Option<String> {
+        if self.palette.is_empty() {
+            INDEXED_COLORS.get(index).map(|color| color.to_string())
+        } else {
+            self.palette.get(index).filter(|color| color.len() == 6).cloned()
+        }
+    }
+
+    /// Theme color by the index used in `<color theme="..."/>`.
+    pub fn color(&self, index: usize) -> Option<String> {
+        // 索引 0-3 对应 lt1、dk1、lt2、dk2，与 clrScheme 中的顺序不同
+        let name = match index {
+            0 => "lt1",
+            1 => "dk1",
+            2 => "lt2",
+            3 => "dk2",
+            4 => "accent1",
+            5 => "accent2",
+            6 => "accent3",
+            7 => "accent4",
+            8 => "accent5",
+            9 => "accent6",
+            10 => "hlink",
+            11 => "folHlink",
+            _ => return None,
+        };
+        let color = self
+            .theme
+            .as_ref()?
+            .child("themeElements")?
+            .child("clrScheme")?
+            .child(name)?;
+        if let Some(srgb) = color.child("srgbClr") {
+            return srgb.attr("val").map(|val| val.to_uppercase());
+        }
+        color
+            .child("sysClr")
+            .and_then(|sys| sys.attr("lastClr"))
+            .map(|val| val.to_uppercase())
+    }
+
+    /// The color scheme of the theme.
+    pub fn colors(&self) -> ThemeColors {
+        // color() 的索引 0-3 依次为 lt1、dk1、lt2、dk2
+        ThemeColors {
+            light1: self.color(0),
+            dark1: self.color(1),
+            light2: self.color(2),
+            dark2: self.color(3),
+            accent1: self.color(4),
+            accent2: self.color(5),
+            accent3: self.color(6),
+            accent4: self.color(7),
+            accent5: self.color(8),
+            accent6: self.color(9),
+            hyperlink: self.color(10),
+            followed_hyperlink: self.color(11),
+        }
+    }
+
+    /// Fonts for the `major` or `minor` scheme.
+    pub fn fonts(&self, scheme: &str) -> Option<ThemeFonts> {
+        let name = match scheme {
+            "major" => "majorFont",
+            "minor" => "minorFont",
+            _ => return None,
+        };
+        let fonts = self
+            .theme
+            .as_ref()?
+            .child("themeElements")?
+            .child("fontScheme")?
+            .child(name)?;
+        let typeface = |node: Option<&XmlNode>| {
+            node.and_then(|node| node.attr("typeface"))
+                .filter(|typeface| !typeface.is_empty())
+                .map(|typeface| typeface.to_string())
+        };
+        // ea 为空时，按中日韩的顺序取脚本专用字体
+        let script_font = |scripts: &[&str]| {
+            scripts.iter().find_map(|script| {
+                typeface(
+                    fonts
+                        .children_named("font")
+                        .find(|font| font.attr("script") == Some(*script)),
+                )
+            })
+        };
+        Some(ThemeFonts {
+            latin: typeface(fonts.child("latin")),
+            east_asian: typeface(fonts.child("ea"))
+                .or_else(|| script_font(&["Hans", "Hant", "Jpan", "Hang"])),
+            complex_script: typeface(fonts.child("cs")),
+        })
+    }
+}
+
+/// Value of a boolean property element like `<b/>` or `<shadow val="0"/>`.
+pub fn flag(node: Option<&XmlNode>) -> bool {
+    node.is_some_and(|node| {
+        node.attr("val")
+            .is_none_or(|val| val == "1" || val == "true")
+    })
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use crate::color_utils::apply_tint;
+use crate::conditional_utils::{apply_format, dxf_format, ConditionalFormat};
+use crate::data_structures::{ListObject, TableData};
+use crate::style_utils::{RawStyles, RawTheme};
+use crate::utils::parse_range;
+use crate::xml_utils::XmlNode;
+
+/// Formatting of each element of a table style ("headerRow", "firstRowStripe", ...), with the
+/// number of rows or columns a stripe spans.
+type StyleElements = HashMap<String, (ConditionalFormat, u32)>;
+
+/// Elements of a custom table style defined in `styles.xml`.
+fn custom_style(style: &XmlNode, raw_styles: &RawStyles, theme: &RawTheme) -> StyleElements {
+    style
+        .children_named("tableStyleElement")
+        .filter_map(|element| {
+            let format = element
+                .attr("dxfId")
+                .and_then(|dxf_id| dxf_id.parse::<usize>().ok())
+                .and_then(|dxf_id| raw_styles.dxf(dxf_id))
+                .map(|dxf| dxf_format(dxf, theme))?;
+            let size = element
+                .attr("size")
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(1);
+            Some((element.attr("type")?.to_string(), (format, size)))
+        })
+        .collect()
+}
+
+/// Approximation of a built-in table style, whose definition isn't stored in the workbook.
+/// Each group of seven styles uses the dark text color and then accent 1 to 6.
+fn builtin_style(name: &str, theme: &RawTheme) -> Option<StyleElements> {
+    let name = name.strip_prefix("TableStyle")?;
+    let (family, number) = ["Light", "Medium", "Dark"]
+        .into_iter()
+        .find_map(|family| Some((family, name.strip_prefix(family)?.parse::<u32>().ok()?)))?;
+    let (group, index) = match (family, number) {
+        ("Light", 1..=21) | ("Medium", 1..=28) | ("Dark", 1..=7) => {
+            ((number - 1) / 7, (number - 1) % 7)
+        }
+        // Dark8 至 Dark11 依次使用深色文字、accent1、accent3 与 accent5
+        ("Dark", 8..=11) => (1, [0, 1, 3, 5][(number - 8) as usize]),
+        _ => return None,
+    };
+    // 主题颜色索引 1 为 dk1，4 至 9 为 accent1 至 accent6
+    let base = theme.color(if index == 0 { 1 } else { index as usize + 3 })?;
+    let fill = |tint: f64| ConditionalFormat {
+        fill: apply_tint(&base, tint),
+        ..Default::default()
+    };
+    let bold = ConditionalFormat {
+        bold: Some(true),
+        ..Default::default()
+    };
+    let banner = |fill: &str| ConditionalFormat {
+        fill: Some(fill.to_string()),
+        bold: Some(true),
+        color: Some("FFFFFF".to_string()),
+        ..Default::default()
+    };
+    let (header, whole_table, stripe) = match (family, group) {
+        ("Light", 1) => (banner(&base), None, None),
+        ("Light", _) => (bold.clone(), None, Some(fill(0.8))),
+        ("Medium", 0) => (banner(&base), None, Some(fill(0.8))),
+        ("Medium", 1) => (banner(&base), Some(fill(0.8)), Some(fill(0.6))),
+        ("Medium", 2) => (banner("000000"), None, Some(fill(0.8))),
+        ("Medium", _) => (bold.clone(), Some(fill(0.8)), Some(fill(0.6))),
+        ("Dark", 0) => {
+            // 黑色无法再加深，深色文字的样式改用灰色
+            let (whole_tint, stripe_tint) = if index == 0 {
+                (0.25, 0.15)
+            } else {
+                (-0.25, -0.5)
+            };
+            let whole_table = ConditionalFormat {
+                color: Some("FFFFFF".to_string()),
+                ..fill(whole_tint)
+            };
+            (banner("000000"), Some(whole_table), Some(fill(stripe_tint)))
+        }
+        _ => (banner("000000"), Some(fill(0.6)), Some(fill(0.4))),
+    };
+    let mut elements = StyleElements::new();
+    let parts = [
+        ("headerRow", Some(header)),
+        ("totalRow", Some(bold.clone())),
+        ("firstColumn", Some(bold.clone())),
+        ("lastColumn", Some(bold)),
+        ("firstRowStripe", stripe),
+        ("wholeTable", whole_table),
+    ];
+    for (element, format) in parts {
+        if let Some(format) = format {
+            elements.insert(element.to_string(), (format, 1));
+        }
+    }
+    Some(elements)
+}
+
+/// Merge the table style of a list object into the styles of its cells, which carry no direct
+/// formatting for it. A fill of the cell itself wins over the style.
+pub fn apply_table_style(
+    table_data: &mut TableData,
+    list_object: &ListObject,
+    raw_styles: &RawStyles,
+    raw_theme: &RawTheme,
+    parse_fill: bool,
+    parse_font: bool,
+) {
+    let style = match &list_object.style {
+        Some(style) => style,
+        None => return,
+    };
+    let elements = match raw_styles.table_style(&style.name) {
+        Some(custom) => custom_style(custom, raw_styles, raw_theme),
+        None => match builtin_style(&style.name, raw_theme) {
+            Some(elements) => elements,
+            None => return,
+        },
+    };
+    let ((first_col, first_row), (last_col, last_row)) = match parse_range(&list_object.range) {
+        Some(range) => range,
+        None => return,
+    };
+    let body_first = first_row + list_object.header_rows;
+    let body_last = last_row.saturating_sub(list_object.totals_rows);
+    let element = |name: &str| elements.get(name).map(|(format, _)| format);
+    // 条纹交替出现，每条跨越的行数或列数由 size 决定
+    let stripe = |index: u32, first: &str, second: &str| {
+        let size = |name: &str| elements.get(name).map_or(1, |(_, size)| (*size).max(1));
+        let period = size(first) + size(second);
+        element(if index % period < size(first) {
+            first
+        } else {
+            second
+        })
+    };
+    for row in &mut table_data.rows {
+        let row_num = row.row_number;
+        if !(first_row..=last_row).contains(&row_num) {
+            continue;
+        }
+        let part = if row_num < body_first {
+            Some("headerRow")
+        } else if row_num > body_last {
+            Some("totalRow")
+        } else {
+            None
+        };
+        for cell in &mut row.cells {
+            if !(first_col..=last_col).contains(&cell.column) {
+                continue;
+            }
+            // 优先级从高到低：标题行与汇总行、首列与末列、行条纹、列条纹、整个表格
+            let mut formats = Vec::new();
+            formats.extend(part.and_then(element));
+            if style.first_column && cell.column == first_col {
+                formats.extend(element("firstColumn"));
+            }
+            if style.last_column && cell.column == last_col {
+                formats.extend(element("lastColumn"));
+            }
+            if part.is_none() && style.row_stripes {
+                formats.extend(stripe(
+                    row_num - body_first,
+                    "firstRowStripe",
+                    "secondRowStripe",
+                ));
+            }
+            if part.is_none() && style.column_stripes {
+                formats.extend(stripe(
+                    cell.column - first_col,
+                    "firstColumnStripe",
+                    "secondColumnStripe",
+                ));
+            }
+            formats.extend(element("wholeTable"));
+            let mut format = ConditionalFormat::default();
+            for rule_format in formats {
+                format.merge(rule_format.clone());
+            }
+            let has_fill = cell.style.as_ref().is_some_and(|style| {
+                style.color.is_some() || style.pattern.is_some() || style.gradient.is_some()
+            });
+            if has_fill {
+                format.fill = None;
+            }
+            apply_format(cell, format, parse_fill, parse_font);
+        }
+    }
+}
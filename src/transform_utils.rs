@@ -0,0 +1,969 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::border_utils::covered_positions;
+use crate::data_structures::{
+    BlankRun, Border, BorderStrokes, CellData, CellStyle, RowData, RowRange, TableData,
+    ValueGrid,
+};
+use crate::utils::number_to_column;
+
+fn transpose_border(border: Border) -> Border {
+    Border {
+        left: border.top,
+        right: border.bottom,
+        top: border.left,
+        bottom: border.right,
+        // 沿主对角线翻转后两条对角线的方向不变
+        diagonal_up: border.diagonal_up,
+        diagonal_down: border.diagonal_down,
+        stroke: border.stroke,
+        strokes: border.strokes.map(|strokes| BorderStrokes {
+            left: strokes.top,
+            right: strokes.bottom,
+            top: strokes.left,
+            bottom: strokes.right,
+            diagonal: strokes.diagonal,
+        }),
+    }
+}
+
+/// Swap the rows and columns of the table, including merged ranges, borders and dimensions.
+pub fn transpose_table(table_data: &mut TableData) {
+    let mut rows: BTreeMap<u32, Vec<CellData>> = BTreeMap::new();
+    for row in table_data.rows.drain(..) {
+        for mut cell in row.cells {
+            let row_number = cell.column;
+            cell.column = row.row_number;
+            if let Some(style) = cell.style.as_mut() {
+                style.border = style.border.take().map(transpose_border);
+            }
+            rows.entry(row_number).or_default().push(cell);
+        }
+    }
+    table_data.rows = rows
+        .into_iter()
+        .map(|(row_number, mut cells)| {
+            cells.sort_by_key(|cell| cell.column);
+            RowData { row_number, cells }
+        })
+        .collect();
+
+    for mc in &mut table_data.merged_cells {
+        std::mem::swap(&mut mc.start.row, &mut mc.start.column);
+        std::mem::swap(&mut mc.end.row, &mut mc.end.column);
+        mc.update_range();
+    }
+
+    // 列宽以字符宽度计（0.1in，即 7.2pt），行高以 pt 计
+    let dims = &mut table_data.dimensions;
+    let columns = dims.rows.iter().map(|height| height / 7.2).collect();
+    let rows = dims.columns.iter().map(|width| width * 7.2).collect();
+    dims.columns = columns;
+    dims.rows = rows;
+    std::mem::swap(&mut dims.max_columns, &mut dims.max_rows);
+    dims.column_overrides.clear();
+    std::mem::swap(&mut table_data.hidden_rows, &mut table_data.hidden_columns);
+    std::mem::swap(&mut table_data.frozen_rows, &mut table_data.frozen_cols);
+    // 打印标题行与分页符转置后成了列，不再适用
+    table_data.print_title_rows = None;
+    table_data.page_breaks.clear();
+    std::mem::swap(
+        &mut table_data.row_outline_levels,
+        &mut table_data.column_outline_levels,
+    );
+
+    let row_borders = std::mem::take(&mut table_data.row_borders);
+    let column_borders = std::mem::take(&mut table_data.column_borders);
+    table_data.row_borders = column_borders
+        .into_iter()
+        .map(|(key, border)| (key, transpose_border(border)))
+        .collect();
+    table_data.column_borders = row_borders
+        .into_iter()
+        .map(|(key, border)| (key, transpose_border(border)))
+        .collect();
+    table_data.frame = table_data.frame.take().map(transpose_border);
+}
+
+/// Crop the table to the cells from `start` to `end` (both `(column, row)`, inclusive) and
+/// renumber them from A1. Merged ranges are clipped to the region, widths and heights trimmed.
+pub fn crop_table(table_data: &mut TableData, start: (u32, u32), end: (u32, u32)) {
+    let (start_col, start_row) = start;
+    let end_col = end.0.min(table_data.dimensions.max_columns.unwrap_or(0));
+    let end_row = end.1.min(table_data.dimensions.max_rows.unwrap_or(0));
+    let column_count = (end_col + 1).saturating_sub(start_col);
+    let row_count = (end_row + 1).saturating_sub(start_row);
+    let columns: HashMap<u32, u32> = (start_col..=end_col)
+        .map(|col_num| (col_num, col_num - start_col + 1))
+        .collect();
+    let rows: HashMap<u32, u32> = (start_row..=end_row)
+        .map(|row_num| (row_num, row_num - start_row + 1))
+        .collect();
+
+    for section in [
+        &mut table_data.header,
+        &mut table_data.rows,
+        &mut table_data.footer,
+    ] {
+        section.retain(|row| rows.contains_key(&row.row_number));
+        for row in section.iter_mut() {
+            row.row_number = rows[&row.row_number];
+        }
+        remap_rows(section, &columns);
+    }
+
+    // 与区域相交的合并单元格截断到区域内，只剩一个单元格的不再合并
+    table_data.merged_cells.retain(|mc| {
+        mc.start.column <= end_col
+            && mc.end.column >= start_col
+            && mc.start.row <= end_row
+            && mc.end.row >= start_row
+    });
+    for mc in &mut table_data.merged_cells {
+        mc.start.column = mc.start.column.max(start_col) - start_col + 1;
+        mc.end.column = mc.end.column.min(end_col) - start_col + 1;
+        mc.start.row = mc.start.row.max(start_row) - start_row + 1;
+        mc.end.row = mc.end.row.min(end_row) - start_row + 1;
+        mc.update_range();
+    }
+    table_data
+        .merged_cells
+        .retain(|mc| mc.start.column != mc.end.column || mc.start.row != mc.end.row);
+
+    let dims = &mut table_data.dimensions;
+    let slice = |values: &[f64], start: u32, count: u32| -> Vec<f64> {
+        values
+            .iter()
+            .skip(start as usize - 1)
+            .take(count as usize)
+            .copied()
+            .collect()
+    };
+    dims.columns = slice(&dims.columns, start_col, column_count);
+    dims.rows = slice(&dims.rows, start_row, row_count);
+    dims.max_columns = Some(column_count);
+    dims.max_rows = Some(row_count);
+    dims.column_overrides = remap_column_keys(&dims.column_overrides, &columns);
+    table_data.column_borders = remap_column_keys(&table_data.column_borders, &columns);
+    table_data.row_borders = remap_column_keys(&table_data.row_borders, &rows);
+    table_data.hidden_rows = remap_numbers(&table_data.hidden_rows, &rows);
+    table_data.hidden_columns = remap_numbers(&table_data.hidden_columns, &columns);
+    table_data.filtered_rows = remap_numbers(&table_data.filtered_rows, &rows);
+    table_data.frozen_rows = remap_count(table_data.frozen_rows, &rows);
+    table_data.frozen_cols = remap_count(table_data.frozen_cols, &columns);
+    table_data.print_title_rows = remap_range(table_data.print_title_rows, &rows);
+    table_data.page_breaks = remap_numbers(&table_data.page_breaks, &rows);
+    table_data.row_outline_levels = remap_column_keys(&table_data.row_outline_levels, &rows);
+    table_data.column_outline_levels =
+        remap_column_keys(&table_data.column_outline_levels, &columns);
+}
+
+/// Remove the hidden rows and columns, renumbering the rest. Merged ranges shrink to their
+/// visible part, keeping their value. Returns the new number of each remaining row.
+pub fn skip_hidden(table_data: &mut TableData) -> HashMap<u32, u32> {
+    let hidden_rows = table_data.hidden_rows.clone();
+    let hidden_columns = table_data.hidden_columns.clone();
+    remove_rows_and_columns(table_data, &hidden_rows, &hidden_columns)
+}
+
+/// Remove the rows filtered out by the autofilter of the sheet, like `skip_hidden`.
+pub fn skip_filtered(table_data: &mut TableData) -> HashMap<u32, u32> {
+    let filtered_rows = std::mem::take(&mut table_data.filtered_rows);
+    remove_rows_and_columns(table_data, &filtered_rows, &[])
+}
+
+/// Remove the given rows and columns, renumbering the rest. Merged ranges shrink to their
+/// remaining part, keeping their value. Returns the new number of each remaining row.
+fn remove_rows_and_columns(
+    table_data: &mut TableData,
+    removed_rows: &[u32],
+    removed_columns: &[u32],
+) -> HashMap<u32, u32> {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    let remaining = |count: u32, removed: &[u32]| -> HashMap<u32, u32> {
+        let removed: HashSet<&u32> = removed.iter().collect();
+        (1..=count)
+            .filter(|number| !removed.contains(number))
+            .enumerate()
+            .map(|(i, number)| (number, i as u32 + 1))
+            .collect()
+    };
+    let columns = remaining(max_col, removed_columns);
+    let rows = remaining(max_row, removed_rows);
+
+    // 合并区域截断到剩余部分，起始单元格被移除时把值移到第一个剩余的位置
+    let mut moved = HashMap::new();
+    table_data.merged_cells.retain_mut(|mc| {
+        let first_col = (mc.start.column..=mc.end.column).find(|c| columns.contains_key(c));
+        let last_col = (mc.start.column..=mc.end.column).rfind(|c| columns.contains_key(c));
+        let first_row = (mc.start.row..=mc.end.row).find(|r| rows.contains_key(r));
+        let last_row = (mc.start.row..=mc.end.row).rfind(|r| rows.contains_key(r));
+        let (Some(first_col), Some(last_col), Some(first_row), Some(last_row)) =
+            (first_col, last_col, first_row, last_row)
+        else {
+            return false;
+        };
+        if (first_col, first_row) != (mc.start.column, mc.start.row) {
+            moved.insert((mc.start.column, mc.start.row), (first_col, first_row));
+        }
+        mc.start.column = columns[&first_col];
+        mc.end.column = columns[&last_col];
+        mc.start.row = rows[&first_row];
+        mc.end.row = rows[&last_row];
+        mc.update_range();
+        mc.start.column != mc.end.column || mc.start.row != mc.end.row
+    });
+    if !moved.is_empty() {
+        let targets: HashSet<(u32, u32)> = moved.values().copied().collect();
+        let mut cells: BTreeMap<u32, Vec<CellData>> = BTreeMap::new();
+        for row in table_data.rows.drain(..) {
+            for mut cell in row.cells {
+                let position = (cell.column, row.row_number);
+                let (col_num, row_num) = match moved.get(&position) {
+                    Some(target) => *target,
+                    // 被合并区域覆盖的单元格让位给移过来的值
+                    None if targets.contains(&position) => continue,
+                    None => position,
+                };
+                cell.column = col_num;
+                cells.entry(row_num).or_default().push(cell);
+            }
+        }
+        table_data.rows = cells
+            .into_iter()
+            .map(|(row_number, mut cells)| {
+                cells.sort_by_key(|cell| cell.column);
+                RowData { row_number, cells }
+            })
+            .collect();
+    }
+
+    for section in [
+        &mut table_data.header,
+        &mut table_data.rows,
+        &mut table_data.footer,
+    ] {
+        section.retain(|row| rows.contains_key(&row.row_number));
+        for row in section.iter_mut() {
+            row.row_number = rows[&row.row_number];
+        }
+        remap_rows(section, &columns);
+    }
+
+    let dims = &mut table_data.dimensions;
+    let keep = |values: &[f64], numbers: &HashMap<u32, u32>| -> Vec<f64> {
+        values
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| numbers.contains_key(&(*i as u32 + 1)))
+            .map(|(_, value)| *value)
+            .collect()
+    };
+    dims.columns = keep(&dims.columns, &columns);
+    dims.rows = keep(&dims.rows, &rows);
+    dims.max_columns = Some(columns.len() as u32);
+    dims.max_rows = Some(rows.len() as u32);
+    dims.column_overrides = remap_column_keys(&dims.column_overrides, &columns);
+    table_data.column_borders = remap_column_keys(&table_data.column_borders, &columns);
+    table_data.row_borders = remap_column_keys(&table_data.row_borders, &rows);
+    table_data.hidden_rows = remap_numbers(&table_data.hidden_rows, &rows);
+    table_data.hidden_columns = remap_numbers(&table_data.hidden_columns, &columns);
+    table_data.filtered_rows = remap_numbers(&table_data.filtered_rows, &rows);
+    table_data.frozen_rows = remap_count(table_data.frozen_rows, &rows);
+    table_data.frozen_cols = remap_count(table_data.frozen_cols, &columns);
+    table_data.print_title_rows = remap_range(table_data.print_title_rows, &rows);
+    table_data.page_breaks = remap_breaks(&table_data.page_breaks, &rows);
+    table_data.row_outline_levels = remap_column_keys(&table_data.row_outline_levels, &rows);
+    table_data.column_outline_levels =
+        remap_column_keys(&table_data.column_outline_levels, &columns);
+    rows
+}
+
+/// Make the table exactly `column_count` columns wide: cells beyond it are dropped, merged
+/// ranges are clipped, and every missing position (including empty rows) gets a padding cell.
+pub fn normalize_columns(
+    table_data: &mut TableData,
+    column_count: u32,
+    default_width: f64,
+    parse_border: bool,
+) {
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+
+    table_data
+        .merged_cells
+        .retain(|mc| mc.start.column <= column_count);
+    for mc in &mut table_data.merged_cells {
+        if mc.end.column > column_count {
+            mc.end.column = column_count;
+            mc.update_range();
+        }
+    }
+    let covered = covered_positions(table_data);
+
+    let truncated: Vec<String> = table_data
+        .rows
+        .iter()
+        .flat_map(|row| {
+            row.cells
+                .iter()
+                .filter(|cell| cell.column > column_count && !cell.value.is_empty())
+                .map(move |cell| format!("{}{}", number_to_column(cell.column), row.row_number))
+        })
+        .collect();
+    table_data.warn_cells(
+        "truncated_cells",
+        "Cells beyond normalize_columns dropped",
+        truncated,
+    );
+
+    let mut rows: BTreeMap<u32, Vec<CellData>> = table_data
+        .rows
+        .drain(..)
+        .map(|row| (row.row_number, row.cells))
+        .collect();
+    for row_num in 1..=max_row {
+        let cells = rows.entry(row_num).or_default();
+        cells.retain(|cell| cell.column <= column_count);
+        for col_num in 1..=column_count {
+            if covered.contains(&(row_num, col_num)) || cells.iter().any(|c| c.column == col_num) {
+                continue;
+            }
+            // 填充单元格与空单元格一样不绘制边框
+            cells.push(CellData {
+                value: String::new(),
+                column: col_num,
+                style: if parse_border {
+                    Some(CellStyle {
+                        border: Some(Border::default()),
+                        ..Default::default()
+                    })
+                } else {
+                    None
+                },
+                ..Default::default()
+            });
+        }
+        cells.sort_by_key(|cell| cell.column);
+    }
+    table_data.rows = rows
+        .into_iter()
+        .map(|(row_number, cells)| RowData { row_number, cells })
+        .collect();
+
+    let dims = &mut table_data.dimensions;
+    dims.columns.resize(column_count as usize, default_width);
+    dims.max_columns = Some(column_count);
+    dims.column_overrides
+        .retain(|key, _| key.parse::<u32>().is_ok_and(|col_num| col_num <= column_count));
+    table_data
+        .column_borders
+        .retain(|key, _| key.parse::<u32>().is_ok_and(|col_num| col_num <= column_count));
+}
+
+/// Each body row as a record keyed by column header: the last header row, or the first row
+/// when there is no header section. Columns without a header are keyed by their letter,
+/// repeated headers get a `_2`, `_3`, ... suffix.
+pub fn keyed_records(table_data: &TableData) -> Vec<BTreeMap<String, String>> {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let (header_row, body) = match table_data.header.last() {
+        Some(header_row) => (Some(header_row), &table_data.rows[..]),
+        None => (
+            table_data.rows.first(),
+            table_data.rows.get(1..).unwrap_or_default(),
+        ),
+    };
+    let header_row = match header_row {
+        Some(header_row) => header_row,
+        None => return Vec::new(),
+    };
+    let values: HashMap<(u32, u32), &str> = table_data
+        .header
+        .iter()
+        .chain(&table_data.rows)
+        .flat_map(|row| {
+            row.cells
+                .iter()
+                .map(move |cell| ((row.row_number, cell.column), cell.value.as_str()))
+        })
+        .collect();
+
+    let mut keys: Vec<String> = Vec::new();
+    for col_num in 1..=max_col {
+        // 被合并单元格覆盖的表头取合并区域左上角的值
+        let anchor = table_data
+            .merged_cells
+            .iter()
+            .find(|mc| {
+                (mc.start.row..=mc.end.row).contains(&header_row.row_number)
+                    && (mc.start.column..=mc.end.column).contains(&col_num)
+            })
+            .map_or((header_row.row_number, col_num), |mc| {
+                (mc.start.row, mc.start.column)
+            });
+        let name = values
+            .get(&anchor)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .map_or_else(|| number_to_column(col_num), |value| value.to_string());
+        let mut key = name.clone();
+        let mut suffix = 1;
+        while keys.contains(&key) {
+            suffix += 1;
+            key = format!("{}_{}", name, suffix);
+        }
+        keys.push(key);
+    }
+
+    body.iter()
+        .map(|row| {
+            keys.iter()
+                .enumerate()
+                .map(|(index, key)| {
+                    let value = row
+                        .cells
+                        .iter()
+                        .find(|cell| cell.column == index as u32 + 1)
+                        .map(|cell| cell.value.clone())
+                        .unwrap_or_default();
+                    (key.clone(), value)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Drop the `kind` and `typed` fields the readers fill in, when typed values weren't asked for.
+pub fn clear_typed_values(table_data: &mut TableData) {
+    for row in table_data
+        .header
+        .iter_mut()
+        .chain(&mut table_data.rows)
+        .chain(&mut table_data.footer)
+    {
+        for cell in &mut row.cells {
+            cell.kind = None;
+            cell.typed = None;
+        }
+    }
+}
+
+/// Drop the `formula` field the readers fill in, when formulas weren't asked for.
+pub fn clear_formulas(table_data: &mut TableData) {
+    for row in table_data
+        .header
+        .iter_mut()
+        .chain(&mut table_data.rows)
+        .chain(&mut table_data.footer)
+    {
+        for cell in &mut row.cells {
+            cell.formula = None;
+        }
+    }
+}
+
+/// Replace font families by those of `font_map`: fonts following a theme scheme by the entry of
+/// the scheme, other fonts (and the fonts of rich text runs) by the entry of their family.
+pub fn apply_font_map(table_data: &mut TableData, font_map: &BTreeMap<String, String>) {
+    let lookup = |name: &str| {
+        font_map
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, family)| family.clone())
+    };
+    let map_name = |name: &mut Option<String>| {
+        if let Some(family) = name.as_deref().and_then(lookup) {
+            *name = Some(family);
+        }
+    };
+    for row in table_data
+        .header
+        .iter_mut()
+        .chain(&mut table_data.rows)
+        .chain(&mut table_data.footer)
+    {
+        for cell in &mut row.cells {
+            if let Some(font) = cell.style.as_mut().and_then(|style| style.font.as_mut()) {
+                // 主题字体的映射优先于字体名的映射
+                match font.scheme.as_deref().and_then(lookup) {
+                    Some(family) => font.name = Some(family),
+                    None => map_name(&mut font.name),
+                }
+                map_name(&mut font.east_asian_name);
+                map_name(&mut font.complex_script_name);
+            }
+            for run in cell.runs.iter_mut().flatten() {
+                map_name(&mut run.font);
+            }
+        }
+    }
+}
+
+/// Move the first `header_rows` rows into the `header` section.
+pub fn split_header_rows(table_data: &mut TableData, header_rows: u32) {
+    // typst 不允许合并单元格跨出 table.header，表头扩展到覆盖从表头开始的合并区域
+    let mut header_rows = header_rows;
+    while let Some(end_row) = table_data
+        .merged_cells
+        .iter()
+        .filter(|mc| mc.start.row <= header_rows && mc.end.row > header_rows)
+        .map(|mc| mc.end.row)
+        .max()
+    {
+        header_rows = end_row;
+    }
+    let (header, body) = table_data
+        .rows
+        .drain(..)
+        .partition(|row| row.row_number <= header_rows);
+    table_data.header = header;
+    table_data.rows = body;
+}
+
+/// Move the last `footer_rows` rows into the `footer` section.
+pub fn split_footer_rows(table_data: &mut TableData, footer_rows: u32) {
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    // 与表头相同，表尾扩展到覆盖跨入表尾的合并区域
+    let mut first_footer_row = max_row.saturating_sub(footer_rows);
+    while let Some(start_row) = table_data
+        .merged_cells
+        .iter()
+        .filter(|mc| mc.start.row <= first_footer_row && mc.end.row > first_footer_row)
+        .map(|mc| mc.start.row)
+        .min()
+    {
+        first_footer_row = start_row - 1;
+    }
+    let (footer, body) = table_data
+        .rows
+        .drain(..)
+        .partition(|row| row.row_number > first_footer_row);
+    table_data.footer = footer;
+    table_data.rows = body;
+}
+
+fn remap_rows(rows: &mut Vec<RowData>, columns: &HashMap<u32, u32>) {
+    for row in rows.iter_mut() {
+        row.cells.retain(|cell| columns.contains_key(&cell.column));
+        for cell in &mut row.cells {
+            cell.column = columns[&cell.column];
+        }
+        row.cells.sort_by_key(|cell| cell.column);
+    }
+    rows.retain(|row| !row.cells.is_empty());
+}
+
+fn remap_column_keys<T: Clone>(
+    map: &BTreeMap<String, T>,
+    columns: &HashMap<u32, u32>,
+) -> BTreeMap<String, T> {
+    map.iter()
+        .filter_map(|(key, value)| {
+            let col_num = columns.get(&key.parse::<u32>().ok()?)?;
+            Some((col_num.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// Number of leading rows or columns still among the first `count` ones after remapping.
+fn remap_count(count: Option<u32>, map: &HashMap<u32, u32>) -> Option<u32> {
+    let count = count?;
+    let remaining = (1..=count).filter(|number| map.contains_key(number)).count() as u32;
+    Some(remaining).filter(|remaining| *remaining > 0)
+}
+
+/// The part of a row range that is still there after remapping.
+fn remap_range(range: Option<RowRange>, map: &HashMap<u32, u32>) -> Option<RowRange> {
+    let range = range?;
+    let remaining: Vec<u32> = (range.first..=range.last)
+        .filter_map(|number| map.get(&number).copied())
+        .collect();
+    Some(RowRange {
+        first: *remaining.iter().min()?,
+        last: *remaining.iter().max()?,
+    })
+}
+
+/// Page breaks after renumbering, a break after a removed row moving up to the last
+/// remaining row before it.
+fn remap_breaks(breaks: &[u32], map: &HashMap<u32, u32>) -> Vec<u32> {
+    let mut remapped: Vec<u32> = breaks
+        .iter()
+        .filter_map(|row_num| (1..=*row_num).rev().find_map(|row_num| map.get(&row_num)))
+        .copied()
+        .collect();
+    remapped.dedup();
+    remapped
+}
+
+fn remap_numbers(numbers: &[u32], map: &HashMap<u32, u32>) -> Vec<u32> {
+    numbers
+        .iter()
+        .filter_map(|number| map.get(number).copied())
+        .collect()
+}
+
+/// A table with the layout of `table_data` but no rows, as the base of a segment: the dimensions,
+/// header rows, merged cells, borders and detected rows. Table-wide data such as `records`,
+/// `warnings`, `styles` and `metadata` stays once at the top level.
+fn segment_base(table_data: &TableData) -> TableData {
+    TableData {
+        dimensions: table_data.dimensions.clone(),
+        header: table_data.header.clone(),
+        merged_cells: table_data.merged_cells.clone(),
+        row_borders: table_data.row_borders.clone(),
+        column_borders: table_data.column_borders.clone(),
+        frame: table_data.frame.clone(),
+        show_gridlines: table_data.show_gridlines,
+        gridline: table_data.gridline.clone(),
+        hidden_rows: table_data.hidden_rows.clone(),
+        hidden_columns: table_data.hidden_columns.clone(),
+        frozen_rows: table_data.frozen_rows,
+        frozen_cols: table_data.frozen_cols,
+        column_outline_levels: table_data.column_outline_levels.clone(),
+        banding: table_data.banding.clone(),
+        heading_rows: table_data.heading_rows.clone(),
+        total_rows: table_data.total_rows.clone(),
+        header_groups: table_data.header_groups.clone(),
+        orientation: table_data.orientation.clone(),
+        ..Default::default()
+    }
+}
+
+/// A segment of the table containing only the given columns, renumbered from 1 in the given order.
+pub fn select_columns(table_data: &TableData, selected: &[u32]) -> TableData {
+    let columns: HashMap<u32, u32> = selected
+        .iter()
+        .enumerate()
+        .map(|(i, col_num)| (*col_num, i as u32 + 1))
+        .collect();
+    let mut block = segment_base(table_data);
+    block.rows = table_data.rows.clone();
+    block.footer = table_data.footer.clone();
+
+    remap_rows(&mut block.header, &columns);
+    remap_rows(&mut block.rows, &columns);
+    remap_rows(&mut block.footer, &columns);
+
+    // 合并区域只保留起始列被选中的部分，并截断到连续选中的列
+    block.merged_cells.retain(|mc| columns.contains_key(&mc.start.column));
+    for mc in &mut block.merged_cells {
+        let start = columns[&mc.start.column];
+        let mut end = start;
+        for col_num in mc.start.column + 1..=mc.end.column {
+            match columns.get(&col_num) {
+                Some(new_col) if *new_col == end + 1 => end = *new_col,
+                _ => break,
+            }
+        }
+        mc.start.column = start;
+        mc.end.column = end;
+        mc.update_range();
+    }
+
+    block.dimensions.column_overrides =
+        remap_column_keys(&table_data.dimensions.column_overrides, &columns);
+    block.column_borders = remap_column_keys(&table_data.column_borders, &columns);
+    block.hidden_columns = remap_numbers(&table_data.hidden_columns, &columns);
+    // 只有排在最前面的选中列仍然冻结
+    block.frozen_cols = table_data.frozen_cols.and_then(|frozen| {
+        let leading = selected.iter().take_while(|col_num| **col_num <= frozen).count() as u32;
+        Some(leading).filter(|leading| *leading > 0)
+    });
+    block.column_outline_levels = remap_column_keys(&table_data.column_outline_levels, &columns);
+    block.dimensions.columns = selected
+        .iter()
+        .map(|col_num| {
+            table_data
+                .dimensions
+                .columns
+                .get(*col_num as usize - 1)
+                .copied()
+                .unwrap_or(0.0)
+        })
+        .collect();
+    block.dimensions.max_columns = Some(selected.len() as u32);
+    block
+}
+
+/// Split a table wider than `max_width` pt into stacked blocks of columns,
+/// each repeating the first `key_columns` columns.
+pub fn split_columns(table_data: &mut TableData, max_width: f64, key_columns: u32) {
+    if !table_data.segments.is_empty() {
+        for segment in &mut table_data.segments {
+            split_columns(segment, max_width, key_columns);
+        }
+        return;
+    }
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    // 列宽以字符宽度计，每个字符宽 0.1in，即 7.2pt
+    let width = |col_num: u32| {
+        table_data
+            .dimensions
+            .columns
+            .get(col_num as usize - 1)
+            .copied()
+            .unwrap_or(0.0)
+            * 7.2
+    };
+    let key_columns = key_columns.min(max_col);
+    let key_width: f64 = (1..=key_columns).map(width).sum();
+
+    let mut blocks: Vec<Vec<u32>> = Vec::new();
+    let mut current: Vec<u32> = Vec::new();
+    let mut current_width = key_width;
+    for col_num in key_columns + 1..=max_col {
+        if !current.is_empty() && current_width + width(col_num) > max_width {
+            blocks.push(std::mem::take(&mut current));
+            current_width = key_width;
+        }
+        current.push(col_num);
+        current_width += width(col_num);
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    if blocks.len() < 2 {
+        return;
+    }
+
+    let segments = blocks
+        .into_iter()
+        .map(|block| {
+            let selected: Vec<u32> = (1..=key_columns).chain(block).collect();
+            select_columns(table_data, &selected)
+        })
+        .collect();
+    table_data.header.clear();
+    table_data.rows.clear();
+    table_data.footer.clear();
+    table_data.segments = segments;
+}
+
+/// Split the body rows into segments of at most `chunk_size` rows, each repeating the header rows.
+/// The footer rows are kept with the last segment.
+pub fn chunk_rows(table_data: &mut TableData, chunk_size: u32) {
+    let chunk_size = chunk_size.max(1) as usize;
+    if table_data.rows.len() <= chunk_size {
+        return;
+    }
+    let body = std::mem::take(&mut table_data.rows);
+    let chunk_count = body.len().div_ceil(chunk_size);
+    let mut body = body.into_iter();
+    let chunks = (0..chunk_count)
+        .map(|_| body.by_ref().take(chunk_size).collect())
+        .collect();
+    segment_rows(table_data, chunks);
+}
+
+/// Split the body into segments at the manual page breaks, each repeating the header rows.
+pub fn split_page_breaks(table_data: &mut TableData) {
+    let breaks = &table_data.page_breaks;
+    let mut chunks: Vec<Vec<RowData>> = Vec::new();
+    let mut previous_row = 0;
+    for row in std::mem::take(&mut table_data.rows) {
+        // 与上一行之间有分页符时另起一段
+        let page_break = breaks
+            .iter()
+            .any(|row_num| (previous_row..row.row_number).contains(row_num));
+        previous_row = row.row_number;
+        match chunks.last_mut() {
+            Some(chunk) if !page_break => chunk.push(row),
+            _ => chunks.push(vec![row]),
+        }
+    }
+    if chunks.len() > 1 {
+        segment_rows(table_data, chunks);
+    } else {
+        table_data.rows = chunks.into_iter().flatten().collect();
+    }
+}
+
+/// Turn the chunks of body rows into segments, each repeating the header rows; the footer
+/// goes into the last segment.
+fn segment_rows(table_data: &mut TableData, chunks: Vec<Vec<RowData>>) {
+    let footer = std::mem::take(&mut table_data.footer);
+    let chunk_count = chunks.len();
+
+    let mut segments = Vec::new();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let first_row = chunk.first().map_or(0, |row| row.row_number);
+        let last_row = chunk.last().map_or(0, |row| row.row_number);
+        let is_last = i + 1 == chunk_count;
+
+        let mut segment = segment_base(table_data);
+        segment.rows = chunk;
+        segment.footer = if is_last { footer.clone() } else { Vec::new() };
+
+        let header_rows: Vec<u32> = segment.header.iter().map(|row| row.row_number).collect();
+        let footer_rows: Vec<u32> = segment.footer.iter().map(|row| row.row_number).collect();
+        // 合并区域截断到本段的最后一行
+        segment.merged_cells.retain(|mc| {
+            header_rows.contains(&mc.start.row)
+                || footer_rows.contains(&mc.start.row)
+                || (first_row..=last_row).contains(&mc.start.row)
+        });
+        for mc in &mut segment.merged_cells {
+            if (first_row..=last_row).contains(&mc.start.row) && mc.end.row > last_row {
+                mc.end.row = last_row;
+                mc.update_range();
+            }
+        }
+
+        let row_numbers: Vec<u32> = segment
+            .header
+            .iter()
+            .chain(&segment.rows)
+            .chain(&segment.footer)
+            .map(|row| row.row_number)
+            .collect();
+        segment.dimensions.rows = row_numbers
+            .iter()
+            .map(|row_num| {
+                table_data
+                    .dimensions
+                    .rows
+                    .get(*row_num as usize - 1)
+                    .copied()
+                    .unwrap_or(0.0)
+            })
+            .collect();
+        segment.dimensions.max_rows = Some(row_numbers.len() as u32);
+        segments.push(segment);
+    }
+
+    table_data.header.clear();
+    table_data.segments = segments;
+}
+
+/// Move identical cell styles into `styles` under stable class names ("style-1", "style-2", ...),
+/// numbered in order of first appearance.
+pub fn assign_style_classes(table_data: &mut TableData) -> Result<(), String> {
+    let mut classes: HashMap<String, String> = HashMap::new();
+    for row in &mut table_data.rows {
+        for cell in &mut row.cells {
+            let style = match cell.style.take() {
+                Some(style) => style,
+                None => continue,
+            };
+            let key = toml::to_string(&style)
+                .map_err(|e| format!("Failed to serialize to TOML: {}", e))?;
+            let class = match classes.get(&key) {
+                Some(class) => class.clone(),
+                None => {
+                    let class = format!("style-{}", classes.len() + 1);
+                    classes.insert(key, class.clone());
+                    table_data.styles.insert(class.clone(), style);
+                    class
+                }
+            };
+            cell.class = Some(class);
+        }
+    }
+    Ok(())
+}
+
+/// Group consecutive numbers into runs.
+fn runs(numbers: impl Iterator<Item = u32>) -> Vec<BlankRun> {
+    let mut runs: Vec<BlankRun> = Vec::new();
+    for number in numbers {
+        match runs.last_mut() {
+            Some(run) if run.start + run.length == number => run.length += 1,
+            _ => runs.push(BlankRun {
+                start: number,
+                length: 1,
+            }),
+        }
+    }
+    runs
+}
+
+/// Record the rows without cells and the empty cells of each row as runs.
+/// Positions covered by a merged cell are not blank.
+pub fn encode_blank_runs(table_data: &mut TableData) {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0);
+    let max_row = table_data.dimensions.max_rows.unwrap_or(0);
+    let covered = covered_positions(table_data);
+    let rows: HashMap<u32, &RowData> = table_data
+        .header
+        .iter()
+        .chain(&table_data.rows)
+        .chain(&table_data.footer)
+        .map(|row| (row.row_number, row))
+        .collect();
+
+    table_data.blank_rows = runs((1..=max_row).filter(|row_num| {
+        !rows.contains_key(row_num)
+            && !(1..=max_col).any(|col_num| covered.contains(&(*row_num, col_num)))
+    }));
+    let mut blank_cells = BTreeMap::new();
+    for (row_num, row) in &rows {
+        let cells: Vec<u32> = row.cells.iter().map(|cell| cell.column).collect();
+        let blanks = runs((1..=max_col).filter(|col_num| {
+            !cells.contains(col_num) && !covered.contains(&(*row_num, *col_num))
+        }));
+        if !blanks.is_empty() {
+            blank_cells.insert(row_num.to_string(), blanks);
+        }
+    }
+    table_data.blank_cells = blank_cells;
+}
+
+fn value_grid(table_data: &mut TableData, row_numbers: Vec<u32>) {
+    let max_col = table_data.dimensions.max_columns.unwrap_or(0) as usize;
+    let last_header = table_data.header.iter().map(|row| row.row_number).max();
+    let first_footer = table_data.footer.iter().map(|row| row.row_number).min();
+    let header_rows = last_header.map_or(0, |last| row_numbers.iter().filter(|n| **n <= last).count());
+    let footer_rows = first_footer.map_or(0, |first| row_numbers.iter().filter(|n| **n >= first).count());
+    let positions: HashMap<u32, usize> = row_numbers
+        .iter()
+        .enumerate()
+        .map(|(index, row_num)| (*row_num, index))
+        .collect();
+
+    let mut cells = vec![vec![String::new(); max_col]; row_numbers.len()];
+    let rows = table_data
+        .header
+        .drain(..)
+        .chain(table_data.rows.drain(..))
+        .chain(table_data.footer.drain(..));
+    for row in rows {
+        let Some(index) = positions.get(&row.row_number) else {
+            continue;
+        };
+        for cell in row.cells {
+            if let Some(value) = cells[*index].get_mut(cell.column as usize - 1) {
+                *value = cell.value;
+            }
+        }
+    }
+    table_data.values = Some(ValueGrid {
+        row_numbers,
+        cells,
+        header_rows: header_rows as u32,
+        footer_rows: footer_rows as u32,
+    });
+}
+
+/// Replace the rows (and those of every segment) with a plain grid of values.
+/// A whole table covers every row; segments cover the rows they contain.
+pub fn encode_value_grid(table_data: &mut TableData, is_segment: bool) {
+    if !table_data.segments.is_empty() {
+        for segment in &mut table_data.segments {
+            encode_value_grid(segment, true);
+        }
+        return;
+    }
+    let row_numbers = if is_segment {
+        table_data
+            .header
+            .iter()
+            .chain(&table_data.rows)
+            .chain(&table_data.footer)
+            .map(|row| row.row_number)
+            .collect()
+    } else {
+        (1..=table_data.dimensions.max_rows.unwrap_or(0)).collect()
+    };
+    value_grid(table_data, row_numbers);
+}
@@ -4,6 +4,16 @@ pub fn column_to_number(column: &str) -> u32 {
         .fold(0, |acc, c| acc * 26 + (c as u32 - 'A' as u32 + 1))
 }
 
+pub fn number_to_column(mut number: u32) -> String {
+    let mut column = Vec::new();
+    while number > 0 {
+        let rem = (number - 1) % 26;
+        column.push((b'A' + rem as u8) as char);
+        number = (number - 1) / 26;
+    }
+    column.iter().rev().collect()
+}
+
 pub fn parse_cell_reference(cell_ref: &str) -> (u32, u32) {
     let col_str: String = cell_ref.chars().take_while(|c| c.is_alphabetic()).collect();
     let row: u32 = cell_ref
@@ -18,4 +28,84 @@ pub fn parse_cell_reference(cell_ref: &str) -> (u32, u32) {
 pub fn parse_merge_range(range: &str) -> (String, String) {
     let parts: Vec<&str> = range.split(':').collect();
     (parts[0].to_string(), parts[1].to_string())
-}
\ No newline at end of file
+}
+
+/// Parse a column key given either as letters ("B", "aa") or as a 1-based index ("2").
+pub fn parse_column_key(key: &str) -> Option<u32> {
+    let key = key.trim();
+    if key.is_empty() {
+        return None;
+    }
+    if let Ok(index) = key.parse::<u32>() {
+        return if index > 0 { Some(index) } else { None };
+    }
+    if key.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Some(column_to_number(&key.to_ascii_uppercase()));
+    }
+    None
+}
+
+/// Check that a value can be safely evaluated as a typst length on the typst side.
+pub fn is_typst_length(value: &str) -> bool {
+    let value = value.trim();
+    if value == "auto" {
+        return true;
+    }
+    let number_end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(number_end);
+    number.parse::<f64>().is_ok()
+        && matches!(unit, "pt" | "mm" | "cm" | "in" | "em" | "fr" | "%")
+}
+
+/// Convert an absolute typst length ("10cm", "400pt") to pt.
+pub fn length_to_pt(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let number_end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(number_end);
+    let number = number.parse::<f64>().ok()?;
+    match unit {
+        "pt" => Some(number),
+        "mm" => Some(number * 72.0 / 25.4),
+        "cm" => Some(number * 72.0 / 2.54),
+        "in" => Some(number * 72.0),
+        _ => None,
+    }
+}
+
+/// Parse an A1-style range ("B2:F40", "$A$1" or a single cell) into
+/// ((start column, start row), (end column, end row)), normalized so that start <= end.
+pub fn parse_range(range: &str) -> Option<((u32, u32), (u32, u32))> {
+    let range = range.replace('$', "");
+    let (start, end) = match range.split_once(':') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => (range.trim(), range.trim()),
+    };
+    let (start_col, start_row) = parse_cell_reference(start);
+    let (end_col, end_row) = parse_cell_reference(end);
+    if start_col == 0 || start_row == 0 || end_col == 0 || end_row == 0 {
+        return None;
+    }
+    Some((
+        (start_col.min(end_col), start_row.min(end_row)),
+        (start_col.max(end_col), start_row.max(end_row)),
+    ))
+}
+
+/// 64-bit FNV-1a hash over several byte slices, as a hex string.
+/// Each part is prefixed with its length so that different splits never collide.
+pub fn content_hash(parts: &[&[u8]]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash = OFFSET_BASIS;
+    for part in parts {
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part.iter()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    format!("{:016x}", hash)
+}
@@ -0,0 +1,465 @@
+use std::io::{Cursor, Read, Seek};
+
+use calamine::{Reader, SheetVisible, Xls, Xlsb};
+
+use crate::archive_utils::{Archive, ArchiveLimits};
+use crate::calamine_utils::{is_xls, is_xlsb};
+use crate::color_utils::resolve_raw_color;
+use crate::data_structures::{
+    DefinedName, ListObject, ListStyle, RowRange, SheetInfo, SheetName, ThemeColors, WorkbookInfo,
+};
+use crate::ods_utils::is_ods;
+use crate::style_utils::RawTheme;
+use crate::utils::{number_to_column, parse_range};
+use crate::xml_utils::XmlNode;
+
+fn indexed(names: Vec<String>) -> Vec<SheetName> {
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| SheetName { index, name })
+        .collect()
+}
+
+/// `<table:table>` elements of an ods `content.xml`.
+fn ods_tables(content: &XmlNode) -> Vec<&XmlNode> {
+    content
+        .child("body")
+        .and_then(|body| body.child("spreadsheet"))
+        .map(|spreadsheet| spreadsheet.children_named("table").collect())
+        .unwrap_or_default()
+}
+
+fn read_ods_content(archive: &mut Archive) -> Result<XmlNode, String> {
+    archive
+        .read_xml("content.xml")?
+        .ok_or_else(|| "Failed to read content.xml".to_string())
+}
+
+fn read_workbook_xml(archive: &mut Archive) -> Result<XmlNode, String> {
+    archive
+        .read_xml("xl/workbook.xml")?
+        .ok_or_else(|| "Failed to read xl/workbook.xml".to_string())
+}
+
+fn workbook_sheets(workbook: &XmlNode) -> Vec<&XmlNode> {
+    workbook
+        .child("sheets")
+        .map(|sheets| sheets.children_named("sheet").collect())
+        .unwrap_or_default()
+}
+
+/// Names of the worksheets of an xlsx, xlsb, xls or ods workbook, in workbook order.
+pub fn read_sheet_names(bytes: &[u8]) -> Result<Vec<SheetName>, String> {
+    if is_xls(bytes) {
+        let workbook = Xls::new(Cursor::new(bytes))
+            .map_err(|e| format!("Failed to read xls file: {}", e))?;
+        return Ok(indexed(workbook.sheet_names()));
+    }
+    let mut archive = Archive::new(bytes, &ArchiveLimits::default())?;
+    if is_xlsb(&mut archive) {
+        return read_xlsb_sheet_names(bytes);
+    }
+    read_archive_sheet_names(&mut archive)
+}
+
+pub fn read_xlsb_sheet_names(bytes: &[u8]) -> Result<Vec<SheetName>, String> {
+    let workbook = Xlsb::new(Cursor::new(bytes))
+        .map_err(|e| format!("Failed to read xlsb file: {}", e))?;
+    Ok(indexed(workbook.sheet_names()))
+}
+
+/// Names of the worksheets of an already opened xlsx or ods archive.
+pub fn read_archive_sheet_names(archive: &mut Archive) -> Result<Vec<SheetName>, String> {
+    if is_ods(archive)? {
+        let content = read_ods_content(archive)?;
+        let names = ods_tables(&content)
+            .into_iter()
+            .map(|table| table.attr("table:name").unwrap_or_default().to_string())
+            .collect();
+        return Ok(indexed(names));
+    }
+    let workbook = read_workbook_xml(archive)?;
+    let names = workbook_sheets(&workbook)
+        .into_iter()
+        .map(|sheet| sheet.attr("name").unwrap_or_default().to_string())
+        .collect();
+    Ok(indexed(names))
+}
+
+/// Index of the worksheet called `name`, or an error listing the available names.
+pub fn resolve_sheet_name(bytes: &[u8], name: &str) -> Result<usize, String> {
+    let sheets = read_sheet_names(bytes)?;
+    sheets
+        .iter()
+        .find(|sheet| sheet.name == name)
+        .map(|sheet| sheet.index)
+        .ok_or_else(|| {
+            let names: Vec<&str> = sheets.iter().map(|sheet| sheet.name.as_str()).collect();
+            format!(
+                "Failed to get worksheet {:?}, available sheets: {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+/// Workbook overview through calamine, for xls and xlsb. Tab colors are not available.
+fn calamine_workbook_info<RS: Read + Seek, R: Reader<RS>>(
+    workbook: &mut R,
+) -> WorkbookInfo {
+    let sheets_metadata = workbook.sheets_metadata().to_vec();
+    let sheet_names: Vec<String> = sheets_metadata.iter().map(|sheet| sheet.name.clone()).collect();
+    let sheets = sheets_metadata
+        .iter()
+        .enumerate()
+        .map(|(index, sheet)| {
+            let used_range = workbook
+                .worksheet_range(&sheet.name)
+                .ok()
+                .and_then(|range| Some((range.start()?, range.end()?)))
+                .map(|((start_row, start_col), (end_row, end_col))| {
+                    format!(
+                        "{}{}:{}{}",
+                        number_to_column(start_col + 1),
+                        start_row + 1,
+                        number_to_column(end_col + 1),
+                        end_row + 1
+                    )
+                });
+            SheetInfo {
+                index,
+                name: sheet.name.clone(),
+                used_range,
+                visibility: match sheet.visible {
+                    SheetVisible::Visible => "visible",
+                    SheetVisible::Hidden => "hidden",
+                    SheetVisible::VeryHidden => "veryHidden",
+                }
+                .to_string(),
+                tab_color: None,
+            }
+        })
+        .collect();
+    // calamine 不区分名称的作用域，工作表级名称带有 "Sheet!" 前缀时按前缀识别
+    let defined_names = workbook
+        .defined_names()
+        .iter()
+        .map(|(name, refers_to)| DefinedName {
+            name: name.clone(),
+            refers_to: refers_to.clone(),
+            sheet: name
+                .split_once('!')
+                .and_then(|(sheet, _)| sheet_names.iter().position(|n| n == sheet)),
+        })
+        .collect();
+    WorkbookInfo {
+        sheet_count: sheet_names.len(),
+        sheets,
+        defined_names,
+    }
+}
+
+/// Workbook overview of an ods file. Visibility and tab colors live in the view settings
+/// and are not read; every sheet is reported as visible.
+fn ods_workbook_info(archive: &mut Archive) -> Result<WorkbookInfo, String> {
+    let content = read_ods_content(archive)?;
+    let tables = ods_tables(&content);
+    let sheets: Vec<SheetInfo> = tables
+        .iter()
+        .enumerate()
+        .map(|(index, table)| SheetInfo {
+            index,
+            name: table.attr("table:name").unwrap_or_default().to_string(),
+            used_range: None,
+            visibility: "visible".to_string(),
+            tab_color: None,
+        })
+        .collect();
+    let defined_names = content
+        .child("body")
+        .and_then(|body| body.child("spreadsheet"))
+        .and_then(|spreadsheet| spreadsheet.child("named-expressions"))
+        .map(|expressions| {
+            expressions
+                .children
+                .iter()
+                .filter_map(|expression| {
+                    let refers_to = match expression.name.as_str() {
+                        "named-range" => expression.attr("table:cell-range-address")?,
+                        "named-expression" => expression.attr("table:expression")?,
+                        _ => return None,
+                    };
+                    Some(DefinedName {
+                        name: expression.attr("table:name")?.to_string(),
+                        refers_to: refers_to.to_string(),
+                        sheet: None,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(WorkbookInfo {
+        sheet_count: sheets.len(),
+        sheets,
+        defined_names,
+    })
+}
+
+/// Workbook overview of an xlsx file, from `workbook.xml` and the `dimension` and
+/// `tabColor` elements of each sheet.
+fn xlsx_workbook_info(archive: &mut Archive) -> Result<WorkbookInfo, String> {
+    let workbook = read_workbook_xml(archive)?;
+    let theme = RawTheme::read(archive)?;
+    let mut sheets = Vec::new();
+    for (index, sheet) in workbook_sheets(&workbook).into_iter().enumerate() {
+        let sheet_xml = match archive.sheet_path(index)? {
+            Some(path) => archive.read_xml(&path)?,
+            None => None,
+        };
+        let used_range = sheet_xml
+            .as_ref()
+            .and_then(|sheet_xml| sheet_xml.child("dimension"))
+            .and_then(|dimension| dimension.attr("ref"))
+            .map(|reference| reference.to_string());
+        let tab_color = sheet_xml
+            .as_ref()
+            .and_then(|sheet_xml| sheet_xml.child("sheetPr"))
+            .and_then(|properties| properties.child("tabColor"))
+            .and_then(|color| resolve_raw_color(color, &theme));
+        sheets.push(SheetInfo {
+            index,
+            name: sheet.attr("name").unwrap_or_default().to_string(),
+            used_range,
+            visibility: sheet.attr("state").unwrap_or("visible").to_string(),
+            tab_color,
+        });
+    }
+    let defined_names = workbook
+        .child("definedNames")
+        .map(|names| {
+            names
+                .children_named("definedName")
+                .map(|name| DefinedName {
+                    name: name.attr("name").unwrap_or_default().to_string(),
+                    refers_to: name.text_content(),
+                    sheet: name.attr("localSheetId").and_then(|id| id.parse().ok()),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(WorkbookInfo {
+        sheet_count: sheets.len(),
+        sheets,
+        defined_names,
+    })
+}
+
+/// Reference of a defined name scoped to the sheet at `sheet_index`, e.g. `_xlnm.Print_Titles`.
+pub fn read_sheet_defined_name(
+    archive: &mut Archive,
+    sheet_index: usize,
+    name: &str,
+) -> Result<Option<String>, String> {
+    let workbook = read_workbook_xml(archive)?;
+    let sheet_id = sheet_index.to_string();
+    Ok(workbook
+        .child("definedNames")
+        .and_then(|names| {
+            names.children_named("definedName").find(|defined_name| {
+                defined_name.attr("name") == Some(name)
+                    && defined_name.attr("localSheetId") == Some(sheet_id.as_str())
+            })
+        })
+        .map(|defined_name| defined_name.text_content()))
+}
+
+/// Rows of a print titles reference, e.g. `Sheet1!$1:$2` or `Sheet1!$A:$A,Sheet1!$1:$1`.
+pub fn print_title_rows(refers_to: &str) -> Option<RowRange> {
+    refers_to.split(',').find_map(|area| {
+        let area = area.rsplit_once('!').map_or(area, |(_, area)| area).replace('$', "");
+        let (first, last) = area.trim().split_once(':')?;
+        let first = first.parse::<u32>().ok()?;
+        let last = last.parse::<u32>().ok()?;
+        Some(RowRange {
+            first: first.min(last),
+            last: first.max(last),
+        })
+    })
+}
+
+/// A1 range of a print area reference, the first area when it has several. Areas are separated
+/// by commas in xlsx files and by spaces in ods files; quoted sheet names may contain either.
+pub fn print_area_range(refers_to: &str) -> Option<String> {
+    let mut quoted = false;
+    let end = refers_to
+        .char_indices()
+        .find(|(_, c)| {
+            if *c == '\'' {
+                quoted = !quoted;
+            }
+            !quoted && (*c == ',' || *c == ' ')
+        })
+        .map_or(refers_to.len(), |(index, _)| index);
+    split_reference(&refers_to[..end]).map(|(_, range)| range)
+}
+
+/// Workbook-level metadata of an xlsx, xlsb, xls or ods workbook.
+pub fn read_workbook_info(bytes: &[u8]) -> Result<WorkbookInfo, String> {
+    if is_xls(bytes) {
+        let mut workbook = Xls::new(Cursor::new(bytes))
+            .map_err(|e| format!("Failed to read xls file: {}", e))?;
+        return Ok(calamine_workbook_info(&mut workbook));
+    }
+    let mut archive = Archive::new(bytes, &ArchiveLimits::default())?;
+    if is_xlsb(&mut archive) {
+        let mut workbook = Xlsb::new(Cursor::new(bytes))
+            .map_err(|e| format!("Failed to read xlsb file: {}", e))?;
+        return Ok(calamine_workbook_info(&mut workbook));
+    }
+    if is_ods(&mut archive)? {
+        return ods_workbook_info(&mut archive);
+    }
+    xlsx_workbook_info(&mut archive)
+}
+
+/// Theme colors of a workbook; only xlsx files have a theme.
+pub fn read_theme_colors(bytes: &[u8]) -> Result<ThemeColors, String> {
+    if is_xls(bytes) {
+        return Ok(ThemeColors::default());
+    }
+    let mut archive = Archive::new(bytes, &ArchiveLimits::default())?;
+    if is_xlsb(&mut archive) || is_ods(&mut archive)? {
+        return Ok(ThemeColors::default());
+    }
+    Ok(RawTheme::read(&mut archive)?.colors())
+}
+
+/// Split a defined name's reference into sheet name and A1 range, accepting both the
+/// xlsx form (`'My Sheet'!$A$1:$D$10`) and the ods form (`$'My Sheet'.$A$1:.$D$10`).
+pub fn split_reference(refers_to: &str) -> Option<(String, String)> {
+    let refers_to = refers_to.trim().trim_start_matches('=');
+    let (sheet, range) = match refers_to.rsplit_once('!') {
+        Some((sheet, range)) => (sheet.to_string(), range.to_string()),
+        None => {
+            let (start, end) = refers_to.split_once(':').unwrap_or((refers_to, ""));
+            let (sheet, start) = start.rsplit_once('.')?;
+            let end = end.rsplit_once('.').map_or(end, |(_, end)| end);
+            let range = if end.is_empty() {
+                start.to_string()
+            } else {
+                format!("{}:{}", start, end)
+            };
+            (sheet.trim_start_matches('$').to_string(), range)
+        }
+    };
+    // 含空格等字符的工作表名加单引号，内部的单引号写作两个
+    let sheet = if sheet.len() >= 2 && sheet.starts_with('\'') && sheet.ends_with('\'') {
+        sheet[1..sheet.len() - 1].replace("''", "'")
+    } else {
+        sheet
+    };
+    parse_range(&range)?;
+    Some((sheet, range.replace('$', "")))
+}
+
+/// Sheet index and A1 range of a defined name, preferring a workbook-wide name over
+/// names scoped to a sheet.
+pub fn resolve_defined_name(bytes: &[u8], name: &str) -> Result<(usize, String), String> {
+    let info = read_workbook_info(bytes)?;
+    let defined_name = info
+        .defined_names
+        .iter()
+        .filter(|defined_name| defined_name.name == name)
+        .min_by_key(|defined_name| defined_name.sheet.is_some())
+        .ok_or_else(|| {
+            let names: Vec<&str> = info
+                .defined_names
+                .iter()
+                .map(|defined_name| defined_name.name.as_str())
+                .collect();
+            format!(
+                "Failed to get defined name {:?}, available names: {}",
+                name,
+                names.join(", ")
+            )
+        })?;
+    let (sheet, range) = split_reference(&defined_name.refers_to).ok_or_else(|| {
+        format!(
+            "Defined name {:?} doesn't refer to a single range: {}",
+            name, defined_name.refers_to
+        )
+    })?;
+    let sheet_index = info
+        .sheets
+        .iter()
+        .find(|sheet_info| sheet_info.name == sheet)
+        .map(|sheet_info| sheet_info.index)
+        .ok_or_else(|| format!("Failed to get worksheet {:?} of defined name {:?}", sheet, name))?;
+    Ok((sheet_index, range))
+}
+
+fn list_object(table: &XmlNode) -> Option<ListObject> {
+    let count = |name: &str, default: u32| {
+        table
+            .attr(name)
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(default)
+    };
+    Some(ListObject {
+        name: table.attr("displayName").or(table.attr("name"))?.to_string(),
+        range: table.attr("ref")?.replace('$', ""),
+        header_rows: count("headerRowCount", 1),
+        totals_rows: count("totalsRowCount", 0),
+        columns: table
+            .child("tableColumns")
+            .into_iter()
+            .flat_map(|columns| columns.children_named("tableColumn"))
+            .map(|column| column.attr("name").unwrap_or_default().to_string())
+            .collect(),
+        style: table.child("tableStyleInfo").and_then(|info| {
+            let flag = |name: &str| matches!(info.attr(name), Some("1") | Some("true"));
+            Some(ListStyle {
+                name: info.attr("name")?.to_string(),
+                first_column: flag("showFirstColumn"),
+                last_column: flag("showLastColumn"),
+                row_stripes: flag("showRowStripes"),
+                column_stripes: flag("showColumnStripes"),
+            })
+        }),
+    })
+}
+
+/// Sheet index and definition of the table (list object) with the given name, compared
+/// case-insensitively like Excel does.
+pub fn find_list_object(archive: &mut Archive, name: &str) -> Result<(usize, ListObject), String> {
+    let sheet_count = read_archive_sheet_names(archive)?.len();
+    let mut names = Vec::new();
+    for sheet_index in 0..sheet_count {
+        let sheet_path = match archive.sheet_path(sheet_index)? {
+            Some(sheet_path) => sheet_path,
+            None => continue,
+        };
+        let targets: Vec<String> = archive
+            .relationships(&sheet_path)?
+            .into_iter()
+            .filter(|rel| rel.rel_type.ends_with("/table"))
+            .map(|rel| rel.target)
+            .collect();
+        for target in targets {
+            let list = match archive.read_xml(&target)?.as_ref().and_then(list_object) {
+                Some(list) => list,
+                None => continue,
+            };
+            if list.name.eq_ignore_ascii_case(name) {
+                return Ok((sheet_index, list));
+            }
+            names.push(list.name);
+        }
+    }
+    Err(format!(
+        "Failed to get table {:?}, available tables: {}",
+        name,
+        names.join(", ")
+    ))
+}
@@ -1,6 +1,10 @@
-use umya_spreadsheet::Worksheet;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::utils::parse_cell_reference;
+use umya_spreadsheet::{Style, Worksheet};
+
+use crate::data_structures::{HeaderFooter, HeaderFooterText, PageMargins, PageSetup};
+use crate::utils::{is_typst_length, parse_cell_reference, parse_column_key, parse_range};
+use crate::xml_utils::XmlNode;
 
 pub fn get_table_dimensions(worksheet: &Worksheet) -> Result<(u32, u32), String> {
     let mut max_col = 0;
@@ -19,23 +23,430 @@ pub fn get_table_dimensions(worksheet: &Worksheet) -> Result<(u32, u32), String>
     Ok((max_col, max_row))
 }
 
-pub fn get_column_widths(worksheet: &Worksheet, max_col: u32, default_width: f64) -> Vec<f64> {
+/// A `<col>` record of the worksheet, which may cover a span of columns.
+pub struct ColumnSpan {
+    pub min: u32,
+    pub max: u32,
+    pub width: Option<f64>,
+    /// Default style (`cellXfs` index) of the cells in the span.
+    pub style: Option<usize>,
+    pub hidden: bool,
+    pub outline_level: u32,
+}
+
+pub fn get_column_spans(sheet_xml: &XmlNode) -> Vec<ColumnSpan> {
+    sheet_xml
+        .children_named("cols")
+        .flat_map(|cols| cols.children_named("col"))
+        .filter_map(|col| {
+            let min = col.attr("min")?.parse::<u32>().ok()?;
+            let max = col
+                .attr("max")
+                .and_then(|max| max.parse::<u32>().ok())
+                .unwrap_or(min);
+            Some(ColumnSpan {
+                min: min.min(max),
+                max: min.max(max),
+                width: col.attr("width").and_then(|width| width.parse::<f64>().ok()),
+                style: col.attr("style").and_then(|style| style.parse::<usize>().ok()),
+                hidden: matches!(col.attr("hidden"), Some("1") | Some("true")),
+                outline_level: col
+                    .attr("outlineLevel")
+                    .and_then(|level| level.parse::<u32>().ok())
+                    .unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+pub fn get_column_widths(spans: &[ColumnSpan], max_col: u32, default_width: f64) -> Vec<f64> {
     let mut columns = vec![default_width; max_col as usize];
-    for col in worksheet.get_column_dimensions() {
-        let col_idx = *col.get_col_num() as usize - 1;
-        if col_idx < columns.len() {
-            columns[col_idx] = col.get_width().clone();
+    // 按文件中的顺序应用，重叠的记录以后出现的为准
+    for span in spans {
+        let width = match span.width {
+            Some(width) => width,
+            None => continue,
+        };
+        for col_num in span.min.max(1)..=span.max.min(max_col) {
+            columns[(col_num - 1) as usize] = width;
         }
     }
     columns
 }
 
+/// Numbers of the hidden columns up to `max_col`.
+pub fn get_hidden_columns(spans: &[ColumnSpan], max_col: u32) -> Vec<u32> {
+    let mut hidden = vec![false; max_col as usize];
+    // 与列宽一样，重叠的记录以后出现的为准
+    for span in spans {
+        for col_num in span.min.max(1)..=span.max.min(max_col) {
+            hidden[(col_num - 1) as usize] = span.hidden;
+        }
+    }
+    (1..=max_col).filter(|col_num| hidden[(col_num - 1) as usize]).collect()
+}
+
+/// Numbers of the hidden rows in `sheetData` up to `max_row`.
+pub fn get_hidden_rows(sheet_xml: &XmlNode, max_row: u32) -> Vec<u32> {
+    sheet_xml
+        .children_named("sheetData")
+        .flat_map(|data| data.children_named("row"))
+        .filter(|row| matches!(row.attr("hidden"), Some("1") | Some("true")))
+        .filter_map(|row| row.attr("r")?.parse::<u32>().ok())
+        .filter(|row_num| *row_num <= max_row)
+        .collect()
+}
+
+/// Outline level of the grouped columns up to `max_col`, keyed by column number.
+pub fn get_column_outline_levels(spans: &[ColumnSpan], max_col: u32) -> BTreeMap<String, u32> {
+    let mut levels = BTreeMap::new();
+    for span in spans {
+        for col_num in span.min.max(1)..=span.max.min(max_col) {
+            levels.insert(col_num, span.outline_level);
+        }
+    }
+    levels
+        .into_iter()
+        .filter(|(_, level)| *level > 0)
+        .map(|(col_num, level)| (col_num.to_string(), level))
+        .collect()
+}
+
+/// Outline level of the grouped rows in `sheetData` up to `max_row`, keyed by row number.
+pub fn get_row_outline_levels(sheet_xml: &XmlNode, max_row: u32) -> BTreeMap<String, u32> {
+    sheet_xml
+        .children_named("sheetData")
+        .flat_map(|data| data.children_named("row"))
+        .filter_map(|row| {
+            let row_num = row.attr("r")?.parse::<u32>().ok()?;
+            let level = row.attr("outlineLevel")?.parse::<u32>().ok()?;
+            Some((row_num, level))
+        })
+        .filter(|(row_num, level)| *row_num <= max_row && *level > 0)
+        .map(|(row_num, level)| (row_num.to_string(), level))
+        .collect()
+}
+
+/// Hidden rows below the header row of the sheet's autofilter, i.e. the rows filtered out.
+pub fn get_filtered_rows(sheet_xml: &XmlNode, hidden_rows: &[u32]) -> Vec<u32> {
+    let range = sheet_xml
+        .child("autoFilter")
+        .and_then(|filter| filter.attr("ref"))
+        .and_then(parse_range);
+    match range {
+        Some(((_, first_row), (_, last_row))) => hidden_rows
+            .iter()
+            .copied()
+            .filter(|row_num| *row_num > first_row && *row_num <= last_row)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+pub fn get_column_overrides(
+    overrides: &BTreeMap<String, String>,
+    max_col: u32,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut columns = BTreeMap::new();
+    for (key, width) in overrides {
+        let col_num =
+            parse_column_key(key).ok_or_else(|| format!("Invalid column in column_widths: {}", key))?;
+        if !is_typst_length(width) {
+            return Err(format!("Invalid width for column {}: {}", key, width));
+        }
+        // 超出表格范围的列无需处理
+        if col_num <= max_col {
+            columns.insert(col_num.to_string(), width.trim().to_string());
+        }
+    }
+    Ok(columns)
+}
+
+pub fn get_uniform_row_height(row_height: &Option<String>) -> Result<Option<String>, String> {
+    match row_height {
+        Some(height) if is_typst_length(height) => Ok(Some(height.trim().to_string())),
+        Some(height) => Err(format!("Invalid row_height: {}", height)),
+        None => Ok(None),
+    }
+}
+
+/// Style (`cellXfs`) index of every cell in `sheetData`, keyed by (column, row).
+pub fn get_cell_style_indices(sheet_xml: &XmlNode) -> HashMap<(u32, u32), usize> {
+    let mut indices = HashMap::new();
+    let rows = sheet_xml
+        .children_named("sheetData")
+        .flat_map(|data| data.children_named("row"));
+    for cell in rows.flat_map(|row| row.children_named("c")) {
+        if let (Some(reference), Some(xf)) = (cell.attr("r"), cell.attr("s")) {
+            if let Ok(xf) = xf.parse::<usize>() {
+                indices.insert(parse_cell_reference(reference), xf);
+            }
+        }
+    }
+    indices
+}
+
+/// Whether the first sheet view displays gridlines, `showGridLines` being on by default.
+pub fn get_show_gridlines(sheet_xml: &XmlNode) -> bool {
+    let show = sheet_xml
+        .child("sheetViews")
+        .and_then(|views| views.child("sheetView"))
+        .and_then(|view| view.attr("showGridLines"));
+    !matches!(show, Some("0") | Some("false"))
+}
+
+/// Numbers of frozen (columns, rows) of the first sheet view, zero when nothing is frozen.
+pub fn get_frozen_panes(sheet_xml: &XmlNode) -> (u32, u32) {
+    let pane = sheet_xml
+        .child("sheetViews")
+        .and_then(|views| views.child("sheetView"))
+        .and_then(|view| view.child("pane"))
+        .filter(|pane| matches!(pane.attr("state"), Some("frozen") | Some("frozenSplit")));
+    let split = |key: &str| {
+        pane.and_then(|pane| pane.attr(key))
+            .and_then(|split| split.parse::<f64>().ok())
+            .map_or(0, |split| split.max(0.0).round() as u32)
+    };
+    (split("xSplit"), split("ySplit"))
+}
+
+/// Rows followed by a manual page break, up to `max_row`.
+pub fn get_page_breaks(sheet_xml: &XmlNode, max_row: u32) -> Vec<u32> {
+    sheet_xml
+        .children_named("rowBreaks")
+        .flat_map(|breaks| breaks.children_named("brk"))
+        .filter(|brk| matches!(brk.attr("man"), Some("1") | Some("true")))
+        .filter_map(|brk| brk.attr("id")?.parse::<u32>().ok())
+        .filter(|row_num| *row_num > 0 && *row_num < max_row)
+        .collect()
+}
+
+/// Typst paper name of an Excel `paperSize` code.
+fn paper_name(code: u32) -> Option<&'static str> {
+    match code {
+        1 => Some("us-letter"),
+        3 => Some("us-tabloid"),
+        5 => Some("us-legal"),
+        7 => Some("us-executive"),
+        8 => Some("a3"),
+        9 => Some("a4"),
+        11 => Some("a5"),
+        12 => Some("jis-b4"),
+        13 => Some("jis-b5"),
+        _ => None,
+    }
+}
+
+/// Orientation, paper size, scale and margins of the sheet, `None` when it has no page setup.
+pub fn get_page_setup(sheet_xml: &XmlNode) -> Option<PageSetup> {
+    let setup = sheet_xml.child("pageSetup");
+    let margins = sheet_xml.child("pageMargins");
+    if setup.is_none() && margins.is_none() {
+        return None;
+    }
+    let attr = |key: &str| setup.and_then(|setup| setup.attr(key));
+    // 边距以英寸计
+    let margins = margins.map(|margins| {
+        let inches = |key: &str, default: f64| {
+            margins
+                .attr(key)
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(default)
+                * 72.0
+        };
+        PageMargins {
+            left: inches("left", 0.7),
+            right: inches("right", 0.7),
+            top: inches("top", 0.75),
+            bottom: inches("bottom", 0.75),
+            header: inches("header", 0.3),
+            footer: inches("footer", 0.3),
+        }
+    });
+    Some(PageSetup {
+        orientation: attr("orientation")
+            .filter(|orientation| *orientation == "portrait" || *orientation == "landscape")
+            .map(|orientation| orientation.to_string()),
+        // 未设置纸张大小时默认为 Letter
+        paper: attr("paperSize")
+            .map_or(Some(1), |code| code.parse::<u32>().ok())
+            .and_then(paper_name)
+            .map(|paper| paper.to_string()),
+        margins,
+        scale: attr("scale").and_then(|scale| scale.parse::<u32>().ok()),
+    })
+}
+
+/// Split an Excel header or footer (`&LConfidential&CPage &P of &N`) into its left, center and
+/// right sections. Fields become placeholders and formatting codes are dropped.
+fn parse_header_footer(text: &str) -> HeaderFooterText {
+    let mut sections = [String::new(), String::new(), String::new()];
+    // 未指定区域的文字居中
+    let mut section = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '&' {
+            sections[section].push(c);
+            continue;
+        }
+        let Some(code) = chars.next() else {
+            break;
+        };
+        let field = match code {
+            'L' | 'l' => {
+                section = 0;
+                None
+            }
+            'C' | 'c' => {
+                section = 1;
+                None
+            }
+            'R' | 'r' => {
+                section = 2;
+                None
+            }
+            'P' | 'p' => Some("{page}"),
+            'N' | 'n' => Some("{pages}"),
+            'D' | 'd' => Some("{date}"),
+            'T' | 't' => Some("{time}"),
+            'F' | 'f' => Some("{file}"),
+            'A' | 'a' => Some("{sheet}"),
+            '&' => Some("&"),
+            // 字体名与样式："&\"Arial,Bold\""
+            '"' => {
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                }
+                None
+            }
+            // 文字颜色：RRGGBB 或主题色 "tt+nnn"
+            'K' | 'k' => {
+                for _ in 0..6 {
+                    chars.next();
+                }
+                None
+            }
+            // 字号
+            digit if digit.is_ascii_digit() => {
+                while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+                None
+            }
+            // 粗体、斜体、下划线等格式开关与图片
+            _ => None,
+        };
+        if let Some(field) = field {
+            sections[section].push_str(field);
+        }
+    }
+    let [left, center, right] = sections.map(|text| Some(text).filter(|text| !text.is_empty()));
+    HeaderFooterText { left, center, right }
+}
+
+/// Header and footer texts of the sheet, `None` when it has neither.
+pub fn get_header_footer(sheet_xml: &XmlNode) -> Option<HeaderFooter> {
+    let node = sheet_xml.child("headerFooter")?;
+    let flag = |key: &str| matches!(node.attr(key), Some("1") | Some("true"));
+    let text = |name: &str| {
+        node.child(name)
+            .map(|text| parse_header_footer(&text.text_content()))
+            .filter(|text| text.left.is_some() || text.center.is_some() || text.right.is_some())
+    };
+    let header_footer = HeaderFooter {
+        header: text("oddHeader"),
+        footer: text("oddFooter"),
+        first_header: text("firstHeader").filter(|_| flag("differentFirst")),
+        first_footer: text("firstFooter").filter(|_| flag("differentFirst")),
+        even_header: text("evenHeader").filter(|_| flag("differentOddEven")),
+        even_footer: text("evenFooter").filter(|_| flag("differentOddEven")),
+    };
+    Some(header_footer).filter(|header_footer| {
+        header_footer.header.is_some()
+            || header_footer.footer.is_some()
+            || header_footer.first_header.is_some()
+            || header_footer.first_footer.is_some()
+            || header_footer.even_header.is_some()
+            || header_footer.even_footer.is_some()
+    })
+}
+
+/// Cached result (`<v>`) of every formula cell in `sheetData`, keyed by (column, row).
+pub fn get_cached_formula_values(sheet_xml: &XmlNode) -> HashMap<(u32, u32), String> {
+    let mut values = HashMap::new();
+    let rows = sheet_xml
+        .children_named("sheetData")
+        .flat_map(|data| data.children_named("row"));
+    for cell in rows.flat_map(|row| row.children_named("c")) {
+        if cell.child("f").is_none() {
+            continue;
+        }
+        let (Some(reference), Some(value)) = (cell.attr("r"), cell.child("v")) else {
+            continue;
+        };
+        let value = match cell.attr("t") {
+            Some("b") => if value.text == "1" { "TRUE" } else { "FALSE" }.to_string(),
+            // 共享字符串与错误值不作为缓存值使用
+            Some("s") | Some("e") => continue,
+            _ => value.text.clone(),
+        };
+        values.insert(parse_cell_reference(reference), value);
+    }
+    values
+}
+
+/// Default style (`cellXfs` index) of each row with custom formatting.
+pub fn get_row_style_indices(sheet_xml: &XmlNode) -> HashMap<u32, usize> {
+    sheet_xml
+        .children_named("sheetData")
+        .flat_map(|data| data.children_named("row"))
+        .filter(|row| matches!(row.attr("customFormat"), Some("1") | Some("true")))
+        .filter_map(|row| {
+            let row_num = row.attr("r")?.parse::<u32>().ok()?;
+            let xf = row.attr("s")?.parse::<usize>().ok()?;
+            Some((row_num, xf))
+        })
+        .collect()
+}
+
+/// Style index a cell without its own style inherits, from its row first and then its column.
+pub fn get_inherited_style_index(
+    row_styles: &HashMap<u32, usize>,
+    column_spans: &[ColumnSpan],
+    col_num: u32,
+    row_num: u32,
+) -> Option<usize> {
+    row_styles.get(&row_num).copied().or_else(|| {
+        column_spans
+            .iter()
+            .rev()
+            .find(|span| span.min <= col_num && col_num <= span.max && span.style.is_some())
+            .and_then(|span| span.style)
+    })
+}
+
+/// Style a cell without its own style inherits, from its row first and then its column.
+pub fn get_inherited_style<'a>(
+    worksheet: &'a Worksheet,
+    row_styles: &HashMap<u32, usize>,
+    col_num: u32,
+    row_num: u32,
+) -> Option<&'a Style> {
+    if row_styles.contains_key(&row_num) {
+        worksheet.get_row_dimension(&row_num).map(|row| row.get_style())
+    } else {
+        worksheet
+            .get_column_dimension_by_number(&col_num)
+            .map(|column| column.get_style())
+    }
+}
+
 pub fn get_row_heights(worksheet: &Worksheet, max_row: u32, default_height: f64) -> Vec<f64> {
     let mut rows = vec![default_height; max_row as usize];
     for row in worksheet.get_row_dimensions() {
         let row_idx = (*row.get_row_num() as usize) - 1;
         if row_idx < rows.len() {
-            rows[row_idx] = row.get_height().clone();
+            rows[row_idx] = *row.get_height();
         }
     }
     rows
@@ -0,0 +1,129 @@
+use std::io::Cursor;
+
+use umya_spreadsheet::{writer, HorizontalAlignmentValues, Style, VerticalAlignmentValues};
+
+use crate::cell_utils::STACKED_ROTATION;
+use crate::data_structures::{CellStyle, Stroke, TableData};
+
+fn write_border_side(target: &mut umya_spreadsheet::Border, side: bool, stroke: Option<&Stroke>) {
+    if !side {
+        return;
+    }
+    // 没有描述的边框按细实线写回
+    target.set_border_style(stroke.map_or("thin", |stroke| stroke.style.as_str()));
+    if let Some(color) = stroke.and_then(|stroke| stroke.color.as_ref()) {
+        target.get_color_mut().set_argb(format!("FF{}", color));
+    }
+}
+
+fn write_style(style: &mut Style, cell_style: &CellStyle) {
+    if let Some(alignment) = &cell_style.alignment {
+        let target = style.get_alignment_mut();
+        match alignment.horizontal.as_str() {
+            "left" => target.set_horizontal(HorizontalAlignmentValues::Left),
+            "center" => target.set_horizontal(HorizontalAlignmentValues::Center),
+            "right" => target.set_horizontal(HorizontalAlignmentValues::Right),
+            _ => {}
+        }
+        match alignment.vertical.as_str() {
+            "top" => target.set_vertical(VerticalAlignmentValues::Top),
+            "center" => target.set_vertical(VerticalAlignmentValues::Center),
+            "bottom" => target.set_vertical(VerticalAlignmentValues::Bottom),
+            _ => {}
+        }
+        target.set_wrap_text(alignment.wrap);
+        target.set_text_rotation(if alignment.stacked {
+            STACKED_ROTATION
+        } else if alignment.rotation < 0 {
+            (90 - alignment.rotation.max(-90)) as u32
+        } else {
+            alignment.rotation.min(90) as u32
+        });
+    }
+    if let Some(border) = &cell_style.border {
+        let strokes = border.strokes.clone().unwrap_or_default();
+        let borders = style.get_borders_mut();
+        write_border_side(borders.get_left_border_mut(), border.left, strokes.left.as_ref());
+        write_border_side(borders.get_right_border_mut(), border.right, strokes.right.as_ref());
+        write_border_side(borders.get_top_border_mut(), border.top, strokes.top.as_ref());
+        write_border_side(
+            borders.get_bottom_border_mut(),
+            border.bottom,
+            strokes.bottom.as_ref(),
+        );
+        if border.diagonal_up || border.diagonal_down {
+            borders.set_diagonal_up(border.diagonal_up);
+            borders.set_diagonal_down(border.diagonal_down);
+            write_border_side(borders.get_diagonal_border_mut(), true, strokes.diagonal.as_ref());
+        }
+    }
+    if let Some(color) = cell_style.color.as_ref().filter(|color| !color.is_empty()) {
+        style.set_background_color(format!("FF{}", color));
+    }
+    if let Some(font) = &cell_style.font {
+        let target = style.get_font_mut();
+        target.set_bold(font.bold);
+        target.set_italic(font.italic);
+        target.set_size(font.size);
+        target.set_strikethrough(font.strike);
+        if font.underline {
+            target.set_underline(font.underline_style.as_deref().unwrap_or("single"));
+        }
+        if let Some(name) = &font.name {
+            target.set_name(name.clone());
+        }
+        if let Some(color) = font.color.as_ref().filter(|color| !color.is_empty()) {
+            target.get_color_mut().set_argb(format!("FF{}", color));
+        }
+    }
+}
+
+/// Write the header, body and footer rows of a table back into a single-sheet xlsx file.
+pub fn write_xlsx(table_data: &TableData) -> Result<Vec<u8>, String> {
+    if table_data.rows.is_empty() && !table_data.segments.is_empty() {
+        return Err("Failed to write xlsx: tables split into segments are not supported".to_string());
+    }
+    let mut book = umya_spreadsheet::new_file();
+    let worksheet = book
+        .get_sheet_mut(&0)
+        .ok_or_else(|| "Failed to get worksheet".to_string())?;
+
+    for (index, width) in table_data.dimensions.columns.iter().enumerate() {
+        worksheet
+            .get_column_dimension_by_number_mut(&(index as u32 + 1))
+            .set_width(*width);
+    }
+    for (index, height) in table_data.dimensions.rows.iter().enumerate() {
+        worksheet
+            .get_row_dimension_mut(&(index as u32 + 1))
+            .set_height(*height);
+    }
+
+    let rows = table_data
+        .header
+        .iter()
+        .chain(&table_data.rows)
+        .chain(&table_data.footer);
+    for row in rows {
+        for cell in &row.cells {
+            let position = (cell.column, row.row_number);
+            worksheet.get_cell_mut(position).set_value(cell.value.clone());
+            // 使用样式类时，样式保存在 styles 中
+            let cell_style = match &cell.class {
+                Some(class) => table_data.styles.get(class),
+                None => cell.style.as_ref(),
+            };
+            if let Some(cell_style) = cell_style {
+                write_style(worksheet.get_style_mut(position), cell_style);
+            }
+        }
+    }
+    for merged_cell in &table_data.merged_cells {
+        worksheet.add_merge_cells(merged_cell.range.clone());
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    writer::xlsx::write_writer(&book, &mut buffer)
+        .map_err(|e| format!("Failed to write xlsx: {}", e))?;
+    Ok(buffer.into_inner())
+}
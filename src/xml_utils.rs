@@ -0,0 +1,130 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// A minimal XML element tree, used for parts of the xlsx file that umya-spreadsheet doesn't expose.
+pub struct XmlNode {
+    /// Element name without namespace prefix.
+    pub name: String,
+    /// Attributes with their original (prefixed) names, e.g. `r:id`.
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+    /// Text before the first child element.
+    pub text: String,
+    /// Text following this element inside its parent, up to the next sibling.
+    pub tail: String,
+}
+
+impl XmlNode {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlNode> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// Concatenated text of this node and all of its descendants.
+    pub fn text_content(&self) -> String {
+        let mut text = self.text.clone();
+        for child in &self.children {
+            text.push_str(&child.text_content());
+            text.push_str(&child.tail);
+        }
+        text
+    }
+}
+
+fn new_node(element: &BytesStart) -> Result<XmlNode, String> {
+    let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+    let mut attributes = Vec::new();
+    for attr in element.attributes() {
+        let attr = attr.map_err(|e| format!("Failed to parse XML attribute: {}", e))?;
+        let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+        let value = attr
+            .unescape_value()
+            .map_err(|e| format!("Failed to parse XML attribute: {}", e))?
+            .to_string();
+        attributes.push((key, value));
+    }
+    Ok(XmlNode {
+        name,
+        attributes,
+        children: Vec::new(),
+        text: String::new(),
+        tail: String::new(),
+    })
+}
+
+/// Append text read inside `node`, after its last child when it has one, so mixed content
+/// keeps its order.
+fn push_text(node: &mut XmlNode, text: &str) {
+    match node.children.last_mut() {
+        Some(child) => child.tail.push_str(text),
+        None => node.text.push_str(text),
+    }
+}
+
+/// Parse an XML document and return its root element.
+pub fn parse_xml(xml: &str) -> Result<XmlNode, String> {
+    let mut reader = Reader::from_str(xml);
+    // 栈底是文档本身，根元素解析完成后会成为它的子节点
+    let mut stack = vec![XmlNode {
+        name: String::new(),
+        attributes: Vec::new(),
+        children: Vec::new(),
+        text: String::new(),
+        tail: String::new(),
+    }];
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => stack.push(new_node(&e)?),
+            Ok(Event::Empty(e)) => {
+                let node = new_node(&e)?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node);
+                }
+            }
+            Ok(Event::End(_)) => {
+                let node = stack
+                    .pop()
+                    .ok_or_else(|| "Failed to parse XML: unexpected end tag".to_string())?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Err("Failed to parse XML: unexpected end tag".to_string()),
+                }
+            }
+            Ok(Event::Text(e)) => {
+                let text = e
+                    .unescape()
+                    .map_err(|e| format!("Failed to parse XML text: {}", e))?;
+                if let Some(node) = stack.last_mut() {
+                    push_text(node, &text);
+                }
+            }
+            Ok(Event::CData(e)) => {
+                if let Some(node) = stack.last_mut() {
+                    push_text(node, &String::from_utf8_lossy(&e));
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Failed to parse XML: {}", e)),
+            _ => {}
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err("Failed to parse XML: unclosed element".to_string());
+    }
+    stack
+        .pop()
+        .and_then(|document| document.children.into_iter().next())
+        .ok_or_else(|| "Failed to parse XML: empty document".to_string())
+}
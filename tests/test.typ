@@ -28,6 +28,10 @@
 
 #xlsx-parser(read("/tests/data/cell/merged.xlsx", encoding: none))
 
+=== rotate
+
+#xlsx-parser(read("/tests/data/cell/rotate.xlsx", encoding: none))
+
 == font
 
 === bold
@@ -88,10 +92,6 @@
 
 #xlsx-parser(read("/tests/data/not_supported/lowercase.xlsx", encoding: none))
 
-=== rotate
-
-#xlsx-parser(read("/tests/data/not_supported/rotate.xlsx", encoding: none))
-
 === uppercase
 
 #xlsx-parser(read("/tests/data/not_supported/uppercase.xlsx", encoding: none))
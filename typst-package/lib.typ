@@ -1 +1 @@
-#import "mod.typ": xlsx-parser, spreet-parser
\ No newline at end of file
+#import "mod.typ": xlsx-parser, xlsx-sheets-parser, xlsx-records, spreet-parser, sheet-names, workbook-info, theme-colors, page-setup, icon-symbol, xlsx-table-data, xlsx-writer, csv-parser, to-datetime
\ No newline at end of file
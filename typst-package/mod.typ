@@ -1,11 +1,205 @@
 #let p = plugin("rexllent.wasm")
 
+// 辅助函数：按 output_format 解码插件返回的数据
+#let decode_output(data, format) = {
+  if format == "cbor" {
+    if sys.version < version(0, 13, 0) { cbor.decode(data) } else { cbor(data) }
+  } else if format == "json" {
+    if sys.version < version(0, 13, 0) { json.decode(data) } else { json(data) }
+  } else {
+    if sys.version < version(0, 13, 0) { toml.decode(data) } else { toml(data) }
+  }
+}
+
+/// Turn a failed conversion (the `error` returned with `structured_errors`) into content with `on-error`,
+/// or else panic with a readable message.
+#let report_error(error, on-error) = {
+  if on-error != none {
+    return on-error(error)
+  }
+  let sheet = error.at("sheet", default: none)
+  let cell = error.at("cell", default: none)
+  let location = ()
+  if sheet != none {
+    location.push("sheet \"" + sheet + "\"")
+  }
+  if cell != none {
+    location.push("cell " + cell)
+  }
+  let location = if location.len() > 0 { " (" + location.join(", ") + ")" } else { "" }
+  panic(error.message + location + " [" + error.code + "]")
+}
+
+// 辅助函数：将边框描述转换为 stroke
+// Typst 的 stroke 没有双线样式，双线画成两条线加间隙宽的线，用硬分界的渐变把中间的间隙画成透明
+#let stroke_from_descriptor(descriptor, side: "top") = {
+  let color = descriptor.at("color", default: none)
+  let paint = if color != none { rgb(color) } else { black }
+  if descriptor.style == "double" {
+    let thickness = descriptor.thickness * 2 + descriptor.gap
+    let inner = descriptor.thickness / thickness * 100%
+    let outer = (descriptor.thickness + descriptor.gap) / thickness * 100%
+    let clear = paint.transparentize(100%)
+    // 渐变沿线宽方向：横线自上而下，竖线自左而右；位置相同的色标在 PNG 输出中会错乱，间隙两端留 1% 过渡
+    return (
+      thickness: thickness * 1pt,
+      paint: gradient.linear(
+        (paint, 0%), (paint, inner), (clear, inner + 1%), (clear, outer - 1%), (paint, outer), (paint, 100%),
+        angle: if side in ("left", "right") { 0deg } else { 90deg },
+        relative: "self",
+      ),
+    )
+  }
+  let args = (thickness: descriptor.thickness * 1pt)
+  let dash = descriptor.at("dash", default: none)
+  if dash != none {
+    args.insert("dash", dash.map(d => d * 1pt))
+  }
+  if color != none {
+    args.insert("paint", paint)
+  }
+  args
+}
+
+// 辅助函数：单元格内的换行（Alt+Enter）输出为强制换行
+#let lines_content(value) = {
+  if type(value) != str or not value.contains("\n") { return value }
+  value.split("\n").join(linebreak())
+}
+
+// 辅助函数：富文本单元格的各段文字分别设置样式，未设置的属性沿用单元格字体
+#let runs_content(runs) = {
+  runs
+    .map(run => {
+      let text_args = (:)
+      let bold = run.at("bold", default: none)
+      if bold != none { text_args.insert("weight", if bold { "bold" } else { "regular" }) }
+      let italic = run.at("italic", default: none)
+      if italic != none { text_args.insert("style", if italic { "italic" } else { "normal" }) }
+      let size = run.at("size", default: none)
+      if size != none { text_args.insert("size", eval(str(size) + "pt")) }
+      let color = run.at("color", default: none)
+      if color != none { text_args.insert("fill", rgb(color)) }
+      let content = text(..text_args, lines_content(run.text))
+      if run.at("underline", default: false) == true { content = underline(content) }
+      if run.at("strike", default: false) == true { content = strike(content) }
+      content
+    })
+    .join()
+}
+
+// 辅助函数：按下划线类型绘制，会计用下划线位置更低
+#let underline_content(content, kind) = {
+  let offset = if kind != none and kind.ends-with("Accounting") { 3pt } else { auto }
+  if kind != none and kind.starts-with("double") {
+    let base = if offset == auto { 1.5pt } else { offset }
+    underline(offset: base, underline(offset: base + 1.5pt, content))
+  } else {
+    underline(offset: offset, content)
+  }
+}
+
+// 数据条画在单元格内容之下，长度按单元格宽度的比例
+#let data_bar_content(cell, content) = {
+  let bar = cell.at("data_bar", default: none)
+  if bar == none { return content }
+  block(width: 100%, {
+    place(left + horizon, rect(width: bar.fraction * 100%, height: 1em, fill: rgb(bar.color)))
+    if bar.show_value { content } else { hide(content) }
+  })
+}
+
+/// Typst content for the `icon` of a cell exported from an icon set rule, drawn with symbols
+/// in the colors of the Excel icon set.
+///
+/// - icon (dictionary): The `icon` of a cell, with `set`, `index` and `show_value`.
+/// -> content
+#let icon-symbol(icon) = {
+  let icon_set = icon.at("set")
+  let count = int(icon_set.first())
+  let index = calc.min(icon.index, count - 1)
+  // 颜色从红（最小值）经黄到绿
+  let color = gradient.linear(rgb("#E06666"), rgb("#F1C232"), rgb("#6AA84F")).sample(index / (count - 1) * 100%)
+  if icon_set.contains("Arrows") {
+    let arrows = (
+      (sym.arrow.b, sym.arrow.r, sym.arrow.t),
+      (sym.arrow.b, sym.arrow.br, sym.arrow.tr, sym.arrow.t),
+      (sym.arrow.b, sym.arrow.br, sym.arrow.r, sym.arrow.tr, sym.arrow.t),
+    ).at(count - 3)
+    text(fill: if icon_set.contains("Gray") { gray } else { color }, arrows.at(index))
+  } else if icon_set.contains("Rating") or icon_set.contains("Boxes") {
+    // 信号格：index 为 0 时不点亮
+    box(stack(dir: ltr, spacing: 1pt, ..range(count - 1).map(i => align(bottom, rect(
+      width: 0.2em,
+      height: (i + 1) / (count - 1) * 0.8em,
+      fill: if i < index { rgb("#3D85C6") } else { luma(220) },
+    )))))
+  } else if icon_set.contains("Quarters") {
+    ("○", "◔", "◑", "◕", "●").at(index)
+  } else if icon_set.contains("RedToBlack") {
+    box(circle(radius: 0.4em, fill: (black, gray, rgb("#EA9999"), rgb("#CC0000")).at(index)))
+  } else if icon_set.contains("Stars") {
+    text(fill: rgb("#F1C232"), (sym.star.stroked, sym.star.op, sym.star.filled).at(index))
+  } else if icon_set.contains("Triangles") {
+    text(fill: color, ("▼", "▬", "▲").at(index))
+  } else if icon_set.contains("Symbols") {
+    text(fill: color, ("✖", "!", "✔").at(index))
+  } else if icon_set.contains("Flags") {
+    text(fill: color, "⚑")
+  } else if icon_set.contains("Signs") {
+    text(fill: color, ("◆", "▲", "●").at(index))
+  } else {
+    // 红绿灯，4TrafficLights 的最小值为黑色
+    let fill = if count == 4 { (black, rgb("#E06666"), rgb("#F1C232"), rgb("#6AA84F")).at(index) } else { color }
+    box(circle(radius: 0.4em, fill: fill))
+  }
+}
+
+// 图标集的图标显示在单元格内容左侧
+#let icon_content(cell, content) = {
+  let icon = cell.at("icon", default: none)
+  if icon == none { return content }
+  box(grid(columns: 2, column-gutter: 0.3em, align: horizon, icon-symbol(icon), if icon.show_value { content }))
+}
+
+// 图片从所在单元格的左上角起，按偏移与尺寸浮在表格之上，Typst 无法显示的格式（如 emf）略过
+#let images_content(cell, content) = {
+  let images = cell.at("images", default: none)
+  if images == none { return content }
+  for picture in images.filter(picture => picture.format in ("png", "jpeg", "gif", "svg", "webp")) {
+    // CBOR 输出为字节串，TOML 与 JSON 输出为字节数组
+    let data = if type(picture.data) == bytes { picture.data } else { bytes(picture.data) }
+    let args = (width: picture.width * 1pt, height: picture.height * 1pt, alt: picture.description)
+    let img = if sys.version < version(0, 13, 0) { image.decode(data, ..args) } else { image(data, ..args) }
+    place(top + left, dx: picture.x * 1pt, dy: picture.y * 1pt, img)
+  }
+  content
+}
+
 // 辅助函数：创建单元格内容
-#let create_cell_content(cell) = {
-  if not cell.keys().contains("style") or cell.style == none { return ({ }, cell.value) }
+#let create_cell_content(cell, respect-wrap: false, use-fonts: false) = {
+  let runs = cell.at("runs", default: none)
+  let value = if runs != none { runs_content(runs) } else { lines_content(cell.value) }
+  // 读音（振り仮名）以小字注在文字上方
+  let phonetic = cell.at("phonetic", default: none)
+  if phonetic != none {
+    value = box(grid(align: center, row-gutter: 0.15em, text(size: 0.5em, phonetic), value))
+  }
+  if not cell.keys().contains("style") or cell.style == none { return ({ }, images_content(cell, data_bar_content(cell, icon_content(cell, value)))) }
 
-  let content = cell.value
+  let content = value
   let style = cell.style
+  let alignment = style.at("alignment", default: none)
+
+  // 竖排文字逐字换行
+  if alignment != none and alignment.at("stacked", default: false) and runs == none {
+    content = lines_content(str(cell.value).clusters().filter(c => c != " ").join("\n"))
+  }
+  // 东亚竖排：字符直立自上而下，各行从右向左排列
+  if alignment != none and alignment.at("east_asian_vertical", default: false) and runs == none {
+    let columns = str(cell.value).split("\n").map(line => stack(dir: ttb, ..line.clusters()))
+    content = stack(dir: rtl, spacing: 0.3em, ..columns)
+  }
 
   // 处理字体样式
   if style.keys().contains("font") and style.font != none {
@@ -16,10 +210,21 @@
     if font.italic { text_args.insert("style", "italic") }
     if font.size != none { text_args.insert("size", eval(str(font.size) + "pt")) }
     if font.color != none { text_args.insert("fill", rgb(font.color)) }
+    // 西文字体在前，东亚字体补足西文字体缺少的字形
+    if use-fonts {
+      let families = (font.at("name", default: none), font.at("east_asian_name", default: none))
+        .filter(name => name != none)
+      if families.len() > 0 { text_args.insert("font", families) }
+    }
+    if font.at("condense", default: false) { text_args.insert("stretch", 75%) }
+    if font.at("extend", default: false) { text_args.insert("stretch", 125%) }
 
     content = text(..text_args)[#content]
 
-    if font.underline { content = underline[#content] }
+    let vertical_align = font.at("vertical_align", default: none)
+    if vertical_align == "superscript" { content = super(content) }
+    if vertical_align == "subscript" { content = sub(content) }
+    if font.underline { content = underline_content(content, font.at("underline_style", default: none)) }
     if font.strike { content = strike[#content] }
   }
 
@@ -44,15 +249,39 @@
     if align.len() > 0 {
       cell_args.insert("align", eval(align.join("+")))
     }
+
+    // 未设置自动换行的单元格保持在一行内，只在显式换行处断行
+    if respect-wrap and not style.alignment.at("wrap", default: false) {
+      content = box(content)
+    }
+
+    // 缩进每级约三个字符宽，右对齐时从右侧缩进
+    let indent = style.alignment.at("indent", default: 0)
+    if indent > 0 {
+      let side = if style.alignment.horizontal == "right" { "right" } else { "left" }
+      content = pad(..((side): indent * 1.5em), content)
+    }
+
+    // Excel 的角度为逆时针方向，Typst 为顺时针方向
+    let rotation = style.alignment.at("rotation", default: 0)
+    if rotation != 0 {
+      content = rotate(-rotation * 1deg, reflow: true, content)
+    }
   }
 
   // 处理边框
   if style.keys().contains("border") and style.border != none {
     let borders = style.border
     let stroke_args = (:)
-    for (border, value) in style.border {
-      if value == false {
-        stroke_args.insert(border, none)
+    let strokes = borders.at("strokes", default: none)
+    // 对角线不属于单元格的边框，只处理四条边
+    for side in ("left", "right", "top", "bottom") {
+      if borders.at(side) == false {
+        stroke_args.insert(side, none)
+      }
+      let descriptor = if strokes != none { strokes.at(side, default: none) }
+      if descriptor != none {
+        stroke_args.insert(side, stroke_from_descriptor(descriptor, side: side))
       }
     }
     cell_args.insert("stroke", stroke_args)
@@ -68,7 +297,60 @@
       cell_args.insert("fill", rgb(fill))
     }
   }
-  return (cell_args, content)
+  // 渐变填充，颜色少于两个时沿用平均色
+  let gradient_fill = style.at("gradient", default: none)
+  if gradient_fill != none and gradient_fill.stops.len() >= 2 {
+    let stops = gradient_fill.stops.map(stop => (rgb(stop.color), stop.position * 100%))
+    let fill = if gradient_fill.kind == "path" {
+      gradient.radial(..stops)
+    } else {
+      gradient.linear(..stops, angle: gradient_fill.angle * 1deg)
+    }
+    cell_args.insert("fill", fill)
+  }
+  return (cell_args, images_content(cell, data_bar_content(cell, icon_content(cell, content))))
+}
+
+// 辅助函数：根据样式类名取回共享的样式
+#let with_class_style(data, cell) = {
+  let class = cell.at("class", default: none)
+  if class == none { return cell }
+  cell.insert("style", data.styles.at(class))
+  cell
+}
+
+// 辅助函数：为没有边框的单元格补上整行/整列的边框
+#let with_collapsed_border(data, row_number, cell) = {
+  let border = data.at("row_borders", default: (:)).at(str(row_number), default: none)
+  if border == none {
+    border = data.at("column_borders", default: (:)).at(str(cell.column), default: none)
+  }
+  if border == none { return cell }
+  let style = cell.at("style", default: none)
+  if style == none { style = (:) }
+  if style.at("border", default: none) != none { return cell }
+  style.insert("border", border)
+  cell.insert("style", style)
+  cell
+}
+
+// 辅助函数：为没有填充的单元格补上条纹填充
+#let band_fill(data, row_number) = {
+  let banding = data.at("banding", default: none)
+  if banding == none or row_number < banding.start_row { return none }
+  let color = banding.colors.at(calc.rem(row_number - banding.start_row, banding.period))
+  if color == "" { none } else { color }
+}
+
+#let with_band_fill(data, row_number, cell) = {
+  let fill = band_fill(data, row_number)
+  if fill == none { return cell }
+  let style = cell.at("style", default: none)
+  if style == none { style = (:) }
+  if style.at("color", default: none) != none { return cell }
+  style.insert("color", fill)
+  cell.insert("style", style)
+  cell
 }
 
 #let parse_excel_table(
@@ -77,18 +359,103 @@
   parse-header: false,
   parse-table-style: true,
   parse-stroke: true,
+  class-args: (:),
+  respect-wrap: false,
+  use-fonts: false,
   ..args,
 ) = {
+  // 过宽或过长的表格被拆分为多个子表格，依次排列；样式类和摘要只在顶层保留一份
+  let segments = data.at("segments", default: ())
+  if segments.len() > 0 {
+    let shared = (
+      styles: data.at("styles", default: (:)),
+      metadata: data.at("metadata", default: none),
+    )
+    let tables = segments.map(segment => parse_excel_table(
+      segment + shared,
+      prepend-elems: prepend-elems,
+      parse-header: parse-header,
+      parse-table-style: parse-table-style,
+      parse-stroke: parse-stroke,
+      class-args: class-args,
+      respect-wrap: respect-wrap,
+      use-fonts: use-fonts,
+      ..args,
+    ))
+    // 在手动分页符处拆分的子表格各自另起一页
+    let page_breaks = data.at("page_breaks", default: ())
+    let starts_page = segment => {
+      let rows = segment.at("rows", default: ())
+      rows.len() > 0 and page_breaks.contains(rows.first().row_number - 1)
+    }
+    if not segments.slice(1).any(starts_page) {
+      return stack(spacing: 1em, ..tables)
+    }
+    return for (i, table) in tables.enumerate() {
+      if i > 0 {
+        if starts_page(segments.at(i)) { pagebreak(weak: true) } else { v(1em) }
+      }
+      table
+    }
+  }
+
   // 解析维度信息
   let dims = data.dimensions
 
+  // values_only 模式只有纯文本网格和合并单元格
+  let values = data.at("values", default: none)
+  if values != none {
+    let spans = (:)
+    let covered = (:)
+    for mc in data.merged_cells {
+      spans.insert(str(mc.start.row) + "," + str(mc.start.column), (
+        rowspan: mc.end.row - mc.start.row + 1,
+        colspan: mc.end.column - mc.start.column + 1,
+      ))
+      for r in range(mc.start.row, mc.end.row + 1) {
+        for c in range(mc.start.column, mc.end.column + 1) {
+          if r != mc.start.row or c != mc.start.column { covered.insert(str(r) + "," + str(c), true) }
+        }
+      }
+    }
+    let rows = values.row_numbers.zip(values.cells).map(((row-number, row)) => row
+      .enumerate()
+      .filter(((i, _)) => str(row-number) + "," + str(i + 1) not in covered)
+      .map(((i, value)) => table.cell(..spans.at(str(row-number) + "," + str(i + 1), default: (:)), lines_content(value))))
+    let header-count = if parse-header { calc.max(values.header_rows, 1) } else { values.header_rows }
+    let footer-count = values.footer_rows
+    let body = rows.slice(header-count, rows.len() - footer-count).flatten()
+    if type(prepend-elems) != array {
+      prepend-elems = (prepend-elems,)
+    }
+    return table(
+      columns: dims.max_columns,
+      ..prepend-elems,
+      ..if header-count > 0 { (table.header(..rows.slice(0, header-count).flatten()),) },
+      ..body,
+      ..if footer-count > 0 { (table.footer(..rows.slice(rows.len() - footer-count).flatten()),) },
+      ..args,
+    )
+  }
+
   // 创建表格参数
   let table_args = (:)
 
   // 设置列宽和行高
   if dims.columns != none and dims.rows != none {
-    let columns = dims.columns.map(c => if c != 0.0 { eval(str(c * 0.1) + "in") } else { auto })
-    let rows = dims.rows.map(r => if r != 0.0 { eval(str(r) + "pt") } else { auto })
+    let overrides = dims.at("column_overrides", default: (:))
+    let columns = dims
+      .columns
+      .enumerate()
+      .map(((i, c)) => {
+        let width = overrides.at(str(i + 1), default: none)
+        if width != none { eval(width) } else if c != 0.0 { eval(str(c * 0.1) + "in") } else { auto }
+      })
+    let rows = if dims.at("uniform_row_height", default: none) != none {
+      eval(dims.uniform_row_height)
+    } else {
+      dims.rows.map(r => if r != 0.0 { eval(str(r) + "pt") } else { auto })
+    }
     if parse-table-style {
       table_args.insert("columns", columns)
     } else {
@@ -100,6 +467,15 @@
       table_args.insert("rows", dims.max_rows)
     }
   }
+  // 表格摘要用于无障碍 PDF 的标签，需要 typst 0.14 及以上版本
+  let metadata = data.at("metadata", default: none)
+  if metadata != none and sys.version >= version(0, 14, 0) {
+    let summary = metadata.at("description", default: none)
+    if summary == none { summary = metadata.at("title", default: none) }
+    if summary != none and "summary" not in args.named() {
+      table_args.insert("summary", summary)
+    }
+  }
   // 创建合并单元格映射
   let merged = (:)
   for mc in data.merged_cells {
@@ -122,7 +498,14 @@
   // 处理每一行
   let cells = ()
   let header_cells = ()
-  for row in data.rows {
+  let footer_cells = ()
+  let header_rows = data.at("header", default: ())
+  let header_numbers = header_rows.map(r => r.row_number)
+  let footer_rows = data.at("footer", default: ())
+  let footer_numbers = footer_rows.map(r => r.row_number)
+  for row in header_rows + data.rows + footer_rows {
+    let is_header = (row.row_number == 1 and parse-header) or header_numbers.contains(row.row_number)
+    let is_footer = footer_numbers.contains(row.row_number)
     // 创建单元格映射，方便快速查找
     let cell_map = (:)
     for cell in row.cells {
@@ -140,6 +523,9 @@
           // 是合并单元格的起始点，创建带合并属性的单元格
           let cell = cell_map.at(str(col), default: none)
           if cell == none { continue }
+          cell = with_class_style(data, cell)
+          cell = with_collapsed_border(data, row.row_number, cell)
+          cell = with_band_fill(data, row.row_number, cell)
 
           let cell_args = (
             rowspan: merge_info.rowspan,
@@ -147,10 +533,14 @@
           )
 
           // 处理样式和内容
-          let (_cell_args, content) = create_cell_content(cell)
+          let (_cell_args, content) = create_cell_content(cell, respect-wrap: respect-wrap, use-fonts: use-fonts)
           cell_args += _cell_args
-          if row.row_number == 1 and parse-header {
+          let class = cell.at("class", default: none)
+          if class != none { cell_args += class-args.at(class, default: (:)) }
+          if is_header {
             header_cells.push(table.cell(..cell_args)[#content])
+          } else if is_footer {
+            footer_cells.push(table.cell(..cell_args)[#content])
           } else {
             cells.push(table.cell(..cell_args)[#content])
           }
@@ -162,83 +552,492 @@
       // 处理普通单元格
       let cell = cell_map.at(str(col), default: none)
       if cell != none {
-        let (_cell_args, content) = create_cell_content(cell)
-        if row.row_number == 1 and parse-header {
+        cell = with_class_style(data, cell)
+        cell = with_collapsed_border(data, row.row_number, cell)
+        cell = with_band_fill(data, row.row_number, cell)
+        let (_cell_args, content) = create_cell_content(cell, respect-wrap: respect-wrap, use-fonts: use-fonts)
+        let class = cell.at("class", default: none)
+        if class != none { _cell_args += class-args.at(class, default: (:)) }
+        if is_header {
           header_cells.push(table.cell(.._cell_args)[#content])
+        } else if is_footer {
+          footer_cells.push(table.cell(.._cell_args)[#content])
         } else {
           cells.push(table.cell(.._cell_args)[#content])
         }
       } else {
         // 空单元格
-        if parse-stroke {
-          if row.row_number == 1 and parse-header {
-            header_cells.push(table.cell(stroke: none)[#none])
-          } else { cells.push(table.cell(stroke: none)[#none]) }
-        } else {
-          if row.row_number == 1 and parse-header {
-            header_cells.push([])
-          } else { cells.push([]) }
-        }
+        // 显示网格线的工作表中，空单元格也画出网格线
+        let gridline = data.at("gridline", default: none)
+        let empty_args = if not parse-stroke { (:) } else if gridline != none {
+          (stroke: stroke_from_descriptor(gridline))
+        } else { (stroke: none) }
+        let fill = band_fill(data, row.row_number)
+        if fill != none { empty_args.insert("fill", rgb(fill)) }
+        if is_header {
+          header_cells.push(table.cell(..empty_args)[#none])
+        } else if is_footer {
+          footer_cells.push(table.cell(..empty_args)[#none])
+        } else { cells.push(table.cell(..empty_args)[#none]) }
       }
     }
   }
   if type(prepend-elems) != array {
     prepend-elems = (prepend-elems,)
   }
-  if parse-header {
+  // 处理外框
+  let frame = data.at("frame", default: none)
+  let frame_stroke(side) = {
+    let strokes = frame.at("strokes", default: none)
+    let descriptor = if strokes != none { strokes.at(side, default: none) }
+    if descriptor != none { stroke_from_descriptor(descriptor, side: side) } else { 1pt + black }
+  }
+  if frame != none {
+    if frame.top { prepend-elems = (table.hline(y: 0, stroke: frame_stroke("top")),) + prepend-elems }
+    if frame.left { prepend-elems = (table.vline(x: 0, stroke: frame_stroke("left")),) + prepend-elems }
+    if frame.right {
+      prepend-elems = (table.vline(x: dims.max_columns, stroke: frame_stroke("right")),) + prepend-elems
+    }
+  }
+  if footer_cells.len() > 0 {
+    cells.push(table.footer(..footer_cells))
+  }
+  if frame != none and frame.bottom {
+    cells.push(table.hline(stroke: frame_stroke("bottom")))
+  }
+  if parse-header or header_cells.len() > 0 {
     table(..table_args, ..prepend-elems, table.header(..header_cells), ..cells, ..args)
   } else {
     table(..table_args, ..prepend-elems, ..cells, ..args)
   }
 }
 
+// 预设的解析选项，显式传入的参数优先
+#let presets = (
+  plain: (
+    parse-table-style: false,
+    parse-alignment: false,
+    parse-stroke: false,
+    parse-fill: false,
+    parse-font: false,
+    options: (:),
+  ),
+  data: (
+    parse-table-style: false,
+    parse-alignment: true,
+    parse-stroke: false,
+    parse-fill: false,
+    parse-font: false,
+    options: (:),
+  ),
+  styled: (
+    parse-table-style: true,
+    parse-alignment: true,
+    parse-stroke: true,
+    parse-fill: true,
+    parse-font: true,
+    options: (:),
+  ),
+  full: (
+    parse-table-style: true,
+    parse-alignment: true,
+    parse-stroke: true,
+    parse-fill: true,
+    parse-font: true,
+    options: (
+      conditional_formats: true,
+      resolve_border_conflicts: true,
+      detect_frame: true,
+      collapse_borders: true,
+    ),
+  ),
+)
+
 /// Parse the xlsx file content and return the table.
 ///
 /// - xlsx (bytes): Pass the xlsx file content by `read("path/to/file.xlsx", encoding: none)`.
 /// - prepend-elems (array): Arguments to be prepended to the table.
-/// - sheet-index (integer): The index of the sheet to be parsed.
-/// - parse-table-style (boolean): Whether to parse the table style(like column width and row height).
-/// - parse-alignment (boolean): Whether to parse the cell alignment.
-/// - parse-stroke (boolean): Whether to parse the cell border.
-/// - parse-fill (boolean): Whether to parse the cell fill color.
-/// - parse-font (boolean): Whether to parse the cell font style.
+/// - sheet-index (integer, str): The index or the name of the sheet to be parsed.
+/// - defined-name (str, none): A defined name (named range) to parse instead of a whole sheet, overriding `sheet-index`.
+/// - table-name (str, none): A table (list object) to parse instead of a whole sheet, overriding `sheet-index`. Its header and totals rows go into `table.header` and `table.footer` unless `header-rows` or `footer-rows` are given.
+/// - preset (string): Bundle of parse options: `"plain"`, `"data"`, `"styled"` or `"full"`.
+/// - parse-table-style (auto, boolean): Whether to parse the table style(like column width and row height). `auto` follows the preset.
+/// - parse-alignment (auto, boolean): Whether to parse the cell alignment. `auto` follows the preset.
+/// - parse-stroke (auto, boolean): Whether to parse the cell border. `auto` follows the preset.
+/// - parse-fill (auto, boolean): Whether to parse the cell fill color. `auto` follows the preset.
+/// - parse-font (auto, boolean): Whether to parse the cell font style. `auto` follows the preset.
 /// - parse-header (boolean): Whether to parse the header row.
+/// - header-rows (integer, none): Number of leading rows to put into `table.header`, repeated across pages.
+/// - footer-rows (integer, none): Number of trailing rows to put into `table.footer`, repeated across pages.
+/// - column-widths (dictionary): Widths overriding the parsed ones, keyed by column letter or 1-based index, e.g. `(B: 3cm, D: auto)`.
+/// - row-height (auto, length, none): A single height for all rows instead of the parsed ones.
+/// - class-args (dictionary): Cell arguments (like `fill` or `stroke`) overriding the cells of a style class, keyed by class name. Requires the `style_classes` option.
+/// - on-error (function, none): Called with the error (`code`, `message`, `sheet`, `cell`) when the conversion fails, returning content shown instead of the table. By default the compilation fails with a readable message.
+/// - respect-wrap (boolean): Keep cells without "wrap text" on one line, breaking only at in-cell line breaks. Requires `parse-alignment`.
+/// - use-fonts (boolean): Set the font family of each cell (and its East Asian font) as in the workbook. Requires `parse-font`; fonts missing from the system fall back with a warning.
+/// - options (dictionary): Extra options passed to the plugin.
 /// - apprend-args (arguments): Other arguments for the table.
 /// -> table
 #let xlsx-parser(
   xlsx,
   prepend-elems: (),
   sheet-index: 0,
-  parse-table-style: true,
-  parse-alignment: true,
-  parse-stroke: true,
-  parse-fill: true,
-  parse-font: true,
+  defined-name: none,
+  table-name: none,
+  preset: "styled",
+  parse-table-style: auto,
+  parse-alignment: auto,
+  parse-stroke: auto,
+  parse-fill: auto,
+  parse-font: auto,
   parse-header: false,
+  header-rows: none,
+  footer-rows: none,
+  column-widths: (:),
+  row-height: none,
+  class-args: (:),
+  on-error: none,
+  respect-wrap: false,
+  use-fonts: false,
+  options: (:),
   ..append-args,
 ) = {
-  let data = p.to_typst(
-    xlsx,
-    bytes(str(sheet-index)),
-    bytes(if parse-alignment { "true" } else { "false" }),
-    bytes(if parse-stroke { "true" } else { "false" }),
-    bytes(if parse-fill { "true" } else { "false" }),
-    bytes(if parse-font { "true" } else { "false" }),
+  assert(preset in presets, message: "Unknown preset: " + repr(preset))
+  let preset = presets.at(preset)
+  let resolve(value, key) = if value == auto { preset.at(key) } else { value }
+  let parse-table-style = resolve(parse-table-style, "parse-table-style")
+  let parse-alignment = resolve(parse-alignment, "parse-alignment")
+  let parse-stroke = resolve(parse-stroke, "parse-stroke")
+  let parse-fill = resolve(parse-fill, "parse-fill")
+  let parse-font = resolve(parse-font, "parse-font")
+  let options = preset.options + options
+  if header-rows != none {
+    options.insert("header_rows", header-rows)
+  }
+  if footer-rows != none {
+    options.insert("footer_rows", footer-rows)
+  }
+  if column-widths.len() > 0 {
+    options.insert("column_widths", column-widths.pairs().map(((k, v)) => (k, if v == auto { "auto" } else { repr(v) })).to-dict())
+  }
+  if row-height != none {
+    options.insert("row_height", if row-height == auto { "auto" } else { repr(row-height) })
+  }
+  // 渲染时总要得到表格，不跳过未改变的转换
+  let _ = options.remove("previous_hash", default: none)
+  // CBOR 比 TOML 解码更快
+  let options = (output_format: "cbor", structured_errors: true) + options
+  let flags = (
+    parse_alignment: parse-alignment,
+    parse_border: parse-stroke,
+    parse_bg_color: parse-fill,
+    parse_font_style: parse-font,
   )
+  let data = if table-name != none {
+    p.table_to_typst(xlsx, bytes(table-name), cbor.encode(options + flags))
+  } else {
+    p.to_typst_v2(
+      xlsx,
+      cbor.encode(
+        options
+          + flags
+          + (
+            sheet_index: if type(sheet-index) == str { 0 } else { sheet-index },
+            sheet_name: if type(sheet-index) == str { sheet-index } else { none },
+            defined_name: defined-name,
+          ),
+      ),
+    )
+  }
+  let data = decode_output(data, options.output_format)
+  if "error" in data {
+    return report_error(data.error, on-error)
+  }
   parse_excel_table(
-    if sys.version < version(0, 13, 0) {
-      toml.decode(data)
-    } else {
-      toml(data)
-    },
+    data,
     prepend-elems: prepend-elems,
     parse-header: parse-header,
     parse-table-style: parse-table-style,
     parse-stroke: parse-stroke,
+    class-args: class-args,
+    respect-wrap: respect-wrap,
+    use-fonts: use-fonts,
     ..append-args,
   )
 }
 
+/// Read the body rows of a sheet as dictionaries keyed by column header, for layouts other than tables (cards, CVs, invoices).
+///
+/// - xlsx (bytes): The workbook content, e.g. `read("path/to/file.xlsx", encoding: none)`.
+/// - sheet-index (integer, str): The index or the name of the sheet.
+/// - header-rows (integer): Number of header rows; the last one names the keys.
+/// - options (dictionary): Extra options passed to the plugin, the same as for `xlsx-parser`.
+/// -> array of dictionaries
+#let xlsx-records(xlsx, sheet-index: 0, header-rows: 1, options: (:)) = {
+  let options = (output_format: "cbor", structured_errors: true) + options + (keyed_rows: true, header_rows: header-rows)
+  let data = p.to_typst_v2(
+    xlsx,
+    cbor.encode(
+      options
+        + (
+          sheet_index: if type(sheet-index) == str { 0 } else { sheet-index },
+          sheet_name: if type(sheet-index) == str { sheet-index } else { none },
+          parse_alignment: false,
+          parse_border: false,
+          parse_bg_color: false,
+          parse_font_style: false,
+        ),
+    ),
+  )
+  let data = decode_output(data, options.output_format)
+  if "error" in data {
+    return report_error(data.error, none)
+  }
+  data.records
+}
+
+/// Parse every sheet of a workbook in one call, which is much faster than calling `xlsx-parser` once per sheet.
+///
+/// - xlsx (bytes): The workbook content, e.g. `read("path/to/file.xlsx", encoding: none)`.
+/// - preset (string): Bundle of parse options, the same as for `xlsx-parser`.
+/// - parse-header (boolean): Whether to parse the header row.
+/// - options (dictionary): Extra options passed to the plugin, the same as for `xlsx-parser`.
+/// - append-args (arguments): Other arguments for each table.
+/// -> dictionary of tables keyed by sheet name
+#let xlsx-sheets-parser(
+  xlsx,
+  preset: "styled",
+  parse-header: false,
+  options: (:),
+  ..append-args,
+) = {
+  assert(preset in presets, message: "Unknown preset: " + repr(preset))
+  let preset = presets.at(preset)
+  let options = (output_format: "cbor", structured_errors: true) + preset.options + options
+  let data = p.all_sheets_to_typst(
+    xlsx,
+    cbor.encode(
+      options
+        + (
+          parse_alignment: preset.parse-alignment,
+          parse_border: preset.parse-stroke,
+          parse_bg_color: preset.parse-fill,
+          parse_font_style: preset.parse-font,
+        ),
+    ),
+  )
+  let sheets = decode_output(data, options.output_format)
+  if "error" in sheets and type(sheets.error) == dictionary and "code" in sheets.error {
+    return report_error(sheets.error, none)
+  }
+  sheets
+    .pairs()
+    .map(((name, sheet)) => (
+      name,
+      parse_excel_table(
+        sheet,
+        parse-header: parse-header,
+        parse-table-style: preset.parse-table-style,
+        parse-stroke: preset.parse-stroke,
+        ..append-args,
+      ),
+    ))
+    .to-dict()
+}
+
+/// Parse delimited text (CSV, TSV) and return the table.
+///
+/// - data (bytes, str): The file content, e.g. `read("data.csv")`.
+/// - delimiter (str): Field delimiter, use `"\t"` for TSV.
+/// - quote (str): Quote character around fields containing delimiters or line breaks.
+/// - header (boolean): Whether the first line is put into `table.header`.
+/// - on-error (function, none): Called with the error when the conversion fails, the same as for `xlsx-parser`.
+/// - options (dictionary): Extra options passed to the plugin, the same as for `xlsx-parser`.
+/// - args (arguments): Other arguments for the table.
+/// -> table
+#let csv-parser(
+  data,
+  delimiter: ",",
+  quote: "\"",
+  header: false,
+  on-error: none,
+  options: (:),
+  ..args,
+) = {
+  let _ = options.remove("previous_hash", default: none)
+  let options = (output_format: "cbor", structured_errors: true) + options + (delimiter: delimiter, quote: quote, header: header)
+  let data = p.csv_to_typst(
+    if type(data) == str { bytes(data) } else { data },
+    bytes(toml.encode(options)),
+  )
+  let data = decode_output(data, options.output_format)
+  if "error" in data {
+    return report_error(data.error, on-error)
+  }
+  parse_excel_table(data, parse-table-style: false, parse-stroke: false, ..args)
+}
+
+/// List the worksheets of a workbook (xlsx, xlsb, xls or ods).
+///
+/// - data (bytes): The workbook, e.g. `read("test.xlsx", encoding: none)`.
+/// -> array of dictionaries with `index` and `name`
+#let sheet-names(data) = {
+  decode_output(p.sheet_names(data), "toml").sheets
+}
+
+/// Workbook-level metadata (xlsx, xlsb, xls or ods), e.g. for an overview page.
+///
+/// - data (bytes): The workbook, e.g. `read("test.xlsx", encoding: none)`.
+/// -> dictionary with `sheet_count`, `sheets` and `defined_names`
+#let workbook-info(data) = {
+  decode_output(p.workbook_info(data, bytes(toml.encode((output_format: "cbor")))), "cbor")
+}
+
+/// Color scheme of the workbook theme, to reuse the palette of the workbook in the document.
+///
+/// - data (bytes): The workbook, e.g. `read("test.xlsx", encoding: none)`.
+/// -> dictionary of `rgb` colors: `dark1`, `light1`, `dark2`, `light2`, `accent1` to `accent6`, `hyperlink` and `followed_hyperlink`, `none` when unknown
+#let theme-colors(data) = {
+  let colors = decode_output(p.theme_colors(data, bytes(toml.encode((output_format: "cbor")))), "cbor")
+  colors.pairs().map(((name, color)) => (name, if color != none { rgb(color) })).to-dict()
+}
+
+// 将页眉页脚中的占位符替换为页码等内容，未知的文件名与时间保留占位符
+#let header_footer_text(text, sheet, file-name, time) = {
+  let fields = (
+    "{page}": context counter(page).display(),
+    "{pages}": context counter(page).final().first(),
+    "{date}": datetime.today().display(),
+    "{time}": if time == none { "{time}" } else { time.display("[hour]:[minute]") },
+    "{file}": if file-name == none { "{file}" } else { file-name },
+    "{sheet}": if sheet == none { [] } else { sheet },
+  )
+  let parts = text.split(regex("\\{(?:page|pages|date|time|file|sheet)\\}"))
+  let matches = text.matches(regex("\\{(?:page|pages|date|time|file|sheet)\\}"))
+  let content = [#parts.first()]
+  for (m, part) in matches.zip(parts.slice(1)) {
+    content += fields.at(m.text) + [#part]
+  }
+  content
+}
+
+#let header_footer_grid(sections, sheet, file-name, time) = {
+  if sections == none { return none }
+  grid(
+    columns: (1fr, 1fr, 1fr),
+    ..(("left", left), ("center", center), ("right", right)).map(((key, side)) => {
+      let text = sections.at(key, default: none)
+      align(side, if text == none { [] } else { header_footer_text(text, sheet, file-name, time) })
+    })
+  )
+}
+
+/// Arguments for `page` matching the page setup of a sheet: paper size, orientation and margins.
+///
+/// - xlsx (bytes): The workbook content, e.g. `read("path/to/file.xlsx", encoding: none)`.
+/// - sheet-index (int, str): Index or name of the sheet.
+/// - file-name (str, none): File name filled in for the `{file}` field of the header and footer, which is kept as is when `none`.
+/// - time (datetime, none): Time filled in for the `{time}` field, kept as is when `none`, e.g. `datetime(hour: 9, minute: 30, second: 0)`.
+/// -> dictionary, empty when the sheet has no page setup, header or footer
+#let page-setup(xlsx, sheet-index: 0, file-name: none, time: none) = {
+  let data = p.to_typst_v2(
+    xlsx,
+    cbor.encode((
+      output_format: "cbor",
+      structured_errors: true,
+      values_only: true,
+      sheet_index: if type(sheet-index) == str { 0 } else { sheet-index },
+      sheet_name: if type(sheet-index) == str { sheet-index } else { none },
+    )),
+  )
+  let data = decode_output(data, "cbor")
+  if "error" in data {
+    return report_error(data.error, none)
+  }
+  let setup = data.at("page_setup", default: none)
+  let args = (:)
+  if setup != none {
+    if setup.paper != none { args.insert("paper", setup.paper) }
+    if setup.orientation == "landscape" { args.insert("flipped", true) }
+    let margins = setup.margins
+    if margins != none {
+      args.insert("margin", (
+        left: margins.left * 1pt,
+        right: margins.right * 1pt,
+        top: margins.top * 1pt,
+        bottom: margins.bottom * 1pt,
+      ))
+    }
+  }
+  let header_footer = data.at("header_footer", default: none)
+  if header_footer != none {
+    let sheet = if type(sheet-index) == str { sheet-index } else { none }
+    if header_footer.header != none {
+      args.insert("header", header_footer_grid(header_footer.header, sheet, file-name, time))
+    }
+    if header_footer.footer != none {
+      args.insert("footer", header_footer_grid(header_footer.footer, sheet, file-name, time))
+    }
+  }
+  args
+}
+
+/// Convert the `typed` value of a date cell (ISO 8601 text) into a `datetime`.
+///
+/// - value (str): `"2024-01-30"`, `"12:30:00"` or `"2024-01-30T12:30:00"`.
+/// -> datetime
+#let to-datetime(value) = {
+  let (date, time) = if value.contains("T") {
+    value.split("T")
+  } else if value.contains(":") {
+    (none, value)
+  } else {
+    (value, none)
+  }
+  let fields = (:)
+  if date != none {
+    let (year, month, day) = date.split("-").map(int)
+    fields += (year: year, month: month, day: day)
+  }
+  if time != none {
+    let (hour, minute, second) = time.split(":").map(int)
+    fields += (hour: hour, minute: minute, second: second)
+  }
+  datetime(..fields)
+}
+
+/// Read a sheet as the table data `xlsx-parser` renders, to inspect or edit it and write it back with `xlsx-writer`.
+///
+/// - xlsx (bytes): The workbook content, e.g. `read("path/to/file.xlsx", encoding: none)`.
+/// - sheet-index (integer, str): The index or the name of the sheet.
+/// - options (dictionary): Extra options passed to the plugin, the same as for `xlsx-parser`.
+/// -> dictionary
+#let xlsx-table-data(xlsx, sheet-index: 0, options: (:)) = {
+  // TOML 输出不含 none，可以直接交给 xlsx-writer 编码
+  let options = (structured_errors: true) + options + (output_format: "toml")
+  let data = p.to_typst_v2(
+    xlsx,
+    cbor.encode(
+      options
+        + (
+          sheet_index: if type(sheet-index) == str { 0 } else { sheet-index },
+          sheet_name: if type(sheet-index) == str { sheet-index } else { none },
+        ),
+    ),
+  )
+  let data = decode_output(data, options.output_format)
+  if "error" in data {
+    return report_error(data.error, none)
+  }
+  data
+}
+
+/// Write table data back into an xlsx file.
+///
+/// - data (dictionary): Table data as returned by `xlsx-table-data`. Every field may be left out; the writer uses `dimensions`, `header`, `rows`, `footer`, `merged_cells` and `styles`.
+/// -> bytes
+#let xlsx-writer(data) = p.from_typst(bytes(toml.encode(data)))
+
 /// Parse table pre-parsed by spreet and return the table. Styles in the table will be ignored but the cell content will be kept. Extra arguments can be passed to the table.
 ///
 /// - dict (dictionary): spreet parsed table.